@@ -0,0 +1,241 @@
+//! Итоги по календарным периодам: пополнения, списания и объём переводов.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::str::FromStr;
+use crate::calendar::{self, Date};
+use crate::compare::ReportFormat;
+use crate::stats::Totals;
+use crate::{json, Transaction, TxType, TxStatus, Result, ParserError};
+
+/// Сдвиг московского времени от UTC, минуты.
+pub const MOSCOW_OFFSET_MINUTES: i32 = 180;
+
+/// Календарный период.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    /// Неделя с понедельника (ISO 8601).
+    Week,
+    Month,
+}
+
+impl Period {
+    pub fn name(self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+
+    /// Первый день периода, в который попадает дата.
+    pub fn start(self, date: Date) -> Date {
+        match self {
+            Period::Day => date,
+            Period::Week => date.start_of_week(),
+            Period::Month => date.start_of_month(),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "day" | "daily" => Ok(Period::Day),
+            "week" | "weekly" => Ok(Period::Week),
+            "month" | "monthly" => Ok(Period::Month),
+            _ => Err(ParserError::Format(format!("Неизвестный период: {}", s))),
+        }
+    }
+}
+
+/// Дополнительная группировка внутри периода.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GroupBy {
+    #[default]
+    None,
+    Type,
+    Status,
+    /// Транзакция учитывается у отправителя и у получателя (кроме `0`).
+    User,
+}
+
+impl FromStr for GroupBy {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(GroupBy::None),
+            "type" => Ok(GroupBy::Type),
+            "status" => Ok(GroupBy::Status),
+            "user" => Ok(GroupBy::User),
+            _ => Err(ParserError::Format(format!("Неизвестная группировка: {}", s))),
+        }
+    }
+}
+
+/// Значение группы.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Group {
+    All,
+    Type(TxType),
+    Status(TxStatus),
+    User(u64),
+}
+
+impl Group {
+    fn label(&self) -> String {
+        match self {
+            Group::All => String::new(),
+            Group::Type(t) => t.as_str().to_string(),
+            Group::Status(s) => s.as_str().to_string(),
+            Group::User(u) => u.to_string(),
+        }
+    }
+}
+
+/// Итоги одного периода и группы.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeriodTotals {
+    pub deposits: Totals,
+    pub withdrawals: Totals,
+    pub transfers: Totals,
+}
+
+/// Параметры агрегации.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AggregateOptions {
+    pub period: Period,
+    pub group_by: GroupBy,
+    /// Сдвиг местного времени от UTC, минуты.
+    pub utc_offset_minutes: i32,
+    /// Учитывать также `FAILURE` и `PENDING`; по умолчанию — только `SUCCESS`.
+    pub all_statuses: bool,
+}
+
+impl Default for AggregateOptions {
+    fn default() -> Self {
+        AggregateOptions { period: Period::Day, group_by: GroupBy::None, utc_offset_minutes: 0, all_statuses: false }
+    }
+}
+
+/// Временной ряд итогов.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Aggregation {
+    options: AggregateOptions,
+    rows: BTreeMap<(Date, Group), PeriodTotals>,
+}
+
+impl Aggregation {
+    pub fn new(options: AggregateOptions) -> Self {
+        Aggregation { options, rows: BTreeMap::new() }
+    }
+
+    /// Учитывает транзакцию; без `all_statuses` — только `SUCCESS`.
+    pub fn add(&mut self, tx: &Transaction) {
+        if !self.options.all_statuses && tx.status != TxStatus::Success {
+            return;
+        }
+        let date = Date::from_timestamp(tx.timestamp, self.options.utc_offset_minutes);
+        let start = self.options.period.start(date);
+        let groups: Vec<Group> = match self.options.group_by {
+            GroupBy::None => vec![Group::All],
+            GroupBy::Type => vec![Group::Type(tx.tx_type)],
+            GroupBy::Status => vec![Group::Status(tx.status)],
+            GroupBy::User => {
+                let mut users = vec![tx.from_user_id, tx.to_user_id];
+                users.retain(|&u| u != 0);
+                users.dedup();
+                users.into_iter().map(Group::User).collect()
+            }
+        };
+        for group in groups {
+            let row = self.rows.entry((start, group)).or_default();
+            match tx.tx_type {
+                TxType::Deposit => row.deposits.add(tx.amount),
+                TxType::Withdrawal => row.withdrawals.add(tx.amount),
+                TxType::Transfer => row.transfers.add(tx.amount),
+            }
+        }
+    }
+
+    pub fn add_all<I>(&mut self, transactions: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<Transaction>>,
+    {
+        for tx in transactions {
+            self.add(&tx?);
+        }
+        Ok(())
+    }
+
+    /// Строки по возрастанию начала периода, затем группы.
+    pub fn rows(&self) -> &BTreeMap<(Date, Group), PeriodTotals> {
+        &self.rows
+    }
+
+    /// CSV (`PERIOD_START[,GROUP],DEPOSIT_COUNT,...`) или JSON.
+    pub fn write<W: Write>(&self, mut writer: W, format: ReportFormat) -> Result<()> {
+        let grouped = self.options.group_by != GroupBy::None;
+        match format {
+            ReportFormat::Csv => {
+                write!(writer, "PERIOD_START")?;
+                if grouped {
+                    write!(writer, ",GROUP")?;
+                }
+                writeln!(
+                    writer,
+                    ",DEPOSIT_COUNT,DEPOSIT_SUM,WITHDRAWAL_COUNT,WITHDRAWAL_SUM,TRANSFER_COUNT,TRANSFER_SUM"
+                )?;
+                for ((date, group), t) in &self.rows {
+                    write!(writer, "{}", date)?;
+                    if grouped {
+                        write!(writer, ",{}", group.label())?;
+                    }
+                    writeln!(
+                        writer,
+                        ",{},{},{},{},{},{}",
+                        t.deposits.count,
+                        t.deposits.sum,
+                        t.withdrawals.count,
+                        t.withdrawals.sum,
+                        t.transfers.count,
+                        t.transfers.sum
+                    )?;
+                }
+            }
+            ReportFormat::Json => {
+                let totals = |t: &Totals| format!("{{\"count\":{},\"sum\":{}}}", t.count, t.sum);
+                let series: Vec<String> = self
+                    .rows
+                    .iter()
+                    .map(|((date, group), t)| {
+                        let group = if grouped { format!(",\"group\":{}", json::string(&group.label())) } else { String::new() };
+                        format!(
+                            "{{\"period_start\":{}{},\"deposits\":{},\"withdrawals\":{},\"transfers\":{}}}",
+                            json::string(&date.to_string()),
+                            group,
+                            totals(&t.deposits),
+                            totals(&t.withdrawals),
+                            totals(&t.transfers)
+                        )
+                    })
+                    .collect();
+                writeln!(
+                    writer,
+                    "{{\"period\":{},\"utc_offset\":{},\"series\":[{}]}}",
+                    json::string(self.options.period.name()),
+                    json::string(&calendar::format_utc_offset(self.options.utc_offset_minutes)),
+                    series.join(",")
+                )?;
+            }
+            ReportFormat::Text => {
+                return Err(ParserError::Format("Итоги выводятся как csv или json".into()));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+//! Безопасное дописывание в файлы YPBankBin и YPBankText.
+//!
+//! При открытии файл проверяется целиком. Оборванная последняя запись (сбой
+//! посреди записи) обрезается или даёт ошибку — по [`AppendOptions`]; битые
+//! полные записи — всегда ошибка. Обрывом считается бинарная запись, чей
+//! `RECORD_SIZE` согласуется с `DESC_LEN`, и текст, оборванный посреди строки. В YPBankBin v2 новые записи пишутся на
+//! место трейлера, и после каждой пачки трейлер пишется заново.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::bin_format::{self, FileHeader, Trailer, Version, FLAG_INDEX, FLAG_RECORD_CRC, FLAG_TRAILER};
+use crate::bin_view::TransactionRef;
+use crate::crc32::crc32;
+use crate::format::Format;
+use crate::{text_format, Transaction, Result, ParserError};
+
+/// Размер трейлера v2 вместе с сигнатурой.
+const TRAILER_LEN: u64 = 32;
+
+/// Параметры дописывания.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AppendOptions {
+    /// Обрезать оборванную последнюю запись; иначе — ошибка.
+    pub truncate_torn: bool,
+    /// `fsync` после каждой пачки.
+    pub sync: bool,
+    /// Версия нового бинарного файла.
+    pub bin_version: Version,
+}
+
+/// Открытый на дописывание файл.
+pub struct Appender {
+    file: File,
+    format: Format,
+    sync: bool,
+    records: usize,
+    /// Заголовок и итоги файла v2 с трейлером.
+    v2: Option<(FileHeader, Trailer)>,
+    /// Текст без пустой строки после последнего блока.
+    needs_separator: bool,
+    truncated: u64,
+}
+
+/// Что нашлось при проверке.
+struct Scan {
+    records: usize,
+    /// Конец последней целой записи (или трейлера).
+    valid_len: u64,
+    v2: Option<(FileHeader, Trailer)>,
+    trailer_found: bool,
+    needs_separator: bool,
+}
+
+impl Appender {
+    /// Открывает или создаёт файл. Поддерживаются `bin` (без индекса) и `text`.
+    pub fn open(path: &Path, format: Format, options: &AppendOptions) -> Result<Self> {
+        if !matches!(format, Format::Bin | Format::Text) {
+            return Err(ParserError::Format(format!("Дописывание не поддерживается для формата {:?}", format)));
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if format == Format::Bin && options.bin_version == Version::V2 && file.metadata()?.len() == 0 {
+            let v2 = bin_format::WriteOptions { version: Version::V2, index: false };
+            bin_format::Writer::with_options(&mut file, &v2)?.finish()?;
+        }
+
+        let len = file.metadata()?.len();
+        file.seek(SeekFrom::Start(0))?;
+        let scan = match format {
+            Format::Bin => scan_bin(BufReader::new(&file), len)?,
+            _ => scan_text(BufReader::new(&file))?,
+        };
+        let missing_trailer = scan.v2.is_some() && !scan.trailer_found;
+        if scan.valid_len < len || missing_trailer {
+            if !options.truncate_torn && scan.valid_len == len {
+                return Err(ParserError::Format("Файл оборвался: нет трейлера".into()));
+            }
+            if !options.truncate_torn {
+                return Err(ParserError::Format(format!(
+                    "Последняя запись оборвана: {} байт после смещения {}",
+                    len - scan.valid_len,
+                    scan.valid_len
+                )));
+            }
+            file.set_len(scan.valid_len)?;
+        }
+
+        let mut appender = Appender {
+            file,
+            format,
+            sync: options.sync,
+            records: scan.records,
+            v2: scan.v2,
+            needs_separator: scan.needs_separator,
+            truncated: len - scan.valid_len,
+        };
+        if scan.trailer_found {
+            appender.file.seek(SeekFrom::Start(scan.valid_len - TRAILER_LEN))?;
+        } else {
+            appender.file.seek(SeekFrom::Start(scan.valid_len))?;
+            if missing_trailer {
+                // Восстанавливаем трейлер, чтобы файл снова читался.
+                appender.append(&[])?;
+            }
+        }
+        Ok(appender)
+    }
+
+    /// Сколько целых записей в файле.
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Сколько байт оборванной записи обрезано при открытии.
+    pub fn truncated(&self) -> u64 {
+        self.truncated
+    }
+
+    /// Дописывает пачку; после возврата она сброшена на диск, если задан `sync`.
+    pub fn append(&mut self, transactions: &[Transaction]) -> Result<()> {
+        let buffered = BufWriter::new(&mut self.file);
+        match (self.format, &mut self.v2) {
+            (Format::Bin, Some((header, totals))) => {
+                let mut writer = bin_format::Writer::continuing(buffered, header.clone(), *totals);
+                for tx in transactions {
+                    writer.write(tx)?;
+                    totals.add(tx);
+                }
+                writer.finish()?;
+                // Следующая пачка пишется поверх трейлера.
+                self.file.seek(SeekFrom::Current(-(TRAILER_LEN as i64)))?;
+            }
+            (Format::Bin, None) => {
+                let mut writer = bin_format::Writer::new(buffered);
+                for tx in transactions {
+                    writer.write(tx)?;
+                }
+                writer.finish()?;
+            }
+            _ => {
+                let mut buffered = buffered;
+                if self.needs_separator && !transactions.is_empty() {
+                    writeln!(buffered)?;
+                    self.needs_separator = false;
+                }
+                let mut writer = text_format::Writer::appending(buffered, self.records);
+                for tx in transactions {
+                    writer.write(tx)?;
+                }
+                writer.finish()?;
+            }
+        }
+        if self.sync {
+            self.file.sync_data()?;
+        }
+        self.records += transactions.len();
+        Ok(())
+    }
+}
+
+fn scan_bin<R: Read>(mut reader: R, len: u64) -> Result<Scan> {
+    let mut scan = Scan { records: 0, valid_len: 0, v2: None, trailer_found: false, needs_separator: false };
+    if len >= 4 {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic == bin_format::FILE_MAGIC {
+            let header = bin_format::read_header(&mut reader)?;
+            if header.has(FLAG_INDEX) {
+                return Err(ParserError::Format("Дописывание в файл с индексом не поддерживается".into()));
+            }
+            scan.valid_len = header.encoded_len();
+            if header.has(FLAG_TRAILER) {
+                scan.v2 = Some((header, Trailer::default()));
+            } else {
+                return Err(ParserError::Format("Дописывание в файл v2 без трейлера не поддерживается".into()));
+            }
+        } else {
+            return scan_bin_records(&mut magic.as_slice().chain(reader), len, scan);
+        }
+    }
+    scan_bin_records(&mut reader, len, scan)
+}
+
+/// Записи с позиции `scan.valid_len` до конца файла длиной `len`.
+fn scan_bin_records<R: Read>(reader: &mut R, len: u64, mut scan: Scan) -> Result<Scan> {
+    let crc = scan.v2.as_ref().is_some_and(|(h, _)| h.has(FLAG_RECORD_CRC));
+    let mut buf = Vec::new();
+    loop {
+        let pos = scan.valid_len;
+        let remaining = len - pos;
+        if remaining < 8 {
+            // Пусто или обрывок заголовка записи.
+            return Ok(scan);
+        }
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if &magic == bin_format::TRAILER_MAGIC {
+            if let Some((_, totals)) = &scan.v2 {
+                if remaining < TRAILER_LEN {
+                    return Ok(scan);
+                }
+                let trailer = bin_format::read_trailer(reader)?;
+                if trailer != *totals {
+                    return Err(ParserError::Format("Трейлер не совпадает с данными".into()));
+                }
+                if remaining > TRAILER_LEN {
+                    return Err(ParserError::Format("Данные после трейлера".into()));
+                }
+                scan.valid_len += TRAILER_LEN;
+                scan.trailer_found = true;
+                return Ok(scan);
+            }
+        }
+        if &magic != bin_format::MAGIC {
+            return Err(ParserError::Format(format!("Неверный заголовок MAGIC на смещении {}", pos)));
+        }
+
+        let mut size = [0u8; 4];
+        reader.read_exact(&mut size)?;
+        let size = u32::from_be_bytes(size) as u64;
+        let record_len = 8 + size + if crc { 4 } else { 0 };
+        if remaining < record_len {
+            // Обрыв, только если RECORD_SIZE согласуется с DESC_LEN из
+            // записанной части; иначе заголовок записи испорчен.
+            buf.resize((remaining - 8) as usize, 0);
+            reader.read_exact(&mut buf)?;
+            let consistent = match buf.get(42..46) {
+                Some(desc_len) => size == 46 + u32::from_be_bytes(desc_len.try_into().unwrap_or_default()) as u64,
+                None => size >= 46,
+            };
+            if !consistent {
+                return Err(ParserError::Format(format!("Неверный RECORD_SIZE записи на смещении {}", pos)));
+            }
+            return Ok(scan);
+        }
+        buf.resize(size as usize, 0);
+        reader.read_exact(&mut buf)?;
+        let tx = TransactionRef::new(&buf)
+            .and_then(|tx| tx.to_transaction())
+            .map_err(|e| ParserError::Format(format!("Запись на смещении {} повреждена: {}", pos, e)))?;
+        if crc {
+            let mut expected = [0u8; 4];
+            reader.read_exact(&mut expected)?;
+            if crc32(&buf) != u32::from_be_bytes(expected) {
+                return Err(ParserError::Format(format!("Контрольная сумма записи на смещении {} не совпадает", pos)));
+            }
+        }
+        if let Some((_, totals)) = &mut scan.v2 {
+            totals.add(&tx);
+        }
+        scan.records += 1;
+        scan.valid_len += record_len;
+    }
+}
+
+fn scan_text<R: BufRead>(mut reader: R) -> Result<Scan> {
+    let mut scan = Scan { records: 0, valid_len: 0, v2: None, trailer_found: false, needs_separator: false };
+    let mut block = Vec::new();
+    let mut pos = 0u64;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)? as u64;
+        if read == 0 {
+            break;
+        }
+        if line.last() != Some(&b'\n') {
+            // Недописанная строка — хвост оборван.
+            return Ok(scan);
+        }
+        pos += read;
+        let text = String::from_utf8(line.clone())?;
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            if !block.is_empty() {
+                text_format::parse_block(&block)
+                    .map_err(|e| ParserError::Format(format!("Запись {} повреждена: {}", scan.records + 1, e)))?;
+                block.clear();
+                scan.records += 1;
+            }
+            scan.valid_len = pos;
+        } else if !trimmed.starts_with('#') {
+            block.push(text);
+        }
+    }
+    // Последний блок без пустой строки после него: строки дописаны целиком,
+    // поэтому неразбираемый блок — повреждение, а не обрыв.
+    if !block.is_empty() {
+        text_format::parse_block(&block)
+            .map_err(|e| ParserError::Format(format!("Запись {} повреждена: {}", scan.records + 1, e)))?;
+        scan.records += 1;
+        scan.valid_len = pos;
+        scan.needs_separator = true;
+    }
+    Ok(scan)
+}
@@ -0,0 +1,221 @@
+//! Пакетная конвертация: каталоги, шаблоны `*`/`?`, несколько потоков.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use crate::filter::Filter;
+use crate::format::Format;
+use crate::{bin_format, Result, ParserError};
+
+/// Один файл: откуда читаем и куда пишем.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchJob {
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+/// Параметры конвертации одного файла.
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    /// Записывать только подходящие транзакции.
+    pub filter: Option<Filter>,
+    /// Версия и индекс бинарного формата на выходе.
+    pub bin: bin_format::WriteOptions,
+}
+
+/// Итог по одному файлу: число записей или ошибка.
+#[derive(Debug)]
+pub struct BatchOutcome {
+    pub job: BatchJob,
+    pub result: Result<usize>,
+}
+
+/// Раскрывает входы: файл берётся как есть, из каталога — файлы с расширением
+/// `format`, шаблон с `*`/`?` сопоставляется с именами файлов в своём каталоге.
+pub fn collect_inputs(specs: &[String], format: Format) -> Result<Vec<PathBuf>> {
+    let mut inputs = Vec::new();
+    for spec in specs {
+        let path = Path::new(spec);
+        if path.is_dir() {
+            let mut found: Vec<PathBuf> = list_files(path)?
+                .into_iter()
+                .filter(|p| Format::from_path(p) == Some(format))
+                .collect();
+            found.sort();
+            inputs.extend(found);
+        } else if is_pattern(spec) {
+            let dir = match path.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p,
+                _ => Path::new("."),
+            };
+            let pattern = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .ok_or_else(|| ParserError::Format(format!("Неверный шаблон: {}", spec)))?;
+            if is_pattern(&dir.to_string_lossy()) {
+                return Err(ParserError::Format(format!(
+                    "Шаблоны допускаются только в имени файла: {}", spec
+                )));
+            }
+            let mut found: Vec<PathBuf> = list_files(dir)?
+                .into_iter()
+                .filter(|p| {
+                    p.file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| wildcard_match(pattern, n))
+                })
+                .collect();
+            found.sort();
+            inputs.extend(found);
+        } else {
+            inputs.push(path.to_path_buf());
+        }
+    }
+    Ok(inputs)
+}
+
+/// Сопоставляет пары вход → `output_dir/<имя>.<расширение>`.
+/// Два входа с одинаковым именем дали бы один выходной файл — это ошибка.
+pub fn plan(inputs: &[PathBuf], output_dir: &Path, format: Format) -> Result<Vec<BatchJob>> {
+    let mut seen = HashSet::new();
+    let mut jobs = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let stem = input
+            .file_stem()
+            .ok_or_else(|| ParserError::Format(format!("Нет имени файла: {}", input.display())))?;
+        let mut name = stem.to_os_string();
+        name.push(".");
+        name.push(format.extension());
+        let output = output_dir.join(name);
+        if !seen.insert(output.clone()) {
+            return Err(ParserError::Format(format!(
+                "Несколько входов записываются в один файл: {}",
+                output.display()
+            )));
+        }
+        jobs.push(BatchJob { input: input.clone(), output });
+    }
+    Ok(jobs)
+}
+
+/// Конвертирует один файл потоком, возвращает число записанных записей.
+/// Запись идёт во временный файл рядом с результатом, который
+/// переименовывается только после успеха: при ошибке выхода не остаётся.
+pub fn convert_file(
+    job: &BatchJob,
+    input_format: Format,
+    output_format: Format,
+    options: &ConvertOptions,
+) -> Result<usize> {
+    let mut temp = job.output.clone().into_os_string();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let result = write_converted(job, &temp, input_format, output_format, options)
+        .and_then(|count| Ok(fs::rename(&temp, &job.output).map(|_| count)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn write_converted(
+    job: &BatchJob,
+    temp: &Path,
+    input_format: Format,
+    output_format: Format,
+    options: &ConvertOptions,
+) -> Result<usize> {
+    let reader = input_format.file_reader(File::open(&job.input)?, options.filter.clone());
+    let output = BufWriter::new(File::create(temp)?);
+    let mut writer = output_format.writer_with(output, &options.bin)?;
+    let mut count = 0;
+    for tx in reader {
+        writer.write(&tx?)?;
+        count += 1;
+    }
+    writer.finish()?;
+    Ok(count)
+}
+
+/// Конвертирует все файлы в `threads` потоках. Ошибка одного файла не
+/// останавливает остальные; итоги возвращаются в порядке `jobs`.
+pub fn run(
+    jobs: Vec<BatchJob>,
+    input_format: Format,
+    output_format: Format,
+    threads: usize,
+    options: &ConvertOptions,
+) -> Vec<BatchOutcome> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<usize>>>> =
+        Mutex::new(jobs.iter().map(|_| None).collect());
+    let workers = threads.clamp(1, jobs.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(idx) else { break };
+                let result = convert_file(job, input_format, output_format, options);
+                if let Ok(mut slots) = results.lock() {
+                    slots[idx] = Some(result);
+                }
+            });
+        }
+    });
+
+    let slots = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    jobs.into_iter()
+        .zip(slots)
+        .map(|(job, result)| BatchOutcome {
+            job,
+            result: result.unwrap_or_else(|| {
+                Err(ParserError::Format("Файл не был обработан".into()))
+            }),
+        })
+        .collect()
+}
+
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn is_pattern(s: &str) -> bool {
+    s.contains('*') || s.contains('?')
+}
+
+/// `*` — любая последовательность символов, `?` — ровно один символ.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    let (mut pi, mut ni) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((sp, sn)) = star {
+            pi = sp + 1;
+            ni = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,40 @@
+use std::io;
+use parser::aggregate::{AggregateOptions, Aggregation};
+use parser::calendar::parse_utc_offset;
+use parser::compare::ReportFormat;
+use parser::filter::Filter;
+use parser::{cli, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut input_file = None;
+    let mut input_format = None;
+    let mut out_fmt = ReportFormat::Csv;
+    let mut options = AggregateOptions::default();
+    let mut filter = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input_file = Some(args.value()?),
+            "--input-format" => input_format = Some(args.value()?),
+            "--output-format" => out_fmt = cli::table_format(&args.value()?, "--output-format")?,
+            "--period" => options.period = args.value()?.parse()?,
+            "--group-by" => options.group_by = args.value()?.parse()?,
+            "--utc-offset" => options.utc_offset_minutes = parse_utc_offset(&args.value()?)?,
+            "--all-statuses" => options.all_statuses = true,
+            "--filter" => filter = Some(Filter::parse(&args.value()?)?),
+            _ => {}
+        }
+    }
+
+    let input_path = input_file.ok_or_else(|| cli::usage("--input <файл>"))?;
+    let in_fmt: Format = input_format.as_deref().unwrap_or("text").parse()?;
+
+    let file = cli::open_file(input_path, "--input")?;
+    let mut aggregation = Aggregation::new(options);
+    aggregation.add_all(in_fmt.file_reader(file, filter))?;
+    aggregation.write(io::stdout().lock(), out_fmt)?;
+
+    Ok(())
+}
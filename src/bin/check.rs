@@ -0,0 +1,37 @@
+use std::io::{self, BufReader};
+use std::process;
+use parser::compare::ReportFormat;
+use parser::integrity::{check, IntegrityOptions};
+use parser::{cli, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut input_file = None;
+    let mut input_format = None;
+    let mut report_format = ReportFormat::Text;
+    let mut options = IntegrityOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input_file = Some(args.value()?),
+            "--input-format" => input_format = Some(args.value()?),
+            "--report" => report_format = cli::report_format(&args.value()?, "--report")?,
+            "--skip" => options = options.skip(&args.value()?)?,
+            _ => {}
+        }
+    }
+
+    let input_path = input_file.ok_or_else(|| cli::usage("--input <файл>"))?;
+    let in_fmt: Format = input_format.as_deref().unwrap_or("text").parse()?;
+
+    let file = cli::open_file(input_path, "--input")?;
+    let report = check(in_fmt.reader(BufReader::new(file)), &options)?;
+    report.write(io::stdout().lock(), report_format)?;
+
+    if !report.is_clean() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
@@ -1,99 +1,93 @@
-use std::env;
-use std::fs::File;
-use parser::{text_format, csv_format, bin_format, Result, Transaction};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use std::process;
+use parser::compare::{compare_by_key, compare_sorted_each, CompareOptions, Normalization, Report, ReportFormat, StreamReport};
+use parser::external_sort::{self, SortOptions};
+use parser::filter::Filter;
+use parser::{cli, Field, Format, Result};
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut args = cli::Args::from_env();
 
     let mut file1_path = None;
     let mut format1 = None;
     let mut file2_path = None;
     let mut format2 = None;
+    let mut key = None;
+    let mut report_format = None;
+    let mut options = CompareOptions::default();
+    let mut external = false;
+    let mut sort_options = SortOptions::default();
+    let mut filter = None;
 
-    let mut i = 1;
-    while i < args.len() {
-        match args[i].as_str() {
-            "--file1" => {
-                if i + 1 < args.len() {
-                    file1_path = Some(&args[i + 1]);
-                    i += 1;
-                }
-            }
-            "--format1" => {
-                if i + 1 < args.len() {
-                    format1 = Some(&args[i + 1]);
-                    i += 1;
-                }
-            }
-            "--file2" => {
-                if i + 1 < args.len() {
-                    file2_path = Some(&args[i + 1]);
-                    i += 1;
-                }
-            }
-            "--format2" => {
-                if i + 1 < args.len() {
-                    format2 = Some(&args[i + 1]);
-                    i += 1;
-                }
-            }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file1" => file1_path = Some(args.value()?),
+            "--format1" => format1 = Some(args.value()?),
+            "--file2" => file2_path = Some(args.value()?),
+            "--format2" => format2 = Some(args.value()?),
+            "--key" => key = Some(args.value()?),
+            "--report" => report_format = Some(args.value()?),
+            "--ignore" => options.ignore = Field::parse_list(&args.value()?)?,
+            "--timestamp-tolerance" => options.timestamp_tolerance = args.value()?.parse()?,
+            "--amount-tolerance" => options.amount_tolerance = args.value()?.parse()?,
+            "--normalize" => options.normalize = Normalization::parse(&args.value()?)?,
+            "--external" => external = true,
+            "--memory-budget" => sort_options.memory_budget = external_sort::parse_size(&args.value()?)?,
+            "--temp-dir" => sort_options.temp_dir = PathBuf::from(args.value()?),
+            "--filter" => filter = Some(Filter::parse(&args.value()?)?),
             _ => {}
         }
-        i += 1;
     }
 
-    let file1_path = file1_path.ok_or_else(|| parser::ParserError::Format("Укажите --file1".into()))?;
-    let file2_path = file2_path.ok_or_else(|| parser::ParserError::Format("Укажите --file2".into()))?;
+    let file1_path = file1_path.as_deref().ok_or_else(|| parser::ParserError::Format("Укажите --file1".into()))?;
+    let file2_path = file2_path.as_deref().ok_or_else(|| parser::ParserError::Format("Укажите --file2".into()))?;
     
-    let fmt1 = format1.map(|s| s.as_str()).unwrap_or("text");
-    let fmt2 = format2.map(|s| s.as_str()).unwrap_or("text");
+    let fmt1: Format = format1.as_deref().unwrap_or("text").parse()?;
+    let fmt2: Format = format2.as_deref().unwrap_or("text").parse()?;
 
-    let txs1 = load_transactions(file1_path, fmt1, "--file1")?;
-    let txs2 = load_transactions(file2_path, fmt2, "--file2")?;
-
-    if txs1.len() != txs2.len() {
-        println!(
-            "The number of transactions differs: {} in first file vs {} in second file.",
-            txs1.len(),
-            txs2.len()
-        );
-        return Ok(());
+    let key = match key {
+        Some(k) => Field::parse_list(&k)?,
+        None => vec![Field::TxId],
+    };
+    if key.is_empty() {
+        return Err(parser::ParserError::Format("--key не может быть пустым".into()));
     }
 
-    let mut identical = true;
-    for (i, (t1, t2)) in txs1.iter().zip(txs2.iter()).enumerate() {
-        if t1 != t2 {
-            println!(
-                "Transaction at index {} does not match (TX_ID: {}).",
-                i, t1.tx_id
-            );
-            println!("  File 1: {:?}", t1);
-            println!("  File 2: {:?}", t2);
-            identical = false;
-        }
-    }
+    let report_format: ReportFormat = match report_format {
+        Some(r) => r.parse()?,
+        None => ReportFormat::Text,
+    };
+
+    let identical = if external {
+        // Первый файл может остаться отсортированным в памяти, пока сортируется
+        // второй, поэтому каждому достаётся половина бюджета.
+        sort_options.memory_budget = (sort_options.memory_budget / 2).max(1);
+        let sorted1 = external_sort::sort(cli::open_transactions(file1_path, fmt1, "--file1", filter.clone())?, &key, &sort_options)?;
+        let sorted2 = external_sort::sort(cli::open_transactions(file2_path, fmt2, "--file2", filter.clone())?, &key, &sort_options)?;
+        // Различия пишутся сразу, не накапливаясь в памяти.
+        let stdout = io::stdout().lock();
+        let mut report = StreamReport::new(BufWriter::new(stdout), report_format, &key, file1_path, file2_path)?;
+        let summary = compare_sorted_each(sorted1, sorted2, &key, &options, |diff| report.difference(&diff))?;
+        report.finish(&summary)?;
+        summary.is_identical()
+    } else {
+        let txs1 = cli::load_transactions(file1_path, fmt1, "--file1", filter.clone())?;
+        let txs2 = cli::load_transactions(file2_path, fmt2, "--file2", filter.clone())?;
+        let cmp = compare_by_key(txs1, txs2, &key, &options);
+        let report = Report {
+            comparison: &cmp,
+            key: &key,
+            first_name: file1_path,
+            second_name: file2_path,
+        };
+        report.write(io::stdout().lock(), report_format)?;
+        cmp.is_identical()
+    };
 
-    if identical {
-        println!(
-            "The transaction records in '{}' and '{}' are identical.",
-            file1_path, file2_path
-        );
+    if !identical {
+        process::exit(1);
     }
 
     Ok(())
 }
-
-fn load_transactions(path: &str, format: &str, arg_name: &str) -> Result<Vec<Transaction>> {
-    let file = File::open(path).map_err(|e| {
-        parser::ParserError::Format(format!(
-            "Не удалось открыть файл, указанный в {} '{}': {}",
-            arg_name, path, e
-        ))
-    })?;
-    match format {
-        "csv" => csv_format::from_read(file),
-        "bin" | "binary" => bin_format::from_read(file),
-        "text" => text_format::from_read(file),
-        _ => Err(parser::ParserError::Format(format!("Неизвестный формат: {}", format))),
-    }
-}
@@ -0,0 +1,30 @@
+use std::io::{self, BufWriter};
+use std::process;
+use parser::inspect::{inspect, InspectOptions};
+use parser::{cli, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut input_file = None;
+    let mut options = InspectOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input_file = Some(args.value()?),
+            "--skip" => options.skip = args.value()?.parse()?,
+            "--limit" => options.limit = Some(args.value()?.parse()?),
+            _ => {}
+        }
+    }
+
+    let input_path = input_file.ok_or_else(|| cli::usage("--input <файл>"))?;
+    let input = cli::open_file(input_path, "--input")?;
+    let summary = inspect(input, BufWriter::new(io::stdout().lock()), &options)?;
+
+    if summary.issues > 0 {
+        process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::process;
+use parser::compare::ReportFormat;
+use parser::ledger::{describe_issue, verify_snapshot, write_balances, Ledger, LedgerOptions, Snapshot};
+use parser::{cli, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut inputs = Vec::new();
+    let mut input_format = None;
+    let mut output_format = None;
+    let mut options = LedgerOptions::default();
+    let mut snapshot_in = None;
+    let mut snapshot_out = None;
+    let mut verify = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => inputs.push(args.value()?),
+            "--input-format" => input_format = Some(args.value()?),
+            "--output-format" => output_format = Some(args.value()?),
+            "--as-of" => options.as_of = Some(args.value()?.parse()?),
+            "--include-pending" => options.include_pending = true,
+            "--snapshot" => snapshot_in = Some(args.value()?),
+            "--write-snapshot" => snapshot_out = Some(args.value()?),
+            "--verify" => verify = Some(args.value()?),
+            _ => {}
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err(cli::usage("--input <файл>"));
+    }
+    let in_fmt: Format = input_format.as_deref().unwrap_or("text").parse()?;
+    let out_fmt: ReportFormat = output_format.as_deref().unwrap_or("text").parse()?;
+
+    let history = inputs
+        .into_iter()
+        .map(|path| cli::open_transactions(path, in_fmt, "--input", None))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten();
+
+    if let Some(path) = verify {
+        let snapshot = load_snapshot(&path)?;
+        let mismatches = verify_snapshot(&snapshot, history)?;
+        if mismatches.is_empty() {
+            println!("Snapshot '{}' matches full recomputation.", path);
+            return Ok(());
+        }
+        for m in &mismatches {
+            println!("USER {}: snapshot {}, recomputed {}", m.user_id, m.snapshot, m.recomputed);
+        }
+        println!("{} balances differ.", mismatches.len());
+        process::exit(1);
+    }
+
+    let mut ledger = match snapshot_in {
+        Some(path) => {
+            let snapshot = load_snapshot(&path)?;
+            if options.include_pending && !snapshot.include_pending {
+                return Err(parser::ParserError::Format(
+                    "Снимок рассчитан без PENDING, --include-pending неприменим".into(),
+                ));
+            }
+            Ledger::from_snapshot(snapshot, options.as_of)
+        }
+        None => Ledger::new(options),
+    };
+    ledger.apply_all(history)?;
+
+    if let Some(path) = snapshot_out {
+        let file = File::create(path)?;
+        ledger.snapshot().write(BufWriter::new(file))?;
+    }
+
+    for issue in ledger.issues() {
+        eprintln!("{}", describe_issue(issue));
+    }
+    write_balances(io::stdout().lock(), ledger.balances(), out_fmt)?;
+
+    Ok(())
+}
+
+fn load_snapshot(path: &str) -> Result<Snapshot> {
+    let file = File::open(path).map_err(|e| {
+        parser::ParserError::Format(format!("Не удалось открыть снимок '{}': {}", path, e))
+    })?;
+    Snapshot::read(file)
+}
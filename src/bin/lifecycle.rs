@@ -0,0 +1,45 @@
+use std::io::{self, BufReader};
+use std::process;
+use parser::compare::ReportFormat;
+use parser::format;
+use parser::lifecycle::{self, LifecycleOptions, Tracker};
+use parser::{cli, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut inputs = Vec::new();
+    let mut input_format = None;
+    let mut report_format = ReportFormat::Text;
+    let mut options = LifecycleOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => inputs.push(args.value()?),
+            "--input-format" => input_format = Some(args.value()?.parse::<Format>()?),
+            "--stuck-after" => options.stuck_after = lifecycle::parse_duration(&args.value()?)?,
+            "--as-of" => options.as_of = Some(args.value()?.parse()?),
+            "--report" => report_format = cli::report_format(&args.value()?, "--report")?,
+            _ => {}
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err(cli::usage("--input <файл>"));
+    }
+
+    let mut tracker = Tracker::new();
+    for spec in &inputs {
+        let (path, fmt) = format::parse_input(spec, input_format)?;
+        let file = cli::open_file(&path, "--input")?;
+        tracker.add_source(&path.display().to_string(), fmt.reader(BufReader::new(file)))?;
+    }
+    let report = tracker.report(&options);
+    report.write(io::stdout().lock(), report_format)?;
+
+    if !report.is_clean() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
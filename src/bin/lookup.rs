@@ -0,0 +1,60 @@
+use std::io::{self, BufWriter};
+use std::process;
+use parser::bin_index::IndexedReader;
+use parser::{cli, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut input_file = None;
+    let mut output_format = None;
+    let mut tx_id = None;
+    let mut from = None;
+    let mut to = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input_file = Some(args.value()?),
+            "--output-format" => output_format = Some(args.value()?),
+            "--tx-id" => tx_id = Some(args.value()?.parse::<u64>()?),
+            "--from" => from = Some(args.value()?.parse::<u64>()?),
+            "--to" => to = Some(args.value()?.parse::<u64>()?),
+            _ => {}
+        }
+    }
+
+    let input_path = input_file.ok_or_else(|| cli::usage("--input <файл>"))?;
+    let out_fmt: Format = output_format.as_deref().unwrap_or("text").parse()?;
+
+    let file = cli::open_file(input_path, "--input")?;
+    let mut reader = IndexedReader::open(file)?;
+    if reader.index().is_none() {
+        eprintln!("В файле нет индекса, поиск идёт просмотром всех записей.");
+    }
+
+    let stdout = io::stdout();
+    let mut writer = out_fmt.writer(BufWriter::new(stdout.lock()));
+    let mut found = 0;
+    match (tx_id, from, to) {
+        (Some(tx_id), None, None) => {
+            if let Some(tx) = reader.get(tx_id)? {
+                writer.write(&tx)?;
+                found += 1;
+            }
+        }
+        (None, from, to) if from.is_some() || to.is_some() => {
+            for tx in reader.range(from.unwrap_or(0), to.unwrap_or(u64::MAX)) {
+                writer.write(&tx?)?;
+                found += 1;
+            }
+        }
+        _ => return Err(cli::usage("--tx-id <id> или --from/--to <мс>")),
+    }
+    writer.finish()?;
+
+    if found == 0 {
+        process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+use parser::external_sort::{self, SortOptions};
+use parser::format::{self, TransactionWrite};
+use parser::merge::{ConflictPolicy, Merge};
+use parser::{cli, Field, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut inputs = Vec::new();
+    let mut input_format = None;
+    let mut output = None;
+    let mut output_format = None;
+    let mut sort_by_timestamp = false;
+    let mut policy = ConflictPolicy::default();
+    let mut sort_options = SortOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => inputs.push(args.value()?),
+            "--input-format" => input_format = Some(args.value()?.parse::<Format>()?),
+            "--output" => output = Some(PathBuf::from(args.value()?)),
+            "--output-format" => output_format = Some(args.value()?.parse::<Format>()?),
+            "--sort-by-timestamp" => sort_by_timestamp = true,
+            "--on-conflict" => policy = args.value()?.parse()?,
+            "--memory-budget" => sort_options.memory_budget = external_sort::parse_size(&args.value()?)?,
+            "--temp-dir" => sort_options.temp_dir = PathBuf::from(args.value()?),
+            _ => {}
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err(cli::usage("--input <файл>"));
+    }
+
+    // Выход заменяется после чтения входов; совпадение с входом — почти наверняка ошибка.
+    let output_canonical = output.as_deref().and_then(|p| fs::canonicalize(p).ok());
+    let mut streams = Vec::with_capacity(inputs.len());
+    for spec in &inputs {
+        let (path, fmt) = format::parse_input(spec, input_format)?;
+        if output_canonical.is_some() && fs::canonicalize(&path).ok() == output_canonical {
+            return Err(parser::ParserError::Format(format!(
+                "Выходной файл (--output) совпадает с входным: '{}'",
+                path.display()
+            )));
+        }
+        let file = cli::open_file(&path, "--input")?;
+        streams.push(fmt.reader(BufReader::new(file)));
+    }
+
+    let out_fmt = output_format
+        .or_else(|| output.as_deref().and_then(Format::from_path))
+        .unwrap_or(Format::Text);
+    let mut merge = Merge::new(streams, policy);
+    let written = match &output {
+        Some(path) => {
+            // Запись идёт во временный файл рядом с выходом, который переименовывается
+            // только после успеха: конфликт посередине не оставляет обрезанный выход.
+            let mut temp = path.clone().into_os_string();
+            temp.push(".tmp");
+            let temp = PathBuf::from(temp);
+            let result = cli::create_file(&temp, "--output")
+                .and_then(|file| merge_into(BufWriter::new(file), out_fmt, &mut merge, sort_by_timestamp, &sort_options))
+                .and_then(|written| Ok(fs::rename(&temp, path).map(|_| written)?));
+            if result.is_err() {
+                let _ = fs::remove_file(&temp);
+            }
+            result?
+        }
+        None => merge_into(BufWriter::new(io::stdout().lock()), out_fmt, &mut merge, sort_by_timestamp, &sort_options)?,
+    };
+
+    for conflict in merge.conflicts() {
+        eprintln!(
+            "CONFLICT TX_ID {}: '{}' and '{}' differ, kept the first",
+            conflict.tx_id, inputs[conflict.first_input], inputs[conflict.second_input]
+        );
+    }
+    eprintln!(
+        "Merged {} records from {} files, {} duplicates dropped, {} conflicts.",
+        written,
+        inputs.len(),
+        merge.duplicates(),
+        merge.conflicts().len()
+    );
+    if !merge.conflicts().is_empty() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Пишет слитый поток в `sink`, возвращает число записей.
+fn merge_into<W: Write>(
+    sink: W,
+    format: Format,
+    merge: &mut Merge,
+    sort_by_timestamp: bool,
+    sort_options: &SortOptions,
+) -> Result<usize> {
+    let mut writer = format.writer(sink);
+    let written = if sort_by_timestamp {
+        // Сортировка устойчивая: при равном TIMESTAMP сохраняется порядок входов.
+        let sorted = external_sort::sort(merge, &[Field::Timestamp], sort_options)?;
+        write_all(&mut writer, sorted)?
+    } else {
+        write_all(&mut writer, merge)?
+    };
+    writer.finish()?;
+    Ok(written)
+}
+
+fn write_all<I>(writer: &mut Box<dyn TransactionWrite + '_>, transactions: I) -> Result<usize>
+where
+    I: IntoIterator<Item = Result<parser::Transaction>>,
+{
+    let mut count = 0;
+    for tx in transactions {
+        writer.write(&tx?)?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,52 @@
+use std::io;
+use std::process;
+use parser::compare::ReportFormat;
+use parser::reconcile::{reconcile, write_report, ReconcileOptions};
+use parser::{cli, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut file1_path = None;
+    let mut format1 = None;
+    let mut file2_path = None;
+    let mut format2 = None;
+    let mut report_format = None;
+    let mut options = ReconcileOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--file1" => file1_path = Some(args.value()?),
+            "--format1" => format1 = Some(args.value()?),
+            "--file2" => file2_path = Some(args.value()?),
+            "--format2" => format2 = Some(args.value()?),
+            "--window" => options.time_window = args.value()?.parse()?,
+            "--amount-tolerance" => options.amount_tolerance = args.value()?.parse()?,
+            "--max-split" => options.max_split = args.value()?.parse()?,
+            "--report" => report_format = Some(args.value()?),
+            _ => {}
+        }
+    }
+
+    let file1_path = file1_path.ok_or_else(|| parser::ParserError::Format("Укажите --file1".into()))?;
+    let file2_path = file2_path.ok_or_else(|| parser::ParserError::Format("Укажите --file2".into()))?;
+
+    let fmt1: Format = format1.as_deref().unwrap_or("text").parse()?;
+    let fmt2: Format = format2.as_deref().unwrap_or("text").parse()?;
+    let report_format: ReportFormat = match report_format {
+        Some(r) => r.parse()?,
+        None => ReportFormat::Text,
+    };
+
+    let txs1 = cli::load_transactions(file1_path, fmt1, "--file1", None)?;
+    let txs2 = cli::load_transactions(file2_path, fmt2, "--file2", None)?;
+
+    let result = reconcile(&txs1, &txs2, &options);
+    write_report(io::stdout().lock(), &result, report_format)?;
+
+    if !result.is_complete() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
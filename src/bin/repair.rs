@@ -0,0 +1,37 @@
+use std::io::{self, BufWriter};
+use std::process;
+use parser::compare::ReportFormat;
+use parser::repair::repair;
+use parser::{cli, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut input_file = None;
+    let mut output_file = None;
+    let mut report_format = ReportFormat::Text;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input_file = Some(args.value()?),
+            "--output" => output_file = Some(args.value()?),
+            "--report" => report_format = cli::report_format(&args.value()?, "--report")?,
+            _ => {}
+        }
+    }
+
+    let (Some(input_path), Some(output_path)) = (input_file, output_file) else {
+        return Err(cli::usage("--input <файл> --output <файл>"));
+    };
+
+    let input = cli::open_file(input_path, "--input")?;
+    let output = cli::create_file(output_path, "--output")?;
+    let report = repair(input, BufWriter::new(output))?;
+    report.write(io::stdout().lock(), report_format)?;
+
+    if !report.is_clean() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
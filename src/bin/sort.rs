@@ -0,0 +1,60 @@
+use std::io::{self, BufReader, BufWriter};
+use std::path::PathBuf;
+use parser::external_sort::{self, SortOptions};
+use parser::format::Transactions;
+use parser::sort::{sort_dedup, DedupOptions};
+use parser::{cli, Field, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut inputs = Vec::new();
+    let mut input_format = None;
+    let mut output_format = None;
+    let mut key = Vec::new();
+    let mut dedup: Option<DedupOptions> = None;
+    let mut policy = None;
+    let mut external = false;
+    let mut sort_options = SortOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => inputs.push(args.value()?),
+            "--input-format" => input_format = Some(args.value()?),
+            "--output-format" => output_format = Some(args.value()?),
+            "--by" => key = Field::parse_list(&args.value()?)?,
+            "--dedup" => dedup = Some(DedupOptions { key: args.value()?.parse()?, ..Default::default() }),
+            "--on-duplicate" => policy = Some(args.value()?.parse()?),
+            "--external" => external = true,
+            "--memory-budget" => sort_options.memory_budget = external_sort::parse_size(&args.value()?)?,
+            "--temp-dir" => sort_options.temp_dir = PathBuf::from(args.value()?),
+            _ => {}
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err(cli::usage("--input <файл>"));
+    }
+    let in_fmt: Format = input_format.as_deref().unwrap_or("text").parse()?;
+    let out_fmt: Format = output_format.as_deref().unwrap_or("text").parse()?;
+    if let Some(policy) = policy {
+        // --on-duplicate без --dedup означает дубликаты по TX_ID.
+        dedup.get_or_insert_with(DedupOptions::default).policy = policy;
+    }
+
+    // Файлы читаются подряд, как один поток.
+    let mut stream: Transactions<'static> = Box::new(std::iter::empty());
+    for path in inputs {
+        let file = cli::open_file(path, "--input")?;
+        stream = Box::new(stream.chain(in_fmt.reader(BufReader::new(file))));
+    }
+
+    let sorted = sort_dedup(stream, &key, dedup, external.then_some(&sort_options))?;
+    let mut writer = out_fmt.writer(BufWriter::new(io::stdout().lock()));
+    for tx in sorted {
+        writer.write(&tx?)?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+use parser::calendar::parse_utc_offset;
+use parser::external_sort::parse_size;
+use parser::split::{split, SplitOptions};
+use parser::{cli, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut input_file = None;
+    let mut input_format = None;
+    let mut output_format = None;
+    let mut output_dir = None;
+    let mut options = SplitOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input_file = Some(args.value()?),
+            "--input-format" => input_format = Some(args.value()?),
+            "--output-format" => output_format = Some(args.value()?),
+            "--output-dir" => output_dir = Some(args.value()?),
+            "--by" => options.key = args.value()?.parse()?,
+            "--utc-offset" => options.utc_offset_minutes = parse_utc_offset(&args.value()?)?,
+            "--max-records" => options.max_records = Some(args.value()?.parse()?),
+            "--max-bytes" => options.max_bytes = Some(parse_size(&args.value()?)? as u64),
+            "--template" => options.template = Some(args.value()?),
+            "--max-open" => options.max_open = args.value()?.parse()?,
+            _ => {}
+        }
+    }
+
+    let input_path = input_file.ok_or_else(|| cli::usage("--input <файл>"))?;
+    let output_dir = output_dir.ok_or_else(|| cli::usage("--output-dir <каталог>"))?;
+    let in_fmt: Format = input_format.as_deref().unwrap_or("text").parse()?;
+    // По умолчанию части пишутся в формате входа.
+    options.format = match output_format {
+        Some(f) => f.parse()?,
+        None => in_fmt,
+    };
+
+    let file = cli::open_file(input_path, "--input")?;
+    fs::create_dir_all(&output_dir)?;
+    let files = split(in_fmt.reader(BufReader::new(file)), Path::new(&output_dir), &options)?;
+
+    for file in &files {
+        println!("{} ({} records, {} bytes)", file.path.display(), file.records, file.bytes);
+    }
+    println!("Wrote {} files.", files.len());
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use std::io;
+use parser::compare::ReportFormat;
+use parser::filter::Filter;
+use parser::stats::{Stats, DEFAULT_TOP};
+use parser::{cli, Format, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut input_file = None;
+    let mut input_format = None;
+    let mut out_fmt = ReportFormat::Text;
+    let mut top = DEFAULT_TOP;
+    let mut filter = None;
+    let mut median = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input_file = Some(args.value()?),
+            "--input-format" => input_format = Some(args.value()?),
+            "--output-format" => out_fmt = cli::report_format(&args.value()?, "--output-format")?,
+            "--top" => top = args.value()?.parse()?,
+            "--filter" => filter = Some(Filter::parse(&args.value()?)?),
+            "--median" => median = true,
+            _ => {}
+        }
+    }
+
+    let input_path = input_file.ok_or_else(|| cli::usage("--input <файл>"))?;
+    let in_fmt: Format = input_format.as_deref().unwrap_or("text").parse()?;
+
+    let file = cli::open_file(input_path, "--input")?;
+    let mut stats = if median { Stats::with_median() } else { Stats::new() };
+    if in_fmt == Format::Columnar {
+        // Читаются только нужные поля, группы строк отсекаются по фильтру.
+        stats.add_columnar(file, filter)?;
+    } else {
+        stats.add_all(in_fmt.file_reader(file, filter))?;
+    }
+    stats.write(io::stdout().lock(), out_fmt, top)?;
+
+    Ok(())
+}
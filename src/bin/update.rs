@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use parser::patch::{self, Assignment, AuditState};
+use parser::{cli, Result};
+
+fn main() -> Result<()> {
+    let mut args = cli::Args::from_env();
+
+    let mut input_file = None;
+    let mut tx_ids: Vec<u64> = Vec::new();
+    let mut assignments: Vec<Assignment> = Vec::new();
+    let mut audit_file = None;
+    let mut dry_run = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => input_file = Some(args.value()?),
+            "--tx-id" => {
+                for id in args.value()?.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    tx_ids.push(id.parse()?);
+                }
+            }
+            "--set" => assignments.push(args.value()?.parse()?),
+            "--audit" => audit_file = Some(args.value()?),
+            "--dry-run" => dry_run = true,
+            _ => {}
+        }
+    }
+
+    let input_path = input_file.ok_or_else(|| cli::usage("--input <файл>"))?;
+    if tx_ids.is_empty() || assignments.is_empty() {
+        return Err(parser::ParserError::Format(
+            "Ошибка: Укажите --tx-id <id[,id...]> и --set <ПОЛЕ=ЗНАЧЕНИЕ>".into(),
+        ));
+    }
+    let updates: BTreeMap<u64, Vec<Assignment>> =
+        tx_ids.into_iter().map(|id| (id, assignments.clone())).collect();
+
+    let plan = patch::plan(Path::new(&input_path), &updates).map_err(|e| match e {
+        parser::ParserError::Io(e) => parser::ParserError::Format(format!(
+            "Не удалось открыть входной файл (--input) '{}': {}",
+            input_path, e
+        )),
+        e => e,
+    })?;
+
+    // Перед изменением файла в журнал пишется pending, после — итог:
+    // pending без committed или failed означает сбой посреди записи.
+    let audit_path = audit_file.unwrap_or_else(|| format!("{}.audit.jsonl", input_path));
+    let mut audit = OpenOptions::new().create(true).append(true).open(&audit_path)?;
+    let at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    if dry_run {
+        plan.write_audit(&mut audit, &input_path, at, AuditState::DryRun)?;
+    } else {
+        plan.write_audit(&mut audit, &input_path, at, AuditState::Pending)?;
+        let applied = plan.apply(Path::new(&input_path));
+        let state = if applied.is_ok() { AuditState::Committed } else { AuditState::Failed };
+        plan.write_audit(&mut audit, &input_path, at, state)?;
+        applied?;
+    }
+    plan.write_summary(io::stdout().lock(), dry_run)?;
+
+    if !plan.not_found.is_empty() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
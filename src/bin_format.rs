@@ -1,118 +1,590 @@
 //! Бинарник: YPBN, размер, поля (big-endian).
+//!
+//! Версия 2 добавляет заголовок файла (`YPBF`), CRC32 после каждой записи и
+//! трейлер (`YPBT`) с числом записей и суммой `AMOUNT`. Читатель определяет
+//! версию по первым байтам файла. С флагом [`FLAG_INDEX`] за трейлером
+//! идёт индекс для произвольного доступа, см. [`crate::bin_index`].
 
-use std::io::{Write, Read};
-use crate::{Transaction, TxType, TxStatus, Result, ParserError};
+use std::io::{BufReader, Write, Read};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::bin_index::IndexBuilder;
+use crate::bin_view::TransactionRef;
+use crate::crc32::crc32;
+use crate::{Transaction, TxType, TxStatus, Result, ParserError, Truncation};
 
 /// Сигнатура записи.
-const MAGIC: &[u8; 4] = b"YPBN";
+pub(crate) const MAGIC: &[u8; 4] = b"YPBN";
+
+/// Сигнатура заголовка файла v2.
+pub(crate) const FILE_MAGIC: &[u8; 4] = b"YPBF";
+
+/// Сигнатура трейлера v2.
+pub(crate) const TRAILER_MAGIC: &[u8; 4] = b"YPBT";
+
+/// После каждой записи идёт CRC32 её тела.
+pub const FLAG_RECORD_CRC: u16 = 0x0001;
+
+/// В конце файла есть трейлер.
+pub const FLAG_TRAILER: u16 = 0x0002;
+
+/// После трейлера идут индекс и футер, см. [`crate::bin_index`].
+pub const FLAG_INDEX: u16 = 0x0004;
+
+const KNOWN_FLAGS: u16 = FLAG_RECORD_CRC | FLAG_TRAILER | FLAG_INDEX;
+
+/// Производитель по умолчанию в заголовке v2.
+pub const DEFAULT_PRODUCER: &str = concat!("ypbank ", env!("CARGO_PKG_VERSION"));
+
+/// Версия формата.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Version {
+    /// Только записи, без заголовка.
+    #[default]
+    V1,
+    V2,
+}
+
+impl FromStr for Version {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "1" | "v1" => Ok(Version::V1),
+            "2" | "v2" => Ok(Version::V2),
+            _ => Err(ParserError::Format(format!("Неизвестная версия бинарного формата: {}", s))),
+        }
+    }
+}
+
+/// Параметры записи.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    pub version: Version,
+    /// Дописать индекс (только для версии 2).
+    pub index: bool,
+}
+
+/// Заголовок файла v2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHeader {
+    pub version: u16,
+    pub flags: u16,
+    /// Время создания, мс от эпохи Unix.
+    pub created_at: u64,
+    /// Кто записал файл.
+    pub producer: String,
+}
+
+impl FileHeader {
+    /// Заголовок с CRC и трейлером, время — текущее.
+    pub fn new(producer: &str) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        FileHeader { version: 2, flags: FLAG_RECORD_CRC | FLAG_TRAILER, created_at, producer: producer.to_string() }
+    }
+
+    /// Тот же заголовок с флагом индекса.
+    pub fn indexed(mut self) -> Self {
+        self.flags |= FLAG_INDEX;
+        self
+    }
+
+    pub fn has(&self, flag: u16) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Размер заголовка в файле, байт.
+    pub(crate) fn encoded_len(&self) -> u64 {
+        4 + 14 + self.producer.len() as u64
+    }
+}
+
+/// Трейлер файла v2.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Trailer {
+    pub record_count: u64,
+    /// Сумма `AMOUNT` всех записей.
+    pub amount_sum: i128,
+}
+
+impl Trailer {
+    pub(crate) fn add(&mut self, tx: &Transaction) {
+        self.record_count += 1;
+        self.amount_sum += tx.amount as i128;
+    }
+}
 
 /// Читаем записи до EOF.
-pub fn from_read<R: Read>(mut reader: R) -> Result<Vec<Transaction>> {
-    let mut transactions = Vec::new();
-
-    loop {
-        let mut magic_buf = [0u8; 4];
-        if let Err(e) = reader.read_exact(&mut magic_buf) {
-            if e.kind() == std::io::ErrorKind::UnexpectedEof {
-                break;
+pub fn from_read<R: Read>(reader: R) -> Result<Vec<Transaction>> {
+    Reader::new(reader).collect()
+}
+
+/// Потоковое чтение: по одной записи за шаг, после ошибки итерация заканчивается.
+pub struct Reader<R: Read> {
+    reader: BufReader<R>,
+    done: bool,
+    started: bool,
+    header: Option<FileHeader>,
+    /// MAGIC первой записи v1, прочитанный при определении версии.
+    pending_magic: Option<[u8; 4]>,
+    /// Что прочитано на самом деле — для сверки с трейлером.
+    seen: Trailer,
+    trailer: Option<Trailer>,
+    /// Смещение следующей записи от начала файла.
+    offset: u64,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Reader {
+            reader: BufReader::new(reader),
+            done: false,
+            started: false,
+            header: None,
+            pending_magic: None,
+            seen: Trailer::default(),
+            trailer: None,
+            offset: 0,
+        }
+    }
+
+    /// Заголовок v2; известен после первого вызова `next`.
+    pub fn header(&self) -> Option<&FileHeader> {
+        self.header.as_ref()
+    }
+
+    /// Версия файла; известна после первого вызова `next`.
+    pub fn version(&self) -> Version {
+        if self.header.is_some() { Version::V2 } else { Version::V1 }
+    }
+
+    /// Трейлер v2; известен после окончания чтения.
+    pub fn trailer(&self) -> Option<&Trailer> {
+        self.trailer.as_ref()
+    }
+
+    /// Смещение следующей записи (после окончания чтения v2 — трейлера).
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn read_next(&mut self) -> Result<Option<Transaction>> {
+        if !self.started {
+            self.started = true;
+            match read_magic(&mut self.reader)? {
+                None => return Ok(None),
+                Some(magic) if &magic == FILE_MAGIC => {
+                    let header = read_header(&mut self.reader)?;
+                    self.offset = header.encoded_len();
+                    self.header = Some(header);
+                }
+                Some(magic) => self.pending_magic = Some(magic),
+            }
+        }
+
+        let (record, offset) = (self.seen.record_count + 1, self.offset);
+        let magic = match self.pending_magic.take() {
+            Some(magic) => Some(magic),
+            None => read_magic(&mut self.reader).map_err(|e| locate(e, record, offset))?,
+        };
+        let Some(magic) = magic else {
+            if self.header.as_ref().is_some_and(|h| h.has(FLAG_TRAILER)) {
+                return Err(ParserError::Format("Файл оборвался: нет трейлера".into()));
+            }
+            return Ok(None);
+        };
+
+        if let Some(header) = &self.header {
+            if &magic == TRAILER_MAGIC && header.has(FLAG_TRAILER) {
+                let trailer = read_trailer(&mut self.reader)?;
+                if trailer != self.seen {
+                    return Err(ParserError::Format(format!(
+                        "Трейлер не совпадает с данными: записей {} (в трейлере {}), сумма {} (в трейлере {})",
+                        self.seen.record_count, trailer.record_count, self.seen.amount_sum, trailer.amount_sum
+                    )));
+                }
+                if header.has(FLAG_INDEX) {
+                    // Индекс нужен только для произвольного доступа: поток его не хранит, но проверяет.
+                    match read_magic(&mut self.reader)? {
+                        Some(magic) if &magic == crate::bin_index::INDEX_MAGIC => {
+                            crate::bin_index::check_index(&mut self.reader, self.offset + 32)?;
+                        }
+                        _ => return Err(ParserError::Format("Нет индекса после трейлера".into())),
+                    }
+                } else {
+                    match read_magic(&mut self.reader) {
+                        Ok(None) => {}
+                        Ok(Some(_)) | Err(ParserError::Truncated(_)) => {
+                            return Err(ParserError::Format("Данные после трейлера".into()));
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                self.trailer = Some(trailer);
+                return Ok(None);
             }
-            return Err(e.into());
         }
 
-        if &magic_buf != MAGIC {
-            return Err(ParserError::Format("Неверный заголовок MAGIC".into()));
+        if &magic != MAGIC {
+            return Err(ParserError::Format(format!(
+                "Неверный заголовок MAGIC записи {} (смещение {})",
+                record, offset
+            )));
         }
+        let crc = self.header.as_ref().is_some_and(|h| h.has(FLAG_RECORD_CRC));
+        let (body, crc_ok) = read_record_after_magic(&mut self.reader, crc).map_err(|e| locate(e, record, offset))?;
+        if !crc_ok {
+            return Err(ParserError::Format(format!(
+                "Контрольная сумма записи {} (TX_ID {}) не совпадает",
+                record,
+                body_tx_id(&body)
+            )));
+        }
+        let tx = parse_body(&body)?;
+        self.seen.add(&tx);
+        self.offset += record_len(&tx, crc);
+        Ok(Some(tx))
+    }
+}
 
-        let mut size_buf = [0u8; 4];
-        reader.read_exact(&mut size_buf)?;
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Transaction>;
 
-        let mut tx_id_buf = [0u8; 8];
-        reader.read_exact(&mut tx_id_buf)?;
-        let tx_id = u64::from_be_bytes(tx_id_buf);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_next().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
 
-        let mut type_buf = [0u8; 1];
-        reader.read_exact(&mut type_buf)?;
-        let tx_type = match type_buf[0] {
-            0 => TxType::Deposit,
-            1 => TxType::Transfer,
-            2 => TxType::Withdrawal,
-            _ => return Err(ParserError::Format("Неверный тип в бинарном файле".into())),
-        };
+/// Размер, тело и CRC (если `crc`) записи после MAGIC; `bool` — совпала ли CRC.
+/// CRC считается только по байтам тела, `MAGIC` и `RECORD_SIZE` в неё не
+/// входят: испорченный `RECORD_SIZE` виден по несовпадению с `DESC_LEN`.
+/// Тело разбирается вызывающим только после проверки CRC, см. [`parse_body`].
+/// Обрыв — [`ParserError::Truncated`] без номера и смещения, см. [`locate`].
+pub(crate) fn read_record_after_magic<R: Read>(reader: &mut R, crc: bool) -> Result<(Vec<u8>, bool)> {
+    let mut size_buf = [0u8; 4];
+    let n = fill(reader, &mut size_buf)?;
+    if n < 4 {
+        return Err(truncated("RECORD_SIZE", (4 - n) as u64));
+    }
+    let size = u32::from_be_bytes(size_buf) as u64;
+    let crc_len = if crc { 4 } else { 0 };
+    let mut body = Vec::new();
+    reader.take(size).read_to_end(&mut body)?;
+    if (body.len() as u64) < size {
+        return Err(truncated(body_field(body.len()), size - body.len() as u64 + crc_len));
+    }
+    if !crc {
+        return Ok((body, true));
+    }
+    let mut crc_buf = [0u8; 4];
+    let n = fill(reader, &mut crc_buf)?;
+    if n < 4 {
+        return Err(truncated("CRC32", (4 - n) as u64));
+    }
+    let crc_ok = u32::from_be_bytes(crc_buf) == crc32(&body);
+    Ok((body, crc_ok))
+}
 
-        let mut from_buf = [0u8; 8];
-        reader.read_exact(&mut from_buf)?;
-        let from_user_id = u64::from_be_bytes(from_buf);
-
-        let mut to_buf = [0u8; 8];
-        reader.read_exact(&mut to_buf)?;
-        let to_user_id = u64::from_be_bytes(to_buf);
-
-        let mut amount_buf = [0u8; 8];
-        reader.read_exact(&mut amount_buf)?;
-        let amount = i64::from_be_bytes(amount_buf);
-
-        let mut ts_buf = [0u8; 8];
-        reader.read_exact(&mut ts_buf)?;
-        let timestamp = u64::from_be_bytes(ts_buf);
-
-        let mut status_buf = [0u8; 1];
-        reader.read_exact(&mut status_buf)?;
-        let status = match status_buf[0] {
-            0 => TxStatus::Success,
-            1 => TxStatus::Failure,
-            2 => TxStatus::Pending,
-            _ => return Err(ParserError::Format("Неверный статус в бинарном файле".into())),
-        };
+/// Транзакция из тела записи.
+pub(crate) fn parse_body(body: &[u8]) -> Result<Transaction> {
+    TransactionRef::new(body)?.to_transaction()
+}
+
+/// `TX_ID` из первых байт тела без его разбора: для сообщений о CRC.
+pub(crate) fn body_tx_id(body: &[u8]) -> String {
+    body.get(..8)
+        .map_or("?".to_string(), |b| u64::from_be_bytes(b.try_into().unwrap_or_default()).to_string())
+}
+
+/// Размер записи в файле: MAGIC, размер, тело и CRC.
+pub(crate) fn record_len(tx: &Transaction, crc: bool) -> u64 {
+    8 + 46 + tx.description.len() as u64 + if crc { 4 } else { 0 }
+}
+
+/// Поле тела записи, в котором лежит байт со смещением `pos` от начала тела.
+pub(crate) fn body_field(pos: usize) -> &'static str {
+    match pos {
+        0..=7 => "TX_ID",
+        8 => "TX_TYPE",
+        9..=16 => "FROM_USER_ID",
+        17..=24 => "TO_USER_ID",
+        25..=32 => "AMOUNT",
+        33..=40 => "TIMESTAMP",
+        41 => "STATUS",
+        42..=45 => "DESC_LEN",
+        _ => "DESCRIPTION",
+    }
+}
+
+/// Обрыв в поле `field`; номер и смещение записи подставляет [`locate`].
+fn truncated(field: &'static str, missing: u64) -> ParserError {
+    ParserError::Truncated(Truncation { record: 0, offset: 0, field, missing })
+}
+
+/// Дополняет ошибку обрыва номером и смещением записи.
+pub(crate) fn locate(error: ParserError, record: u64, offset: u64) -> ParserError {
+    match error {
+        ParserError::Truncated(t) => ParserError::Truncated(Truncation { record, offset, ..t }),
+        e => e,
+    }
+}
+
+/// Читает, пока буфер не заполнится или не кончатся данные; возвращает число байт.
+fn fill<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(k) => n += k,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
 
-        let mut desc_len_buf = [0u8; 4];
-        reader.read_exact(&mut desc_len_buf)?;
-        let desc_len = u32::from_be_bytes(desc_len_buf) as usize;
+/// Запись, если дальше идёт MAGIC записи; `None` — EOF или другой блок (трейлер).
+pub(crate) fn read_record<R: Read>(reader: &mut R, crc: bool) -> Result<Option<Transaction>> {
+    match read_magic(reader)? {
+        Some(magic) if &magic == MAGIC => {
+            let (body, crc_ok) = read_record_after_magic(reader, crc)?;
+            if !crc_ok {
+                return Err(ParserError::Format(format!(
+                    "Контрольная сумма записи TX_ID {} не совпадает",
+                    body_tx_id(&body)
+                )));
+            }
+            Ok(Some(parse_body(&body)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Четыре байта сигнатуры; `None` — чистый EOF, часть сигнатуры — обрыв.
+pub(crate) fn read_magic<R: Read>(reader: &mut R) -> Result<Option<[u8; 4]>> {
+    let mut magic_buf = [0u8; 4];
+    match fill(reader, &mut magic_buf)? {
+        0 => Ok(None),
+        4 => Ok(Some(magic_buf)),
+        n => Err(truncated("MAGIC", (4 - n) as u64)),
+    }
+}
+
+/// Заголовок v2 после `YPBF`.
+pub(crate) fn read_header<R: Read>(reader: &mut R) -> Result<FileHeader> {
+    let mut buf = [0u8; 14];
+    reader.read_exact(&mut buf)?;
+    let version = u16::from_be_bytes([buf[0], buf[1]]);
+    let flags = u16::from_be_bytes([buf[2], buf[3]]);
+    let created_at = u64::from_be_bytes(buf[4..12].try_into().unwrap_or_default());
+    let producer_len = u16::from_be_bytes([buf[12], buf[13]]) as usize;
+    if version != 2 {
+        return Err(ParserError::Format(format!("Неподдерживаемая версия бинарного формата: {}", version)));
+    }
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(ParserError::Format(format!("Неизвестные флаги бинарного файла: {:#06x}", flags)));
+    }
+    let mut producer = vec![0u8; producer_len];
+    reader.read_exact(&mut producer)?;
+    Ok(FileHeader { version, flags, created_at, producer: String::from_utf8(producer)? })
+}
+
+/// Трейлер после `YPBT`: число записей, сумма, CRC32 этих 24 байт.
+pub(crate) fn read_trailer<R: Read>(reader: &mut R) -> Result<Trailer> {
+    let mut buf = [0u8; 28];
+    let n = fill(reader, &mut buf)?;
+    if n < buf.len() {
+        return Err(ParserError::Format(format!("Файл оборвался в трейлере: не хватает {} байт", buf.len() - n)));
+    }
+    let crc = u32::from_be_bytes([buf[24], buf[25], buf[26], buf[27]]);
+    if crc32(&buf[..24]) != crc {
+        return Err(ParserError::Format("Контрольная сумма трейлера не совпадает".into()));
+    }
+    Ok(Trailer {
+        record_count: u64::from_be_bytes(buf[..8].try_into().unwrap_or_default()),
+        amount_sum: i128::from_be_bytes(buf[8..24].try_into().unwrap_or_default()),
+    })
+}
+
+pub(crate) fn encode_type(tx_type: TxType) -> u8 {
+    match tx_type {
+        TxType::Deposit => 0,
+        TxType::Transfer => 1,
+        TxType::Withdrawal => 2,
+    }
+}
 
-        let mut desc_bytes = vec![0u8; desc_len];
-        reader.read_exact(&mut desc_bytes)?;
-        let description = String::from_utf8(desc_bytes)?;
+pub(crate) fn encode_status(status: TxStatus) -> u8 {
+    match status {
+        TxStatus::Success => 0,
+        TxStatus::Failure => 1,
+        TxStatus::Pending => 2,
+    }
+}
 
-        transactions.push(Transaction {
-            tx_id,
-            tx_type,
-            from_user_id,
-            to_user_id,
-            amount,
-            timestamp,
-            status,
-            description,
-        });
+pub(crate) fn decode_type(byte: u8) -> Result<TxType> {
+    match byte {
+        0 => Ok(TxType::Deposit),
+        1 => Ok(TxType::Transfer),
+        2 => Ok(TxType::Withdrawal),
+        _ => Err(ParserError::Format("Неверный тип в бинарном файле".into())),
     }
+}
 
-    Ok(transactions)
+pub(crate) fn decode_status(byte: u8) -> Result<TxStatus> {
+    match byte {
+        0 => Ok(TxStatus::Success),
+        1 => Ok(TxStatus::Failure),
+        2 => Ok(TxStatus::Pending),
+        _ => Err(ParserError::Format("Неверный статус в бинарном файле".into())),
+    }
 }
 
 /// MAGIC + размер + поля (big-endian).
-pub fn write_to<W: Write>(mut writer: W, transactions: &[Transaction]) -> Result<()> {
+pub fn write_to<W: Write>(writer: W, transactions: &[Transaction]) -> Result<()> {
+    let mut writer = Writer::new(writer);
     for tx in transactions {
-        writer.write_all(MAGIC)?;
-
-        let desc_bytes = tx.description.as_bytes();
-        let body_size = (8 + 1 + 8 + 8 + 8 + 8 + 1 + 4 + desc_bytes.len()) as u32;
-        writer.write_all(&body_size.to_be_bytes())?;
-
-        writer.write_all(&tx.tx_id.to_be_bytes())?;
-        writer.write_all(&[match tx.tx_type {
-            TxType::Deposit => 0,
-            TxType::Transfer => 1,
-            TxType::Withdrawal => 2,
-        }])?;
-        writer.write_all(&tx.from_user_id.to_be_bytes())?;
-        writer.write_all(&tx.to_user_id.to_be_bytes())?;
-        writer.write_all(&tx.amount.to_be_bytes())?;
-        writer.write_all(&tx.timestamp.to_be_bytes())?;
-        writer.write_all(&[match tx.status {
-            TxStatus::Success => 0,
-            TxStatus::Failure => 1,
-            TxStatus::Pending => 2,
-        }])?;
-        writer.write_all(&(desc_bytes.len() as u32).to_be_bytes())?;
-        writer.write_all(desc_bytes)?;
+        writer.write(tx)?;
     }
+    writer.finish()?;
     Ok(())
 }
+
+/// Потоковая запись по одной транзакции.
+pub struct Writer<W: Write> {
+    writer: W,
+    /// Заголовок v2; `None` — версия 1.
+    header: Option<FileHeader>,
+    header_written: bool,
+    totals: Trailer,
+    body: Vec<u8>,
+    /// Сколько байт уже записано — смещение следующей записи.
+    position: u64,
+    index: Option<IndexBuilder>,
+}
+
+impl<W: Write> Writer<W> {
+    /// Запись в версии 1.
+    pub fn new(writer: W) -> Self {
+        Writer {
+            writer,
+            header: None,
+            header_written: false,
+            totals: Trailer::default(),
+            body: Vec::new(),
+            position: 0,
+            index: None,
+        }
+    }
+
+    /// Запись в версии 2 с заданным заголовком; с [`FLAG_INDEX`] в конце пишется индекс.
+    pub fn v2(writer: W, header: FileHeader) -> Self {
+        let index = header.has(FLAG_INDEX).then(IndexBuilder::default);
+        Writer { header: Some(header), index, ..Writer::new(writer) }
+    }
+
+    /// Продолжение файла v2, где уже записаны заголовок и записи с итогами
+    /// `totals`: пишутся только новые записи и трейлер.
+    pub(crate) fn continuing(writer: W, header: FileHeader, totals: Trailer) -> Self {
+        Writer { header: Some(header), header_written: true, totals, ..Writer::new(writer) }
+    }
+
+    /// Запись с выбранными версией и индексом; для v2 — с [`DEFAULT_PRODUCER`].
+    pub fn with_options(writer: W, options: &WriteOptions) -> Result<Self> {
+        match (options.version, options.index) {
+            (Version::V1, false) => Ok(Writer::new(writer)),
+            (Version::V1, true) => Err(ParserError::Format("Индекс поддерживается только в версии 2".into())),
+            (Version::V2, index) => {
+                let header = FileHeader::new(DEFAULT_PRODUCER);
+                Ok(Writer::v2(writer, if index { header.indexed() } else { header }))
+            }
+        }
+    }
+
+    pub fn write(&mut self, tx: &Transaction) -> Result<()> {
+        self.write_header()?;
+        self.body.clear();
+        encode_body(&mut self.body, tx);
+        if let Some(index) = &mut self.index {
+            index.add(tx, self.position);
+        }
+        self.writer.write_all(MAGIC)?;
+        self.writer.write_all(&(self.body.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&self.body)?;
+        self.position += 8 + self.body.len() as u64;
+        if self.header.as_ref().is_some_and(|h| h.has(FLAG_RECORD_CRC)) {
+            self.writer.write_all(&crc32(&self.body).to_be_bytes())?;
+            self.position += 4;
+        }
+        self.totals.add(tx);
+        Ok(())
+    }
+
+    /// Дописывает заголовок, трейлер и индекс v2, сбрасывает буферы и возвращает исходный writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.write_header()?;
+        if self.header.as_ref().is_some_and(|h| h.has(FLAG_TRAILER)) {
+            self.writer.write_all(&encode_trailer(&self.totals))?;
+            self.position += 32;
+        }
+        if let Some(index) = self.index.take() {
+            index.write_to(&mut self.writer, self.position)?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        let Some(header) = &self.header else { return Ok(()) };
+        if self.header_written {
+            return Ok(());
+        }
+        self.header_written = true;
+        self.position = header.encoded_len();
+        let producer = header.producer.as_bytes();
+        let producer_len = u16::try_from(producer.len())
+            .map_err(|_| ParserError::Format("Слишком длинное имя производителя".into()))?;
+        self.writer.write_all(FILE_MAGIC)?;
+        self.writer.write_all(&header.version.to_be_bytes())?;
+        self.writer.write_all(&header.flags.to_be_bytes())?;
+        self.writer.write_all(&header.created_at.to_be_bytes())?;
+        self.writer.write_all(&producer_len.to_be_bytes())?;
+        self.writer.write_all(producer)?;
+        Ok(())
+    }
+}
+
+/// Трейлер целиком: `YPBT`, итоги и их CRC32.
+pub(crate) fn encode_trailer(totals: &Trailer) -> Vec<u8> {
+    let mut trailer = Vec::with_capacity(32);
+    trailer.extend_from_slice(TRAILER_MAGIC);
+    trailer.extend_from_slice(&totals.record_count.to_be_bytes());
+    trailer.extend_from_slice(&totals.amount_sum.to_be_bytes());
+    let crc = crc32(&trailer[4..]);
+    trailer.extend_from_slice(&crc.to_be_bytes());
+    trailer
+}
+
+/// Тело записи без MAGIC и размера.
+pub(crate) fn encode_body(body: &mut Vec<u8>, tx: &Transaction) {
+    let desc_bytes = tx.description.as_bytes();
+    body.extend_from_slice(&tx.tx_id.to_be_bytes());
+    body.push(encode_type(tx.tx_type));
+    body.extend_from_slice(&tx.from_user_id.to_be_bytes());
+    body.extend_from_slice(&tx.to_user_id.to_be_bytes());
+    body.extend_from_slice(&tx.amount.to_be_bytes());
+    body.extend_from_slice(&tx.timestamp.to_be_bytes());
+    body.push(encode_status(tx.status));
+    body.extend_from_slice(&(desc_bytes.len() as u32).to_be_bytes());
+    body.extend_from_slice(desc_bytes);
+}
@@ -0,0 +1,325 @@
+//! Индекс YPBankBin v2 и чтение с произвольным доступом.
+//!
+//! Индекс пишется после трейлера, если в заголовке стоит
+//! [`crate::bin_format::FLAG_INDEX`]:
+//!
+//! ```text
+//! YPBI | TX_COUNT u64 | TX_COUNT × (TX_ID u64, OFFSET u64)   — по возрастанию TX_ID
+//!      | BLOCK_COUNT u64 | BLOCK_COUNT × (OFFSET u64, RECORDS u32, MIN_TS u64, MAX_TS u64)
+//!      | CRC32 (всё после YPBI)
+//! INDEX_OFFSET u64 | YPBX                                   — футер, последние 12 байт
+//! ```
+//!
+//! Блоки по [`TIMESTAMP_BLOCK`] записей подряд хранят минимальный и
+//! максимальный `TIMESTAMP`, поэтому поиск по времени пропускает блоки и в
+//! неупорядоченном по времени файле.
+//!
+//! Индекс целиком держится в памяти и при записи, и в [`IndexedReader`]:
+//! 16 байт на запись и 28 байт на блок, то есть около 1,6 ГБ на сто
+//! миллионов записей. Файлы, для которых это много, пишутся без индекса.
+//! Потоковое чтение индекс не загружает, а проверяет по ходу: CRC, смещение
+//! в футере и конец файла.
+
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use crate::bin_format::{self, FileHeader, FLAG_INDEX, FLAG_RECORD_CRC};
+use crate::crc32::{crc32, Crc32};
+use crate::{Transaction, Result, ParserError};
+
+/// Сигнатура индекса.
+pub(crate) const INDEX_MAGIC: &[u8; 4] = b"YPBI";
+
+/// Сигнатура футера.
+const FOOTER_MAGIC: &[u8; 4] = b"YPBX";
+
+/// Сколько записей в блоке временного индекса.
+pub const TIMESTAMP_BLOCK: u32 = 256;
+
+/// Блок записей, идущих подряд.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    pub offset: u64,
+    pub records: u32,
+    pub min_timestamp: u64,
+    pub max_timestamp: u64,
+}
+
+/// Индекс файла.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Index {
+    /// `(TX_ID, смещение)` по возрастанию.
+    pub tx_ids: Vec<(u64, u64)>,
+    pub blocks: Vec<Block>,
+}
+
+/// Накопление индекса при записи; память растёт с числом записей.
+#[derive(Debug, Default)]
+pub(crate) struct IndexBuilder {
+    index: Index,
+}
+
+impl IndexBuilder {
+    pub(crate) fn add(&mut self, tx: &Transaction, offset: u64) {
+        self.index.tx_ids.push((tx.tx_id, offset));
+        match self.index.blocks.last_mut() {
+            Some(block) if block.records < TIMESTAMP_BLOCK => {
+                block.records += 1;
+                block.min_timestamp = block.min_timestamp.min(tx.timestamp);
+                block.max_timestamp = block.max_timestamp.max(tx.timestamp);
+            }
+            _ => self.index.blocks.push(Block {
+                offset,
+                records: 1,
+                min_timestamp: tx.timestamp,
+                max_timestamp: tx.timestamp,
+            }),
+        }
+    }
+
+    /// Пишет индекс и футер; `offset` — где в файле начнётся индекс.
+    pub(crate) fn write_to<W: Write>(mut self, writer: &mut W, offset: u64) -> Result<()> {
+        // Устойчивая сортировка: при повторах TX_ID первым остаётся более раннее смещение.
+        self.index.tx_ids.sort_by_key(|&(tx_id, _)| tx_id);
+        let mut crc = Crc32::new();
+        let mut put = |writer: &mut W, bytes: &[u8]| -> io::Result<()> {
+            crc.update(bytes);
+            writer.write_all(bytes)
+        };
+        writer.write_all(INDEX_MAGIC)?;
+        put(writer, &(self.index.tx_ids.len() as u64).to_be_bytes())?;
+        for (tx_id, record_offset) in &self.index.tx_ids {
+            put(writer, &tx_id.to_be_bytes())?;
+            put(writer, &record_offset.to_be_bytes())?;
+        }
+        put(writer, &(self.index.blocks.len() as u64).to_be_bytes())?;
+        for block in &self.index.blocks {
+            put(writer, &block.offset.to_be_bytes())?;
+            put(writer, &block.records.to_be_bytes())?;
+            put(writer, &block.min_timestamp.to_be_bytes())?;
+            put(writer, &block.max_timestamp.to_be_bytes())?;
+        }
+        writer.write_all(&crc.finish().to_be_bytes())?;
+        writer.write_all(&offset.to_be_bytes())?;
+        writer.write_all(FOOTER_MAGIC)?;
+        Ok(())
+    }
+}
+
+/// Проверяет индекс при потоковом чтении, не загружая его: CRC, смещение в
+/// футере и то, что за футером файл кончается. `YPBI` уже прочитан,
+/// `offset` — где он начинался.
+pub(crate) fn check_index<R: Read>(reader: &mut R, offset: u64) -> Result<()> {
+    let mut crc = Crc32::new();
+    for entry_len in [16, 28] {
+        let mut count = [0u8; 8];
+        read_index(reader, &mut count)?;
+        crc.update(&count);
+        let mut left = u64::from_be_bytes(count)
+            .checked_mul(entry_len)
+            .ok_or_else(|| ParserError::Format("Повреждённый индекс".into()))?;
+        let mut buf = [0u8; 8192];
+        while left > 0 {
+            let chunk = &mut buf[..left.min(8192) as usize];
+            read_index(reader, chunk)?;
+            crc.update(chunk);
+            left -= chunk.len() as u64;
+        }
+    }
+    let mut tail = [0u8; 16];
+    read_index(reader, &mut tail)?;
+    if crc.finish().to_be_bytes() != tail[..4] {
+        return Err(ParserError::Format("Контрольная сумма индекса не совпадает".into()));
+    }
+    if &tail[12..] != FOOTER_MAGIC {
+        return Err(ParserError::Format("Нет футера индекса".into()));
+    }
+    if u64::from_be_bytes(tail[4..12].try_into().unwrap_or_default()) != offset {
+        return Err(ParserError::Format("Неверное смещение индекса".into()));
+    }
+    match reader.read(&mut [0u8; 1])? {
+        0 => Ok(()),
+        _ => Err(ParserError::Format("Данные после индекса".into())),
+    }
+}
+
+/// `read_exact`, для которого конец данных — оборванный индекс.
+fn read_index<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => ParserError::Format("Индекс оборвался".into()),
+        _ => e.into(),
+    })
+}
+
+/// Разбирает индекс по байтам после `YPBI` (вместе с CRC).
+fn parse_index(bytes: &[u8]) -> Result<Index> {
+    let invalid = || ParserError::Format("Повреждённый индекс".into());
+    let (body, crc) = bytes.split_at(bytes.len().checked_sub(4).ok_or_else(invalid)?);
+    if crc32(body).to_be_bytes() != crc {
+        return Err(ParserError::Format("Контрольная сумма индекса не совпадает".into()));
+    }
+    let mut pos = 0;
+    let mut take = |n: usize| -> Result<&[u8]> {
+        let slice = body.get(pos..pos + n).ok_or_else(invalid)?;
+        pos += n;
+        Ok(slice)
+    };
+    let u64_at = |b: &[u8]| u64::from_be_bytes(b.try_into().unwrap_or_default());
+
+    let count = u64_at(take(8)?) as usize;
+    let mut tx_ids = Vec::with_capacity(count.min(body.len() / 16));
+    for _ in 0..count {
+        let entry = take(16)?;
+        tx_ids.push((u64_at(&entry[..8]), u64_at(&entry[8..])));
+    }
+    let count = u64_at(take(8)?) as usize;
+    let mut blocks = Vec::with_capacity(count.min(body.len() / 28));
+    for _ in 0..count {
+        let entry = take(28)?;
+        blocks.push(Block {
+            offset: u64_at(&entry[..8]),
+            records: u32::from_be_bytes(entry[8..12].try_into().unwrap_or_default()),
+            min_timestamp: u64_at(&entry[12..20]),
+            max_timestamp: u64_at(&entry[20..28]),
+        });
+    }
+    Ok(Index { tx_ids, blocks })
+}
+
+/// Чтение с произвольным доступом. Без индекса (v1 или v2 без флага)
+/// поиск идёт последовательным просмотром.
+pub struct IndexedReader<R: Read + Seek> {
+    reader: BufReader<R>,
+    header: Option<FileHeader>,
+    /// Смещение первой записи.
+    start: u64,
+    crc: bool,
+    index: Option<Index>,
+}
+
+impl<R: Read + Seek> IndexedReader<R> {
+    pub fn open(reader: R) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let header = match bin_format::read_magic(&mut reader)? {
+            Some(magic) if &magic == bin_format::FILE_MAGIC => Some(bin_format::read_header(&mut reader)?),
+            _ => None,
+        };
+        let start = header.as_ref().map_or(0, FileHeader::encoded_len);
+        let crc = header.as_ref().is_some_and(|h| h.has(FLAG_RECORD_CRC));
+
+        let mut index = None;
+        if header.as_ref().is_some_and(|h| h.has(FLAG_INDEX)) {
+            let len = reader.seek(SeekFrom::End(0))?;
+            let footer_at = len.checked_sub(12).ok_or_else(|| ParserError::Format("Нет футера индекса".into()))?;
+            reader.seek(SeekFrom::Start(footer_at))?;
+            let mut footer = [0u8; 12];
+            reader.read_exact(&mut footer)?;
+            if &footer[8..] != FOOTER_MAGIC {
+                return Err(ParserError::Format("Нет футера индекса".into()));
+            }
+            let offset = u64::from_be_bytes(footer[..8].try_into().unwrap_or_default());
+            if offset < start || offset.checked_add(4).is_none_or(|end| end > footer_at) {
+                return Err(ParserError::Format("Неверное смещение индекса".into()));
+            }
+            reader.seek(SeekFrom::Start(offset))?;
+            let mut magic = [0u8; 4];
+            reader.read_exact(&mut magic)?;
+            if &magic != INDEX_MAGIC {
+                return Err(ParserError::Format("Неверная сигнатура индекса".into()));
+            }
+            let mut bytes = vec![0u8; (footer_at - offset - 4) as usize];
+            reader.read_exact(&mut bytes)?;
+            index = Some(parse_index(&bytes)?);
+        }
+        Ok(IndexedReader { reader, header, start, crc, index })
+    }
+
+    pub fn header(&self) -> Option<&FileHeader> {
+        self.header.as_ref()
+    }
+
+    pub fn index(&self) -> Option<&Index> {
+        self.index.as_ref()
+    }
+
+    /// Первая транзакция с `tx_id`.
+    pub fn get(&mut self, tx_id: u64) -> Result<Option<Transaction>> {
+        let Some(index) = &self.index else {
+            return self.range_scan(vec![(self.start, None)], move |tx| tx.tx_id == tx_id).next().transpose();
+        };
+        let i = index.tx_ids.partition_point(|&(id, _)| id < tx_id);
+        match index.tx_ids.get(i) {
+            Some(&(id, offset)) if id == tx_id => {
+                self.reader.seek(SeekFrom::Start(offset))?;
+                bin_format::read_record(&mut self.reader, self.crc).map_err(|e| bin_format::locate(e, 0, offset))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Транзакции с `from <= TIMESTAMP <= to` в порядке файла.
+    pub fn range(&mut self, from: u64, to: u64) -> RangeScan<'_, R> {
+        let plan = match &self.index {
+            Some(index) => index
+                .blocks
+                .iter()
+                .filter(|b| b.max_timestamp >= from && b.min_timestamp <= to)
+                .map(|b| (b.offset, Some(b.records)))
+                .collect(),
+            None => vec![(self.start, None)],
+        };
+        self.range_scan(plan, move |tx| (from..=to).contains(&tx.timestamp))
+    }
+
+    fn range_scan<F>(&mut self, plan: Vec<(u64, Option<u32>)>, keep: F) -> RangeScan<'_, R>
+    where
+        F: Fn(&Transaction) -> bool + 'static,
+    {
+        RangeScan { reader: self, plan: plan.into_iter(), remaining: Some(0), keep: Box::new(keep), done: false }
+    }
+}
+
+/// Просмотр выбранных блоков (или всего файла) с отбором записей.
+pub struct RangeScan<'a, R: Read + Seek> {
+    reader: &'a mut IndexedReader<R>,
+    plan: std::vec::IntoIter<(u64, Option<u32>)>,
+    /// Сколько записей осталось в текущем блоке; `None` — до конца записей.
+    remaining: Option<u32>,
+    keep: Box<dyn Fn(&Transaction) -> bool>,
+    done: bool,
+}
+
+impl<R: Read + Seek> Iterator for RangeScan<'_, R> {
+    type Item = Result<Transaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if self.remaining == Some(0) {
+                let Some((offset, records)) = self.plan.next() else {
+                    self.done = true;
+                    break;
+                };
+                if let Err(e) = self.reader.reader.seek(SeekFrom::Start(offset)) {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+                self.remaining = records;
+            }
+            match bin_format::read_record(&mut self.reader.reader, self.reader.crc) {
+                Ok(Some(tx)) => {
+                    if let Some(n) = &mut self.remaining {
+                        *n -= 1;
+                    }
+                    if (self.keep)(&tx) {
+                        return Some(Ok(tx));
+                    }
+                }
+                // Трейлер или конец файла: записей дальше нет.
+                Ok(None) => self.remaining = Some(0),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
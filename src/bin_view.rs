@@ -0,0 +1,225 @@
+//! Чтение YPBankBin из среза байт без копирования.
+//!
+//! [`SliceReader`] разбирает файл, целиком лежащий в памяти (прочитанный или
+//! отображённый в память), и выдаёт [`TransactionRef`] — ссылки на тела
+//! записей. Числовые поля читаются по запросу, описание проверяется на UTF-8
+//! только при обращении к нему. Версии, CRC и трейлер проверяются так же, как
+//! в [`bin_format::Reader`].
+
+use crate::bin_format::{self, FileHeader, Trailer, FLAG_INDEX, FLAG_RECORD_CRC, FLAG_TRAILER};
+use crate::crc32::crc32;
+use crate::{Transaction, TxType, TxStatus, Result, ParserError, Truncation};
+
+/// Размер тела записи без описания.
+const FIXED_LEN: usize = 46;
+
+/// Транзакция, заимствованная из тела записи.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionRef<'a> {
+    body: &'a [u8],
+}
+
+impl<'a> TransactionRef<'a> {
+    /// Проверяет длины и перечисления; описание не проверяется.
+    pub fn new(body: &'a [u8]) -> Result<Self> {
+        let tx = TransactionRef { body };
+        if body.len() < FIXED_LEN || body.len() != FIXED_LEN + tx.u32_at(42) as usize {
+            return Err(ParserError::Format("Размер записи не совпадает с DESC_LEN".into()));
+        }
+        bin_format::decode_type(body[8])?;
+        bin_format::decode_status(body[41])?;
+        Ok(tx)
+    }
+
+    fn u64_at(&self, pos: usize) -> u64 {
+        u64::from_be_bytes(self.body[pos..pos + 8].try_into().unwrap_or_default())
+    }
+
+    fn u32_at(&self, pos: usize) -> u32 {
+        self.body.get(pos..pos + 4).map_or(0, |b| u32::from_be_bytes(b.try_into().unwrap_or_default()))
+    }
+
+    pub fn tx_id(&self) -> u64 {
+        self.u64_at(0)
+    }
+
+    pub fn tx_type(&self) -> TxType {
+        bin_format::decode_type(self.body[8]).unwrap_or(TxType::Deposit)
+    }
+
+    pub fn from_user_id(&self) -> u64 {
+        self.u64_at(9)
+    }
+
+    pub fn to_user_id(&self) -> u64 {
+        self.u64_at(17)
+    }
+
+    pub fn amount(&self) -> i64 {
+        self.u64_at(25) as i64
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.u64_at(33)
+    }
+
+    pub fn status(&self) -> TxStatus {
+        bin_format::decode_status(self.body[41]).unwrap_or(TxStatus::Success)
+    }
+
+    /// Байты описания как есть.
+    pub fn description_bytes(&self) -> &'a [u8] {
+        &self.body[FIXED_LEN..]
+    }
+
+    /// Описание с проверкой UTF-8.
+    pub fn description(&self) -> Result<&'a str> {
+        std::str::from_utf8(self.description_bytes()).map_err(|e| {
+            ParserError::Format(format!("Описание TX_ID {} не в UTF-8: {}", self.tx_id(), e))
+        })
+    }
+
+    /// Владеющая копия.
+    pub fn to_transaction(&self) -> Result<Transaction> {
+        Ok(Transaction {
+            tx_id: self.tx_id(),
+            tx_type: self.tx_type(),
+            from_user_id: self.from_user_id(),
+            to_user_id: self.to_user_id(),
+            amount: self.amount(),
+            timestamp: self.timestamp(),
+            status: self.status(),
+            description: self.description()?.to_string(),
+        })
+    }
+}
+
+/// Потоковое чтение записей из среза, после ошибки итерация заканчивается.
+pub struct SliceReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    header: Option<FileHeader>,
+    started: bool,
+    done: bool,
+    seen: Trailer,
+    trailer: Option<Trailer>,
+}
+
+impl<'a> SliceReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        SliceReader { data, pos: 0, header: None, started: false, done: false, seen: Trailer::default(), trailer: None }
+    }
+
+    /// Заголовок v2; известен после первого вызова `next`.
+    pub fn header(&self) -> Option<&FileHeader> {
+        self.header.as_ref()
+    }
+
+    /// Трейлер v2; известен после окончания чтения.
+    pub fn trailer(&self) -> Option<&Trailer> {
+        self.trailer.as_ref()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let data: &'a [u8] = self.data;
+        let slice = data.get(self.pos..self.pos + n).ok_or_else(|| {
+            ParserError::Format(format!("Файл оборвался: нужно {} байт со смещения {}", n, self.pos))
+        })?;
+        self.pos += n;
+        Ok(slice)
+    }
+
+    /// Обрыв: от текущей позиции нужно ещё `need` байт до конца записи.
+    fn truncated(&self, record: u64, offset: u64, field: &'static str, need: usize) -> ParserError {
+        let missing = (need - (self.data.len() - self.pos)) as u64;
+        ParserError::Truncated(Truncation { record, offset, field, missing })
+    }
+
+    fn has(&self, flag: u16) -> bool {
+        self.header.as_ref().is_some_and(|h| h.has(flag))
+    }
+
+    fn read_next(&mut self) -> Result<Option<TransactionRef<'a>>> {
+        if !self.started {
+            self.started = true;
+            if self.data.starts_with(bin_format::FILE_MAGIC) {
+                let mut rest = &self.data[4..];
+                let header = bin_format::read_header(&mut rest)?;
+                self.pos = header.encoded_len() as usize;
+                self.header = Some(header);
+            }
+        }
+
+        if self.pos == self.data.len() {
+            if self.has(FLAG_TRAILER) {
+                return Err(ParserError::Format("Файл оборвался: нет трейлера".into()));
+            }
+            return Ok(None);
+        }
+
+        let (record, start) = (self.seen.record_count + 1, self.pos as u64);
+        let magic = self.take(4).map_err(|_| self.truncated(record, start, "MAGIC", 4))?;
+        if magic == bin_format::TRAILER_MAGIC && self.has(FLAG_TRAILER) {
+            let mut rest = self.take(28)?;
+            let trailer = bin_format::read_trailer(&mut rest)?;
+            if trailer != self.seen {
+                return Err(ParserError::Format(format!(
+                    "Трейлер не совпадает с данными: записей {} (в трейлере {}), сумма {} (в трейлере {})",
+                    self.seen.record_count, trailer.record_count, self.seen.amount_sum, trailer.amount_sum
+                )));
+            }
+            let rest = &self.data[self.pos..];
+            if self.has(FLAG_INDEX) {
+                if !rest.starts_with(crate::bin_index::INDEX_MAGIC) {
+                    return Err(ParserError::Format("Нет индекса после трейлера".into()));
+                }
+                crate::bin_index::check_index(&mut &rest[4..], self.pos as u64)?;
+            } else if !rest.is_empty() {
+                return Err(ParserError::Format("Данные после трейлера".into()));
+            }
+            self.trailer = Some(trailer);
+            return Ok(None);
+        }
+        if magic != bin_format::MAGIC {
+            return Err(ParserError::Format(format!("Неверный заголовок MAGIC записи {} (смещение {})", record, start)));
+        }
+
+        let size = self.take(4).map_err(|_| self.truncated(record, start, "RECORD_SIZE", 4))?;
+        let size = u32::from_be_bytes(size.try_into().unwrap_or_default()) as usize;
+        let crc_len = if self.has(FLAG_RECORD_CRC) { 4 } else { 0 };
+        let body = self.take(size).map_err(|_| {
+            let field = bin_format::body_field(self.data.len() - self.pos);
+            self.truncated(record, start, field, size + crc_len)
+        })?;
+        if self.has(FLAG_RECORD_CRC) {
+            let expected = self.take(4).map_err(|_| self.truncated(record, start, "CRC32", 4))?;
+            let expected = u32::from_be_bytes(expected.try_into().unwrap_or_default());
+            if crc32(body) != expected {
+                return Err(ParserError::Format(format!(
+                    "Контрольная сумма записи {} (TX_ID {}) не совпадает",
+                    record,
+                    bin_format::body_tx_id(body)
+                )));
+            }
+        }
+        let tx = TransactionRef::new(body)?;
+        self.seen.record_count += 1;
+        self.seen.amount_sum += tx.amount() as i128;
+        Ok(Some(tx))
+    }
+}
+
+impl<'a> Iterator for SliceReader<'a> {
+    type Item = Result<TransactionRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_next().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
@@ -0,0 +1,141 @@
+//! Потоковое окно для побайтового разбора YPBankBin (восстановление и
+//! разбор по полям): файл не держится в памяти целиком, пропускаемые
+//! участки не копятся.
+
+use std::io::{self, Read};
+use crate::bin_format;
+
+/// Сколько байт дочитывается из потока за раз.
+const WINDOW_CHUNK: usize = 64 * 1024;
+
+/// Окно над потоком для побайтового разбора файла, который не нужно
+/// держать в памяти целиком. Хранит байты от текущей позиции (или от метки,
+/// если она поставлена) и дочитывает поток по мере надобности; пропуск до
+/// следующей сигнатуры не копит пропущенное.
+pub(crate) struct Window<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// Смещение в потоке первого байта `buf`.
+    base: u64,
+    /// Текущая позиция внутри `buf`.
+    at: usize,
+    /// Начало участка, который нельзя выбрасывать.
+    mark: Option<usize>,
+    eof: bool,
+}
+
+impl<R: Read> Window<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Window { reader, buf: Vec::new(), base: 0, at: 0, mark: None, eof: false }
+    }
+
+    /// Смещение текущей позиции от начала потока.
+    pub(crate) fn pos(&self) -> u64 {
+        self.base + self.at as u64
+    }
+
+    /// До `n` байт с текущей позиции; меньше — только в конце потока.
+    pub(crate) fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        self.fill(n)?;
+        let end = self.buf.len().min(self.at + n);
+        Ok(&self.buf[self.at..end])
+    }
+
+    pub(crate) fn is_at_end(&mut self) -> io::Result<bool> {
+        Ok(self.peek(1)?.is_empty())
+    }
+
+    /// Сдвигает позицию на `n` уже прочитанных `peek` байт.
+    pub(crate) fn advance(&mut self, n: usize) {
+        self.at = self.buf.len().min(self.at + n);
+    }
+
+    /// Запоминает текущую позицию: байты от неё до новой позиции остаются
+    /// доступны через [`Window::marked`].
+    pub(crate) fn set_mark(&mut self) {
+        self.mark = Some(self.at);
+    }
+
+    pub(crate) fn clear_mark(&mut self) {
+        self.mark = None;
+    }
+
+    /// Байты от метки до текущей позиции.
+    pub(crate) fn marked(&self) -> &[u8] {
+        &self.buf[self.mark.unwrap_or(self.at)..self.at]
+    }
+
+    /// Пропускает байты до следующей сигнатуры записи (или трейлера) после
+    /// текущей позиции либо до конца потока. Возвращает длину пропущенного,
+    /// его первые `head` байт и признак того, что дошли до конца.
+    pub(crate) fn skip_to_record(&mut self, trailer: bool, head: usize) -> io::Result<(u64, Vec<u8>, bool)> {
+        self.skip(head, |w| w == bin_format::MAGIC || (trailer && w == bin_format::TRAILER_MAGIC))
+    }
+
+    /// Пропускает всё до конца потока.
+    pub(crate) fn skip_to_end(&mut self, head: usize) -> io::Result<(u64, Vec<u8>)> {
+        let (len, head, _) = self.skip(head, |_| false)?;
+        Ok((len, head))
+    }
+
+    fn skip(&mut self, head: usize, found: impl Fn(&[u8]) -> bool) -> io::Result<(u64, Vec<u8>, bool)> {
+        let start = self.pos();
+        let head = self.peek(head)?.to_vec();
+        self.advance(1);
+        loop {
+            let avail = self.peek(WINDOW_CHUNK)?;
+            if avail.len() < 4 {
+                let n = avail.len();
+                self.advance(n);
+                return Ok((self.pos() - start, head, true));
+            }
+            if let Some(i) = avail.windows(4).position(&found) {
+                self.advance(i);
+                return Ok((self.pos() - start, head, false));
+            }
+            // Последние три байта могут оказаться началом сигнатуры.
+            let n = avail.len() - 3;
+            self.advance(n);
+        }
+    }
+
+    fn fill(&mut self, n: usize) -> io::Result<()> {
+        if self.at + n <= self.buf.len() || self.eof {
+            return Ok(());
+        }
+        let keep = self.mark.unwrap_or(self.at);
+        if keep > 0 {
+            self.buf.drain(..keep);
+            self.base += keep as u64;
+            self.at -= keep;
+            self.mark = self.mark.map(|_| 0);
+        }
+        let want = self.at + n;
+        while self.buf.len() < want {
+            let len = self.buf.len();
+            self.buf.resize(len + WINDOW_CHUNK, 0);
+            let read = self.reader.read(&mut self.buf[len..]);
+            self.buf.truncate(len + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(0) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Window<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let avail = self.peek(out.len())?;
+        let n = avail.len();
+        out[..n].copy_from_slice(avail);
+        self.advance(n);
+        Ok(n)
+    }
+}
@@ -0,0 +1,121 @@
+//! Календарные даты по `TIMESTAMP` (мс от эпохи Unix) со сдвигом от UTC.
+
+use std::fmt;
+use std::str::FromStr;
+use crate::{Result, ParserError};
+
+const MS_PER_DAY: i64 = 86_400_000;
+
+/// Дата по григорианскому календарю.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// Местная дата для `TIMESTAMP` при сдвиге `offset_minutes` от UTC.
+    pub fn from_timestamp(timestamp: u64, offset_minutes: i32) -> Self {
+        // В i128 сумма не переполняется при любом `u64`, а число дней
+        // после деления всегда помещается в i64.
+        let local = timestamp as i128 + offset_minutes as i128 * 60_000;
+        Self::from_days(local.div_euclid(MS_PER_DAY as i128) as i64)
+    }
+
+    /// Дата по числу дней от 1970-01-01 (алгоритм Howard Hinnant).
+    pub fn from_days(days: i64) -> Self {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+        Date { year, month, day }
+    }
+
+    /// Число дней от 1970-01-01.
+    pub fn to_days(self) -> i64 {
+        let y = self.year as i64 - i64::from(self.month <= 2);
+        let era = y.div_euclid(400);
+        let yoe = y.rem_euclid(400);
+        let m = self.month as i64;
+        let doy = (153 * if m > 2 { m - 3 } else { m + 9 } + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    /// `TIMESTAMP` местной полуночи этой даты.
+    pub fn start_timestamp(self, offset_minutes: i32) -> i64 {
+        self.to_days().saturating_mul(MS_PER_DAY).saturating_sub(offset_minutes as i64 * 60_000)
+    }
+
+    /// Понедельник той же недели (ISO 8601).
+    pub fn start_of_week(self) -> Self {
+        let days = self.to_days();
+        // 1970-01-01 — четверг, поэтому понедельник даёт остаток 0 при сдвиге на 3.
+        Self::from_days(days - (days + 3).rem_euclid(7))
+    }
+
+    /// Первое число того же месяца.
+    pub fn start_of_month(self) -> Self {
+        Date { day: 1, ..self }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = ParserError;
+
+    /// `YYYY-MM-DD`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || ParserError::Format(format!("Неверная дата: {}", s));
+        let mut parts = s.splitn(3, '-');
+        let (Some(y), Some(m), Some(d)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        let date = Date {
+            year: y.parse().map_err(|_| invalid())?,
+            month: m.parse().map_err(|_| invalid())?,
+            day: d.parse().map_err(|_| invalid())?,
+        };
+        // Проверка через обратное преобразование отсекает 2023-02-30 и т.п.
+        if date.month == 0 || date.day == 0 || Date::from_days(date.to_days()) != date {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
+
+/// Сдвиг от UTC в минутах: `+03:00`, `-05:30`, `+3`, `0`.
+pub fn parse_utc_offset(s: &str) -> Result<i32> {
+    let invalid = || ParserError::Format(format!("Неверный сдвиг от UTC: {}", s));
+    let (sign, rest) = match s.as_bytes().first() {
+        Some(b'-') => (-1, &s[1..]),
+        Some(b'+') => (1, &s[1..]),
+        _ => (1, s),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    // Без знака: `+03:-30` и `--3` не проходят.
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 14 || minutes >= 60 {
+        return Err(invalid());
+    }
+    Ok(sign * (hours * 60 + minutes) as i32)
+}
+
+/// `+03:00` для 180 минут.
+pub fn format_utc_offset(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let abs = minutes.unsigned_abs();
+    format!("{}{:02}:{:02}", sign, abs / 60, abs % 60)
+}
@@ -0,0 +1,118 @@
+//! Общее для консольных утилит: разбор аргументов, открытие файлов с
+//! понятной ошибкой и разбор значений аргументов.
+
+use std::env;
+use std::fs::File;
+use std::path::Path;
+use crate::compare::ReportFormat;
+use crate::filter::Filter;
+use crate::format::{Format, Transactions};
+use crate::{Transaction, Result, ParserError};
+
+/// Аргументы командной строки: флаги `--имя` и `--имя значение`.
+/// Неизвестные аргументы пропускаются.
+///
+/// ```no_run
+/// let mut args = parser::cli::Args::from_env();
+/// let mut input = None;
+/// let mut quiet = false;
+/// while let Some(arg) = args.next() {
+///     match arg.as_str() {
+///         "--input" => input = Some(args.value()?),
+///         "--quiet" => quiet = true,
+///         _ => {}
+///     }
+/// }
+/// # Ok::<(), parser::ParserError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Args {
+    args: Vec<String>,
+    pos: usize,
+}
+
+impl Args {
+    /// Аргументы процесса без имени программы.
+    pub fn from_env() -> Self {
+        Args { args: env::args().skip(1).collect(), pos: 0 }
+    }
+
+    /// Значение флага, только что полученного из `next`.
+    pub fn value(&mut self) -> Result<String> {
+        let value = self.args.get(self.pos).cloned().ok_or_else(|| {
+            let flag = self.pos.checked_sub(1).and_then(|i| self.args.get(i)).map_or("", String::as_str);
+            ParserError::Format(format!("Нет значения для {}", flag))
+        })?;
+        self.pos += 1;
+        Ok(value)
+    }
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let arg = self.args.get(self.pos).cloned()?;
+        self.pos += 1;
+        Some(arg)
+    }
+}
+
+/// Ошибка о недостающих аргументах: `usage("--input <файл>")`.
+pub fn usage(args: &str) -> ParserError {
+    ParserError::Format(format!("Ошибка: Используйте {}", args))
+}
+
+/// Открывает файл, указанный в аргументе `arg` (например, `--input`).
+pub fn open_file(path: impl AsRef<Path>, arg: &str) -> Result<File> {
+    let path = path.as_ref();
+    File::open(path).map_err(|e| {
+        ParserError::Format(format!("Не удалось открыть входной файл ({}) '{}': {}", arg, path.display(), e))
+    })
+}
+
+/// Создаёт выходной файл, указанный в аргументе `arg`.
+pub fn create_file(path: impl AsRef<Path>, arg: &str) -> Result<File> {
+    let path = path.as_ref();
+    File::create(path).map_err(|e| {
+        ParserError::Format(format!("Не удалось создать выходной файл ({}) '{}': {}", arg, path.display(), e))
+    })
+}
+
+/// Поток транзакций файла; с фильтром — только подходящие.
+pub fn open_transactions(
+    path: impl AsRef<Path>,
+    format: Format,
+    arg: &str,
+    filter: Option<Filter>,
+) -> Result<Transactions<'static>> {
+    Ok(format.file_reader(open_file(path, arg)?, filter))
+}
+
+/// Все транзакции файла в памяти.
+pub fn load_transactions(
+    path: impl AsRef<Path>,
+    format: Format,
+    arg: &str,
+    filter: Option<Filter>,
+) -> Result<Vec<Transaction>> {
+    open_transactions(path, format, arg, filter)?.collect()
+}
+
+/// Формат отчёта, у которого нет табличного вида: CSV отклоняется сразу,
+/// до чтения входных файлов.
+pub fn report_format(value: &str, arg: &str) -> Result<ReportFormat> {
+    match value.parse()? {
+        ReportFormat::Csv => Err(ParserError::Format(format!("Отчёт ({}) выводится как text или json", arg))),
+        format => Ok(format),
+    }
+}
+
+/// Формат итогов, у которых нет текстового вида: text отклоняется сразу,
+/// до чтения входных файлов.
+pub fn table_format(value: &str, arg: &str) -> Result<ReportFormat> {
+    match value.parse()? {
+        ReportFormat::Text => Err(ParserError::Format(format!("Итоги ({}) выводятся как csv или json", arg))),
+        format => Ok(format),
+    }
+}
@@ -0,0 +1,498 @@
+//! Колоночный формат YPBankColumnar для аналитики.
+//!
+//! Файл — `YPCF`, версия (u16) и группы строк. Группа хранит каждое поле
+//! отдельным блоком (column chunk) и начинается с заголовка с длинами блоков
+//! и минимальными/максимальными значениями полей:
+//!
+//! ```text
+//! YPCG | ROWS u32 | 8 × (LEN u64, CRC32 u32) | MIN | MAX | HEADER_CRC32 u32
+//! TX_ID | TX_TYPE | FROM_USER_ID | TO_USER_ID | AMOUNT | TIMESTAMP | STATUS | DESCRIPTION
+//! ```
+//!
+//! Числа — big-endian по 8 байт, `TX_TYPE` и `STATUS` — по байту, описания —
+//! `ROWS` длин u32 и затем байты подряд. `MIN` и `MAX` — поля `TX_ID`…`STATUS`
+//! в том же порядке (42 байта). [`Reader`] пропускает группы, которые по
+//! статистике не могут пройти фильтр; внутри библиотеки он умеет читать
+//! только нужные поля (см. [`crate::stats::Stats::add_columnar`]).
+
+use std::collections::VecDeque;
+use std::io::{self, BufReader, Read, Seek, Write};
+use crate::bin_format::{decode_status, decode_type, encode_status, encode_type};
+use crate::crc32::crc32;
+use crate::field::{Field, FieldValue};
+use crate::filter::Filter;
+use crate::{Transaction, TxType, TxStatus, Result, ParserError};
+
+/// Сигнатура файла.
+const FILE_MAGIC: &[u8; 4] = b"YPCF";
+
+/// Сигнатура группы строк.
+const GROUP_MAGIC: &[u8; 4] = b"YPCG";
+
+const VERSION: u16 = 1;
+
+/// Строк в группе по умолчанию.
+pub const DEFAULT_ROW_GROUP: usize = 4096;
+
+/// Порядок блоков в группе.
+const COLUMNS: [Field; 8] = [
+    Field::TxId,
+    Field::TxType,
+    Field::FromUserId,
+    Field::ToUserId,
+    Field::Amount,
+    Field::Timestamp,
+    Field::Status,
+    Field::Description,
+];
+
+/// Размер `MIN`/`MAX` в заголовке группы.
+const BOUND_LEN: usize = 42;
+
+/// Размер заголовка группы после `YPCG`.
+const HEADER_LEN: usize = 4 + COLUMNS.len() * 12 + 2 * BOUND_LEN + 4;
+
+/// Читаем записи до EOF.
+pub fn from_read<R: Read>(reader: R) -> Result<Vec<Transaction>> {
+    Reader::new(reader).collect()
+}
+
+/// Пишет транзакции группами по [`DEFAULT_ROW_GROUP`].
+pub fn write_to<W: Write>(writer: W, transactions: &[Transaction]) -> Result<()> {
+    let mut writer = Writer::new(writer);
+    for tx in transactions {
+        writer.write(tx)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Статистика группы строк.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowGroupStats {
+    rows: u32,
+    /// Поле за полем наименьшие значения; описание пустое.
+    min: Transaction,
+    max: Transaction,
+}
+
+impl RowGroupStats {
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Наименьшее и наибольшее значения поля; для `DESCRIPTION` — `None`.
+    pub fn bounds(&self, field: Field) -> Option<(FieldValue, FieldValue)> {
+        (field != Field::Description).then(|| (self.min.field(field), self.max.field(field)))
+    }
+}
+
+fn empty_row() -> Transaction {
+    Transaction {
+        tx_id: 0,
+        tx_type: TxType::Deposit,
+        from_user_id: 0,
+        to_user_id: 0,
+        amount: 0,
+        timestamp: 0,
+        status: TxStatus::Success,
+        description: String::new(),
+    }
+}
+
+/// Потоковая запись: строки копятся до размера группы.
+pub struct Writer<W: Write> {
+    writer: W,
+    header_written: bool,
+    row_group: usize,
+    rows: Vec<Transaction>,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Writer::with_row_group(writer, DEFAULT_ROW_GROUP)
+    }
+
+    /// Запись с `rows` строк в группе.
+    pub fn with_row_group(writer: W, rows: usize) -> Self {
+        let row_group = rows.clamp(1, u32::MAX as usize);
+        Writer { writer, header_written: false, row_group, rows: Vec::new() }
+    }
+
+    /// Запись в конец файла, где уже есть заголовок: дописываются только группы.
+    pub fn appending(writer: W) -> Self {
+        Writer { header_written: true, ..Writer::new(writer) }
+    }
+
+    pub fn write(&mut self, tx: &Transaction) -> Result<()> {
+        self.rows.push(tx.clone());
+        if self.rows.len() >= self.row_group {
+            self.flush_group()?;
+        }
+        Ok(())
+    }
+
+    /// Дописывает неполную группу, сбрасывает буферы и возвращает исходный writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.flush_group()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn flush_group(&mut self) -> Result<()> {
+        if !self.header_written {
+            self.header_written = true;
+            self.writer.write_all(FILE_MAGIC)?;
+            self.writer.write_all(&VERSION.to_be_bytes())?;
+        }
+        let Some(first) = self.rows.first() else {
+            return Ok(());
+        };
+
+        let mut min = Transaction { description: String::new(), ..first.clone() };
+        let mut max = min.clone();
+        for tx in &self.rows {
+            min.tx_id = min.tx_id.min(tx.tx_id);
+            max.tx_id = max.tx_id.max(tx.tx_id);
+            min.tx_type = min.tx_type.min(tx.tx_type);
+            max.tx_type = max.tx_type.max(tx.tx_type);
+            min.from_user_id = min.from_user_id.min(tx.from_user_id);
+            max.from_user_id = max.from_user_id.max(tx.from_user_id);
+            min.to_user_id = min.to_user_id.min(tx.to_user_id);
+            max.to_user_id = max.to_user_id.max(tx.to_user_id);
+            min.amount = min.amount.min(tx.amount);
+            max.amount = max.amount.max(tx.amount);
+            min.timestamp = min.timestamp.min(tx.timestamp);
+            max.timestamp = max.timestamp.max(tx.timestamp);
+            min.status = min.status.min(tx.status);
+            max.status = max.status.max(tx.status);
+        }
+
+        let chunks: Vec<Vec<u8>> = COLUMNS.iter().map(|&field| encode_column(&self.rows, field)).collect();
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&(self.rows.len() as u32).to_be_bytes());
+        for chunk in &chunks {
+            header.extend_from_slice(&(chunk.len() as u64).to_be_bytes());
+            header.extend_from_slice(&crc32(chunk).to_be_bytes());
+        }
+        encode_bound(&mut header, &min);
+        encode_bound(&mut header, &max);
+        header.extend_from_slice(&crc32(&header).to_be_bytes());
+
+        self.writer.write_all(GROUP_MAGIC)?;
+        self.writer.write_all(&header)?;
+        for chunk in &chunks {
+            self.writer.write_all(chunk)?;
+        }
+        self.rows.clear();
+        Ok(())
+    }
+}
+
+fn encode_bound(buf: &mut Vec<u8>, tx: &Transaction) {
+    buf.extend_from_slice(&tx.tx_id.to_be_bytes());
+    buf.push(encode_type(tx.tx_type));
+    buf.extend_from_slice(&tx.from_user_id.to_be_bytes());
+    buf.extend_from_slice(&tx.to_user_id.to_be_bytes());
+    buf.extend_from_slice(&tx.amount.to_be_bytes());
+    buf.extend_from_slice(&tx.timestamp.to_be_bytes());
+    buf.push(encode_status(tx.status));
+}
+
+fn decode_bound(buf: &[u8]) -> Result<Transaction> {
+    let u64_at = |pos: usize| u64::from_be_bytes(buf[pos..pos + 8].try_into().unwrap_or_default());
+    Ok(Transaction {
+        tx_id: u64_at(0),
+        tx_type: decode_type(buf[8])?,
+        from_user_id: u64_at(9),
+        to_user_id: u64_at(17),
+        amount: u64_at(25) as i64,
+        timestamp: u64_at(33),
+        status: decode_status(buf[41])?,
+        description: String::new(),
+    })
+}
+
+fn encode_column(rows: &[Transaction], field: Field) -> Vec<u8> {
+    let mut buf = Vec::new();
+    match field {
+        Field::TxType => buf.extend(rows.iter().map(|tx| encode_type(tx.tx_type))),
+        Field::Status => buf.extend(rows.iter().map(|tx| encode_status(tx.status))),
+        Field::Description => {
+            for tx in rows {
+                buf.extend_from_slice(&(tx.description.len() as u32).to_be_bytes());
+            }
+            for tx in rows {
+                buf.extend_from_slice(tx.description.as_bytes());
+            }
+        }
+        _ => {
+            for tx in rows {
+                let value = match field {
+                    Field::TxId => tx.tx_id,
+                    Field::FromUserId => tx.from_user_id,
+                    Field::ToUserId => tx.to_user_id,
+                    Field::Amount => tx.amount as u64,
+                    _ => tx.timestamp,
+                };
+                buf.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+    buf
+}
+
+/// Заполняет поле `field` во всех строках группы из блока.
+fn decode_column(rows: &mut [Transaction], field: Field, chunk: &[u8]) -> Result<()> {
+    let invalid = || ParserError::Format(format!("Неверный размер блока {}", field));
+    match field {
+        Field::TxType | Field::Status => {
+            if chunk.len() != rows.len() {
+                return Err(invalid());
+            }
+            for (tx, &byte) in rows.iter_mut().zip(chunk) {
+                if field == Field::TxType {
+                    tx.tx_type = decode_type(byte)?;
+                } else {
+                    tx.status = decode_status(byte)?;
+                }
+            }
+        }
+        Field::Description => {
+            let (lengths, mut text) = chunk.split_at_checked(rows.len() * 4).ok_or_else(invalid)?;
+            for (tx, len) in rows.iter_mut().zip(lengths.chunks_exact(4)) {
+                let len = u32::from_be_bytes(len.try_into().unwrap_or_default()) as usize;
+                let (bytes, rest) = text.split_at_checked(len).ok_or_else(invalid)?;
+                tx.description = String::from_utf8(bytes.to_vec())?;
+                text = rest;
+            }
+            if !text.is_empty() {
+                return Err(invalid());
+            }
+        }
+        _ => {
+            if chunk.len() != rows.len() * 8 {
+                return Err(invalid());
+            }
+            for (tx, bytes) in rows.iter_mut().zip(chunk.chunks_exact(8)) {
+                let value = u64::from_be_bytes(bytes.try_into().unwrap_or_default());
+                match field {
+                    Field::TxId => tx.tx_id = value,
+                    Field::FromUserId => tx.from_user_id = value,
+                    Field::ToUserId => tx.to_user_id = value,
+                    Field::Amount => tx.amount = value as i64,
+                    _ => tx.timestamp = value,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Длины блоков должны сходиться с `ROWS`, иначе числу строк нельзя верить.
+fn check_chunk_lengths(header: &GroupHeader) -> Result<()> {
+    let rows = header.stats.rows as u64;
+    for (&field, &(len, _)) in COLUMNS.iter().zip(&header.chunks) {
+        let fits = match field {
+            Field::TxType | Field::Status => len == rows,
+            Field::Description => len >= rows * 4,
+            _ => len == rows * 8,
+        };
+        if !fits {
+            return Err(ParserError::Format(format!("Размер блока {} не сходится с ROWS {}", field, rows)));
+        }
+    }
+    Ok(())
+}
+
+/// Заголовок группы после `YPCG`.
+struct GroupHeader {
+    stats: RowGroupStats,
+    /// Длина и CRC32 блоков в порядке [`COLUMNS`].
+    chunks: Vec<(u64, u32)>,
+}
+
+fn parse_group_header(buf: &[u8; HEADER_LEN]) -> Result<GroupHeader> {
+    let (body, crc) = buf.split_at(HEADER_LEN - 4);
+    if crc32(body).to_be_bytes() != crc {
+        return Err(ParserError::Format("Контрольная сумма заголовка группы не совпадает".into()));
+    }
+    let rows = u32::from_be_bytes(body[..4].try_into().unwrap_or_default());
+    let chunks = body[4..4 + COLUMNS.len() * 12]
+        .chunks_exact(12)
+        .map(|c| {
+            (u64::from_be_bytes(c[..8].try_into().unwrap_or_default()), u32::from_be_bytes(c[8..].try_into().unwrap_or_default()))
+        })
+        .collect();
+    let bounds = &body[4 + COLUMNS.len() * 12..];
+    let min = decode_bound(&bounds[..BOUND_LEN])?;
+    let max = decode_bound(&bounds[BOUND_LEN..])?;
+    Ok(GroupHeader { stats: RowGroupStats { rows, min, max }, chunks })
+}
+
+fn skip_by_read<R: Read>(reader: &mut BufReader<R>, n: u64) -> io::Result<()> {
+    let copied = io::copy(&mut reader.take(n), &mut io::sink())?;
+    if copied < n {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn skip_by_seek<R: Read + Seek>(reader: &mut BufReader<R>, n: u64) -> io::Result<()> {
+    let n = i64::try_from(n).map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+    reader.seek_relative(n)
+}
+
+/// Потоковое чтение по группам. После ошибки итерация заканчивается.
+pub struct Reader<R: Read> {
+    reader: BufReader<R>,
+    skip: fn(&mut BufReader<R>, u64) -> io::Result<()>,
+    columns: [bool; 8],
+    filter: Option<Filter>,
+    started: bool,
+    done: bool,
+    rows: VecDeque<Transaction>,
+    groups_read: usize,
+    groups_skipped: usize,
+}
+
+impl<R: Read> Reader<R> {
+    /// Чтение из потока: ненужные блоки читаются и отбрасываются.
+    pub fn new(reader: R) -> Self {
+        Reader {
+            reader: BufReader::new(reader),
+            skip: skip_by_read::<R>,
+            columns: [true; 8],
+            filter: None,
+            started: false,
+            done: false,
+            rows: VecDeque::new(),
+            groups_read: 0,
+            groups_skipped: 0,
+        }
+    }
+
+    /// Читать только эти поля (и поля фильтра; хотя бы одно поле); остальные
+    /// остаются значениями по умолчанию (`0`, `DEPOSIT`, `SUCCESS`, пустое
+    /// описание), поэтому такие строки наружу не отдаются.
+    pub(crate) fn columns(mut self, fields: &[Field]) -> Self {
+        self.columns = COLUMNS.map(|c| fields.contains(&c));
+        self
+    }
+
+    /// Выдавать только подходящие строки; группы, где подходящих нет по
+    /// статистике, не читаются.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Сколько групп прочитано.
+    pub fn groups_read(&self) -> usize {
+        self.groups_read
+    }
+
+    /// Сколько групп пропущено по статистике.
+    pub fn groups_skipped(&self) -> usize {
+        self.groups_skipped
+    }
+
+    fn read_next(&mut self) -> Result<Option<Transaction>> {
+        if !self.started {
+            self.started = true;
+            let mut buf = [0u8; 6];
+            // Пустой поток — пустой файл, как в остальных форматах.
+            if self.reader.read(&mut buf[..1])? == 0 {
+                return Ok(None);
+            }
+            self.reader.read_exact(&mut buf[1..])?;
+            if &buf[..4] != FILE_MAGIC {
+                return Err(ParserError::Format("Неверная сигнатура колоночного файла".into()));
+            }
+            let version = u16::from_be_bytes([buf[4], buf[5]]);
+            if version != VERSION {
+                return Err(ParserError::Format(format!("Неподдерживаемая версия колоночного файла: {}", version)));
+            }
+            if let Some(filter) = &self.filter {
+                for field in filter.fields() {
+                    if let Some(i) = COLUMNS.iter().position(|&c| c == field) {
+                        self.columns[i] = true;
+                    }
+                }
+            }
+        }
+
+        loop {
+            if let Some(tx) = self.rows.pop_front() {
+                if self.filter.as_ref().is_none_or(|f| f.matches(&tx)) {
+                    return Ok(Some(tx));
+                }
+                continue;
+            }
+            let mut magic = [0u8; 4];
+            if self.reader.read(&mut magic[..1])? == 0 {
+                return Ok(None);
+            }
+            self.reader.read_exact(&mut magic[1..])?;
+            if &magic != GROUP_MAGIC {
+                return Err(ParserError::Format("Неверная сигнатура группы строк".into()));
+            }
+            let mut buf = [0u8; HEADER_LEN];
+            self.reader.read_exact(&mut buf)?;
+            let header = parse_group_header(&buf)?;
+
+            if self.filter.as_ref().is_some_and(|f| !f.may_match(|field| header.stats.bounds(field))) {
+                let total = header.chunks.iter().map(|&(len, _)| len).sum();
+                (self.skip)(&mut self.reader, total)?;
+                self.groups_skipped += 1;
+                continue;
+            }
+
+            check_chunk_lengths(&header)?;
+            let mut rows = Vec::new();
+            for (i, &(len, crc)) in header.chunks.iter().enumerate() {
+                if !self.columns[i] {
+                    (self.skip)(&mut self.reader, len)?;
+                    continue;
+                }
+                let mut chunk = Vec::new();
+                (&mut self.reader).take(len).read_to_end(&mut chunk)?;
+                if chunk.len() as u64 != len {
+                    return Err(ParserError::Format("Файл оборвался внутри группы строк".into()));
+                }
+                if crc32(&chunk) != crc {
+                    return Err(ParserError::Format(format!("Контрольная сумма блока {} не совпадает", COLUMNS[i])));
+                }
+                if rows.is_empty() {
+                    // Строки заводятся после первого прочитанного блока: его размер подтверждает ROWS.
+                    rows = vec![empty_row(); header.stats.rows as usize];
+                }
+                decode_column(&mut rows, COLUMNS[i], &chunk)?;
+            }
+            self.groups_read += 1;
+            self.rows = rows.into();
+        }
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Чтение из файла: ненужные блоки и группы пропускаются без чтения.
+    pub fn seekable(reader: R) -> Self {
+        Reader { skip: skip_by_seek::<R>, ..Reader::new(reader) }
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Transaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_next().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
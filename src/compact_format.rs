@@ -0,0 +1,252 @@
+//! Компактный бинарник YPBankCompact: varint, дельты и словарь описаний.
+//!
+//! Файл — последовательность сегментов. Сегмент начинается с `YPBC`, за ним
+//! записи. Запись — байт заголовка и поля в varint (LEB128):
+//!
+//! ```text
+//! HEAD u8 | ΔTX_ID zigzag | FROM_USER_ID | TO_USER_ID | AMOUNT zigzag | ΔTIMESTAMP zigzag | описание
+//! ```
+//!
+//! `HEAD` = `0x80 | TX_TYPE | STATUS << 2 | KIND << 4`. Дельты считаются от
+//! предыдущей записи сегмента (для первой — от нуля). Описание по `KIND`:
+//! `0` — пустое, `1` — длина и байты, строка добавляется в словарь сегмента,
+//! `2` — номер в словаре, `3` — длина и байты без добавления в словарь.
+//!
+//! Каждый сегмент независим, поэтому дописывание в файл и склейка файлов
+//! начинают новый сегмент.
+
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Write};
+use crate::bin_format::{decode_status, decode_type, encode_status, encode_type};
+use crate::{Transaction, Result, ParserError};
+
+/// Сигнатура сегмента.
+const MAGIC: &[u8; 4] = b"YPBC";
+
+/// Сколько байт описаний хранит словарь одного сегмента.
+pub const DICTIONARY_BYTES: usize = 1 << 20;
+
+const KIND_EMPTY: u8 = 0;
+const KIND_NEW: u8 = 1;
+const KIND_REF: u8 = 2;
+const KIND_LITERAL: u8 = 3;
+
+/// Читаем записи до EOF.
+pub fn from_read<R: Read>(reader: R) -> Result<Vec<Transaction>> {
+    Reader::new(reader).collect()
+}
+
+/// Состояние сегмента, общее для чтения и записи.
+#[derive(Debug, Default)]
+struct Segment {
+    tx_id: u64,
+    timestamp: u64,
+    dictionary_bytes: usize,
+}
+
+/// Потоковое чтение: по одной записи за шаг, после ошибки итерация заканчивается.
+pub struct Reader<R: Read> {
+    reader: BufReader<R>,
+    segment: Option<Segment>,
+    dictionary: Vec<String>,
+    done: bool,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Reader { reader: BufReader::new(reader), segment: None, dictionary: Vec::new(), done: false }
+    }
+
+    fn read_next(&mut self) -> Result<Option<Transaction>> {
+        let head = loop {
+            let mut byte = [0u8; 1];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] & 0x80 != 0 && self.segment.is_some() {
+                break byte[0];
+            }
+            // Не запись — значит, начало сегмента.
+            let mut rest = [0u8; 3];
+            self.reader.read_exact(&mut rest)?;
+            if byte[0] != MAGIC[0] || rest != MAGIC[1..] {
+                return Err(ParserError::Format("Неверный заголовок сегмента".into()));
+            }
+            self.segment = Some(Segment::default());
+            self.dictionary.clear();
+        };
+        if head & 0x40 != 0 {
+            return Err(ParserError::Format("Неверный байт заголовка записи".into()));
+        }
+        let Some(segment) = self.segment.as_mut() else {
+            return Err(ParserError::Format("Запись вне сегмента".into()));
+        };
+
+        let tx_type = decode_type(head & 0x03)?;
+        let status = decode_status((head >> 2) & 0x03)?;
+        segment.tx_id = segment.tx_id.wrapping_add(read_signed(&mut self.reader)? as u64);
+        let from_user_id = read_varint(&mut self.reader)?;
+        let to_user_id = read_varint(&mut self.reader)?;
+        let amount = read_signed(&mut self.reader)?;
+        segment.timestamp = segment.timestamp.wrapping_add(read_signed(&mut self.reader)? as u64);
+
+        let description = match (head >> 4) & 0x03 {
+            KIND_EMPTY => String::new(),
+            KIND_REF => {
+                let index = read_varint(&mut self.reader)?;
+                self.dictionary
+                    .get(index as usize)
+                    .cloned()
+                    .ok_or_else(|| ParserError::Format(format!("Нет описания {} в словаре", index)))?
+            }
+            kind => {
+                let len = read_varint(&mut self.reader)? as usize;
+                if kind == KIND_NEW && segment.dictionary_bytes.checked_add(len).is_none_or(|n| n > DICTIONARY_BYTES) {
+                    return Err(ParserError::Format("Словарь описаний переполнен".into()));
+                }
+                let mut bytes = Vec::new();
+                (&mut self.reader).take(len as u64).read_to_end(&mut bytes)?;
+                if bytes.len() != len {
+                    return Err(ParserError::Format("Файл оборвался внутри описания".into()));
+                }
+                let description = String::from_utf8(bytes)?;
+                if kind == KIND_NEW {
+                    segment.dictionary_bytes += len;
+                    self.dictionary.push(description.clone());
+                }
+                description
+            }
+        };
+
+        Ok(Some(Transaction {
+            tx_id: segment.tx_id,
+            tx_type,
+            from_user_id,
+            to_user_id,
+            amount,
+            timestamp: segment.timestamp,
+            status,
+            description,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Transaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_next().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+/// Пишет транзакции одним сегментом.
+pub fn write_to<W: Write>(writer: W, transactions: &[Transaction]) -> Result<()> {
+    let mut writer = Writer::new(writer);
+    for tx in transactions {
+        writer.write(tx)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Потоковая запись. Сегмент начинается с первой записи, поэтому пустой
+/// поток даёт пустой файл, а запись в конец существующего файла корректна.
+pub struct Writer<W: Write> {
+    writer: W,
+    segment: Option<Segment>,
+    dictionary: HashMap<String, u64>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Writer { writer, segment: None, dictionary: HashMap::new(), buf: Vec::new() }
+    }
+
+    pub fn write(&mut self, tx: &Transaction) -> Result<()> {
+        let segment = match &mut self.segment {
+            Some(segment) => segment,
+            None => {
+                self.writer.write_all(MAGIC)?;
+                self.segment.insert(Segment::default())
+            }
+        };
+        self.buf.clear();
+        self.buf.push(0);
+        write_signed(&mut self.buf, tx.tx_id.wrapping_sub(segment.tx_id) as i64);
+        write_varint(&mut self.buf, tx.from_user_id);
+        write_varint(&mut self.buf, tx.to_user_id);
+        write_signed(&mut self.buf, tx.amount);
+        write_signed(&mut self.buf, tx.timestamp.wrapping_sub(segment.timestamp) as i64);
+        segment.tx_id = tx.tx_id;
+        segment.timestamp = tx.timestamp;
+
+        let description = tx.description.as_bytes();
+        let kind = if description.is_empty() {
+            KIND_EMPTY
+        } else if let Some(&index) = self.dictionary.get(&tx.description) {
+            write_varint(&mut self.buf, index);
+            KIND_REF
+        } else {
+            write_varint(&mut self.buf, description.len() as u64);
+            self.buf.extend_from_slice(description);
+            if segment.dictionary_bytes + description.len() <= DICTIONARY_BYTES {
+                segment.dictionary_bytes += description.len();
+                self.dictionary.insert(tx.description.clone(), self.dictionary.len() as u64);
+                KIND_NEW
+            } else {
+                KIND_LITERAL
+            }
+        };
+        self.buf[0] = 0x80 | encode_type(tx.tx_type) | encode_status(tx.status) << 2 | kind << 4;
+        self.writer.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    /// Сбрасывает буферы и возвращает исходный writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_signed(buf: &mut Vec<u8>, value: i64) {
+    write_varint(buf, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        // В десятом байте помещается только старший бит u64.
+        if shift == 63 && byte[0] > 1 {
+            return Err(ParserError::Format("Varint не помещается в u64".into()));
+        }
+        value |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(ParserError::Format("Слишком длинный varint".into()))
+}
+
+fn read_signed<R: Read>(reader: &mut R) -> Result<i64> {
+    let value = read_varint(reader)?;
+    Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+}
@@ -0,0 +1,621 @@
+//! Сравнение двух наборов транзакций по ключу, а не по позиции.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use crate::field::{Field, FieldValue};
+use crate::{json, Transaction, Result, ParserError};
+
+/// Различие в одном поле.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: Field,
+    pub first: FieldValue,
+    pub second: FieldValue,
+}
+
+impl fmt::Display for FieldDiff {
+    /// `STATUS: PENDING -> SUCCESS`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.first, self.second)
+    }
+}
+
+/// Поля, которыми различаются две записи, в порядке форматов.
+pub fn diff_fields(first: &Transaction, second: &Transaction) -> Vec<FieldDiff> {
+    diff_fields_with(first, second, &CompareOptions::default())
+}
+
+/// То же, что [`diff_fields`], но с учётом игнорируемых полей, допусков и нормализации.
+pub fn diff_fields_with(first: &Transaction, second: &Transaction, options: &CompareOptions) -> Vec<FieldDiff> {
+    Field::ALL
+        .into_iter()
+        .filter(|field| !options.ignore.contains(field))
+        .filter(|&field| !options.field_equal(field, first, second))
+        .map(|field| FieldDiff { field, first: first.field(field), second: second.field(field) })
+        .collect()
+}
+
+/// Что считать несущественными расхождениями.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompareOptions {
+    /// Эти поля не сравниваются.
+    pub ignore: Vec<Field>,
+    /// Допустимая разница `TIMESTAMP`, мс.
+    pub timestamp_tolerance: u64,
+    /// Допустимая разница `AMOUNT`.
+    pub amount_tolerance: u64,
+    /// Как приводить `DESCRIPTION` перед сравнением.
+    pub normalize: Normalization,
+}
+
+impl CompareOptions {
+    fn field_equal(&self, field: Field, first: &Transaction, second: &Transaction) -> bool {
+        match field {
+            Field::Timestamp => first.timestamp.abs_diff(second.timestamp) <= self.timestamp_tolerance,
+            Field::Amount => first.amount.abs_diff(second.amount) <= self.amount_tolerance,
+            Field::Description => {
+                self.normalize.apply(&first.description) == self.normalize.apply(&second.description)
+            }
+            _ => first.field(field) == second.field(field),
+        }
+    }
+}
+
+/// Приведение описаний к виду для сравнения.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalization {
+    /// Обрезать края и схлопнуть пробелы внутри.
+    pub trim: bool,
+    /// Не различать регистр.
+    pub case_fold: bool,
+    /// Сведение диакритики по короткой таблице [`ACCENT_FOLDS`] (`е` +
+    /// U+0308 → `ё`) и замена особых пробелов обычными. Это не нормализация
+    /// Unicode: сочетания вне таблицы остаются как есть.
+    pub accent_fold: bool,
+}
+
+impl Normalization {
+    /// Список через запятую: `trim,case,accent-fold`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut norm = Normalization::default();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part {
+                "trim" => norm.trim = true,
+                "case" => norm.case_fold = true,
+                "accent-fold" => norm.accent_fold = true,
+                _ => return Err(ParserError::Format(format!("Неизвестная нормализация: {}", part))),
+            }
+        }
+        Ok(norm)
+    }
+
+    /// Приводит строку к виду для сравнения.
+    pub fn apply(&self, s: &str) -> String {
+        let mut out = if self.accent_fold { fold_accents(s) } else { s.to_string() };
+        if self.trim {
+            out = out.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+        if self.case_fold {
+            out = out.to_lowercase();
+        }
+        out
+    }
+}
+
+/// Основа, комбинируемый знак, составной символ: `ё`, `й` и латинские
+/// буквы с грависом, акутом, циркумфлексом, тильдой, умлаутом, кольцом,
+/// седилем и гачеком. Других сочетаний сведение не знает.
+pub const ACCENT_FOLDS: &[(char, char, char)] = &[
+    ('е', '\u{308}', 'ё'), ('Е', '\u{308}', 'Ё'), ('и', '\u{306}', 'й'), ('И', '\u{306}', 'Й'),
+    ('a', '\u{300}', 'à'), ('a', '\u{301}', 'á'), ('a', '\u{302}', 'â'), ('a', '\u{303}', 'ã'),
+    ('a', '\u{308}', 'ä'), ('a', '\u{30A}', 'å'), ('e', '\u{300}', 'è'), ('e', '\u{301}', 'é'),
+    ('e', '\u{302}', 'ê'), ('e', '\u{308}', 'ë'), ('i', '\u{300}', 'ì'), ('i', '\u{301}', 'í'),
+    ('i', '\u{302}', 'î'), ('i', '\u{308}', 'ï'), ('o', '\u{300}', 'ò'), ('o', '\u{301}', 'ó'),
+    ('o', '\u{302}', 'ô'), ('o', '\u{303}', 'õ'), ('o', '\u{308}', 'ö'), ('u', '\u{300}', 'ù'),
+    ('u', '\u{301}', 'ú'), ('u', '\u{302}', 'û'), ('u', '\u{308}', 'ü'), ('y', '\u{301}', 'ý'),
+    ('n', '\u{303}', 'ñ'), ('c', '\u{327}', 'ç'), ('c', '\u{30C}', 'č'), ('s', '\u{30C}', 'š'),
+    ('z', '\u{30C}', 'ž'), ('A', '\u{300}', 'À'), ('A', '\u{301}', 'Á'), ('A', '\u{302}', 'Â'),
+    ('A', '\u{303}', 'Ã'), ('A', '\u{308}', 'Ä'), ('A', '\u{30A}', 'Å'), ('E', '\u{300}', 'È'),
+    ('E', '\u{301}', 'É'), ('E', '\u{302}', 'Ê'), ('E', '\u{308}', 'Ë'), ('I', '\u{301}', 'Í'),
+    ('O', '\u{301}', 'Ó'), ('O', '\u{308}', 'Ö'), ('U', '\u{301}', 'Ú'), ('U', '\u{308}', 'Ü'),
+    ('N', '\u{303}', 'Ñ'), ('C', '\u{327}', 'Ç'), ('C', '\u{30C}', 'Č'), ('S', '\u{30C}', 'Š'),
+    ('Z', '\u{30C}', 'Ž'),
+];
+
+/// Сводит пары из [`ACCENT_FOLDS`] в одну букву; U+00A0, U+2007, U+2009,
+/// U+202F и U+3000 заменяет обычным пробелом.
+fn fold_accents(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut prev: Option<char> = None;
+    for ch in s.chars() {
+        let ch = match ch {
+            '\u{A0}' | '\u{2007}' | '\u{2009}' | '\u{202F}' | '\u{3000}' => ' ',
+            c => c,
+        };
+        if let Some(base) = prev {
+            if let Some(&(_, _, composed)) = ACCENT_FOLDS.iter().find(|(b, m, _)| *b == base && *m == ch) {
+                prev = Some(composed);
+                continue;
+            }
+            out.push(base);
+        }
+        prev = Some(ch);
+    }
+    out.extend(prev);
+    out
+}
+
+/// Записи с одинаковым ключом и разными полями.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub first: Transaction,
+    pub second: Transaction,
+    pub diffs: Vec<FieldDiff>,
+}
+
+/// Результат сравнения.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    /// Есть только в первом наборе (в порядке первого набора).
+    pub only_in_first: Vec<Transaction>,
+    /// Есть только во втором наборе (в порядке второго набора).
+    pub only_in_second: Vec<Transaction>,
+    /// Ключ совпал, остальные поля — нет.
+    pub changed: Vec<Change>,
+    /// Число совпавших записей.
+    pub matched: usize,
+}
+
+impl Comparison {
+    /// Наборы совпадают с точностью до порядка.
+    pub fn is_identical(&self) -> bool {
+        self.only_in_first.is_empty() && self.only_in_second.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Сопоставляет записи по значениям полей `key` независимо от порядка.
+/// Повторяющиеся ключи сопоставляются по очереди появления.
+pub fn compare_by_key(
+    first: Vec<Transaction>,
+    second: Vec<Transaction>,
+    key: &[Field],
+    options: &CompareOptions,
+) -> Comparison {
+    let mut pending: BTreeMap<Vec<FieldValue>, VecDeque<(usize, Transaction)>> = BTreeMap::new();
+    for (idx, tx) in second.into_iter().enumerate() {
+        pending.entry(tx.key(key)).or_default().push_back((idx, tx));
+    }
+
+    let mut result = Comparison::default();
+    for tx in first {
+        match pending.get_mut(&tx.key(key)).and_then(VecDeque::pop_front) {
+            Some((_, other)) => {
+                let diffs = diff_fields_with(&tx, &other, options);
+                if diffs.is_empty() {
+                    result.matched += 1;
+                } else {
+                    result.changed.push(Change { first: tx, second: other, diffs });
+                }
+            }
+            None => result.only_in_first.push(tx),
+        }
+    }
+
+    let mut rest: Vec<(usize, Transaction)> = pending.into_values().flatten().collect();
+    rest.sort_by_key(|(idx, _)| *idx);
+    result.only_in_second = rest.into_iter().map(|(_, tx)| tx).collect();
+    result
+}
+
+/// Отличие, найденное при потоковом сравнении.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    OnlyInFirst(Transaction),
+    OnlyInSecond(Transaction),
+    Changed(Change),
+}
+
+/// Счётчики потокового сравнения.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub matched: usize,
+    pub only_in_first: usize,
+    pub only_in_second: usize,
+    pub changed: usize,
+}
+
+impl Summary {
+    pub fn is_identical(&self) -> bool {
+        self.only_in_first == 0 && self.only_in_second == 0 && self.changed == 0
+    }
+}
+
+impl Comparison {
+    pub fn summary(&self) -> Summary {
+        Summary {
+            matched: self.matched,
+            only_in_first: self.only_in_first.len(),
+            only_in_second: self.only_in_second.len(),
+            changed: self.changed.len(),
+        }
+    }
+}
+
+/// Потоковое сравнение двух наборов, уже отсортированных по `key`
+/// (например, [`crate::external_sort::sort`]). Различия передаются в
+/// `on_diff` по мере нахождения в порядке ключа; в памяти держится по
+/// одной записи из каждого набора.
+pub fn compare_sorted_each<A, B, F>(
+    first: A,
+    second: B,
+    key: &[Field],
+    options: &CompareOptions,
+    mut on_diff: F,
+) -> Result<Summary>
+where
+    A: IntoIterator<Item = Result<Transaction>>,
+    B: IntoIterator<Item = Result<Transaction>>,
+    F: FnMut(Difference) -> Result<()>,
+{
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    let mut a = first.next().transpose()?;
+    let mut b = second.next().transpose()?;
+    let mut summary = Summary::default();
+
+    loop {
+        match (a.take(), b.take()) {
+            (None, None) => break,
+            (Some(tx), None) => {
+                summary.only_in_first += 1;
+                on_diff(Difference::OnlyInFirst(tx))?;
+                a = first.next().transpose()?;
+            }
+            (None, Some(tx)) => {
+                summary.only_in_second += 1;
+                on_diff(Difference::OnlyInSecond(tx))?;
+                b = second.next().transpose()?;
+            }
+            (Some(t1), Some(t2)) => match t1.cmp_by(&t2, key) {
+                std::cmp::Ordering::Less => {
+                    summary.only_in_first += 1;
+                    on_diff(Difference::OnlyInFirst(t1))?;
+                    a = first.next().transpose()?;
+                    b = Some(t2);
+                }
+                std::cmp::Ordering::Greater => {
+                    summary.only_in_second += 1;
+                    on_diff(Difference::OnlyInSecond(t2))?;
+                    a = Some(t1);
+                    b = second.next().transpose()?;
+                }
+                std::cmp::Ordering::Equal => {
+                    let diffs = diff_fields_with(&t1, &t2, options);
+                    if diffs.is_empty() {
+                        summary.matched += 1;
+                    } else {
+                        summary.changed += 1;
+                        on_diff(Difference::Changed(Change { first: t1, second: t2, diffs }))?;
+                    }
+                    a = first.next().transpose()?;
+                    b = second.next().transpose()?;
+                }
+            },
+        }
+    }
+    Ok(summary)
+}
+
+/// То же, что [`compare_sorted_each`], но различия собираются в
+/// [`Comparison`]: память растёт с их числом.
+pub fn compare_sorted<A, B>(first: A, second: B, key: &[Field], options: &CompareOptions) -> Result<Comparison>
+where
+    A: IntoIterator<Item = Result<Transaction>>,
+    B: IntoIterator<Item = Result<Transaction>>,
+{
+    let mut result = Comparison::default();
+    result.matched = compare_sorted_each(first, second, key, options, |diff| {
+        match diff {
+            Difference::OnlyInFirst(tx) => result.only_in_first.push(tx),
+            Difference::OnlyInSecond(tx) => result.only_in_second.push(tx),
+            Difference::Changed(change) => result.changed.push(change),
+        }
+        Ok(())
+    })?
+    .matched;
+    Ok(result)
+}
+
+/// Формат отчёта о сравнении.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+impl FromStr for ReportFormat {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            _ => Err(ParserError::Format(format!("Неизвестный формат отчёта: {}", s))),
+        }
+    }
+}
+
+/// Отчёт о сравнении: имена файлов нужны только для заголовков.
+pub struct Report<'a> {
+    pub comparison: &'a Comparison,
+    pub key: &'a [Field],
+    pub first_name: &'a str,
+    pub second_name: &'a str,
+}
+
+impl Report<'_> {
+    /// Пишет отчёт в выбранном формате.
+    pub fn write<W: Write>(&self, writer: W, format: ReportFormat) -> Result<()> {
+        match format {
+            ReportFormat::Text => self.write_text(writer),
+            ReportFormat::Json => self.write_json(writer),
+            ReportFormat::Csv => self.write_csv(writer),
+        }
+    }
+
+    fn write_text<W: Write>(&self, mut writer: W) -> Result<()> {
+        let cmp = self.comparison;
+        if cmp.is_identical() {
+            return write_text_identical(&mut writer, self.first_name, self.second_name);
+        }
+        for tx in &cmp.only_in_first {
+            writeln!(writer, "Transaction {} is only in '{}'.", describe_key(self.key, tx), self.first_name)?;
+        }
+        for tx in &cmp.only_in_second {
+            writeln!(writer, "Transaction {} is only in '{}'.", describe_key(self.key, tx), self.second_name)?;
+        }
+        for change in &cmp.changed {
+            write_text_change(&mut writer, self.key, change)?;
+        }
+        write_text_summary(&mut writer, &cmp.summary())
+    }
+
+    fn write_json<W: Write>(&self, mut writer: W) -> Result<()> {
+        let cmp = self.comparison;
+        write_json_header(&mut writer, self.key, self.first_name, self.second_name)?;
+        write!(writer, "\"summary\":{},", json_summary(&cmp.summary()))?;
+        for (name, txs) in [("only_in_first", &cmp.only_in_first), ("only_in_second", &cmp.only_in_second)] {
+            write!(writer, "\"{}\":[", name)?;
+            for (i, tx) in txs.iter().enumerate() {
+                if i > 0 {
+                    write!(writer, ",")?;
+                }
+                json::write_transaction(&mut writer, tx)?;
+            }
+            write!(writer, "],")?;
+        }
+        write!(writer, "\"changed\":[")?;
+        for (i, change) in cmp.changed.iter().enumerate() {
+            if i > 0 {
+                write!(writer, ",")?;
+            }
+            write!(writer, "{{{}}}", json_change_fields(self.key, change))?;
+        }
+        writeln!(writer, "]}}")?;
+        Ok(())
+    }
+
+    /// Строка на отличие: `KIND,KEY,FIELD,FIRST,SECOND`.
+    fn write_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        let cmp = self.comparison;
+        writeln!(writer, "{}", CSV_HEADER)?;
+        for tx in &cmp.only_in_first {
+            writeln!(writer, "ONLY_IN_FIRST,{},,,", csv_quote(&describe_key(self.key, tx)))?;
+        }
+        for tx in &cmp.only_in_second {
+            writeln!(writer, "ONLY_IN_SECOND,{},,,", csv_quote(&describe_key(self.key, tx)))?;
+        }
+        for change in &cmp.changed {
+            write_csv_change(&mut writer, self.key, change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Отчёт, который пишется по мере сравнения: [`StreamReport::difference`]
+/// на каждое отличие, затем [`StreamReport::finish`].
+///
+/// Текст и CSV совпадают с [`Report`], но строки идут в порядке ключа.
+/// JSON: `{"file1","file2","key","differences":[...],"summary"}`, где
+/// у каждого отличия есть поле `kind` (`only_in_first`, `only_in_second`,
+/// `changed`).
+pub struct StreamReport<'a, W: Write> {
+    writer: W,
+    format: ReportFormat,
+    key: &'a [Field],
+    first_name: &'a str,
+    second_name: &'a str,
+    written: usize,
+}
+
+impl<'a, W: Write> StreamReport<'a, W> {
+    /// Пишет заголовок отчёта.
+    pub fn new(
+        mut writer: W,
+        format: ReportFormat,
+        key: &'a [Field],
+        first_name: &'a str,
+        second_name: &'a str,
+    ) -> Result<Self> {
+        match format {
+            ReportFormat::Text => {}
+            ReportFormat::Json => {
+                write_json_header(&mut writer, key, first_name, second_name)?;
+                write!(writer, "\"differences\":[")?;
+            }
+            ReportFormat::Csv => writeln!(writer, "{}", CSV_HEADER)?,
+        }
+        Ok(StreamReport { writer, format, key, first_name, second_name, written: 0 })
+    }
+
+    pub fn difference(&mut self, diff: &Difference) -> Result<()> {
+        let (key, w) = (self.key, &mut self.writer);
+        match (self.format, diff) {
+            (ReportFormat::Text, Difference::OnlyInFirst(tx)) => {
+                writeln!(w, "Transaction {} is only in '{}'.", describe_key(key, tx), self.first_name)?
+            }
+            (ReportFormat::Text, Difference::OnlyInSecond(tx)) => {
+                writeln!(w, "Transaction {} is only in '{}'.", describe_key(key, tx), self.second_name)?
+            }
+            (ReportFormat::Text, Difference::Changed(change)) => write_text_change(w, key, change)?,
+            (ReportFormat::Json, diff) => {
+                if self.written > 0 {
+                    write!(w, ",")?;
+                }
+                let (kind, tx) = match diff {
+                    Difference::OnlyInFirst(tx) => ("only_in_first", tx),
+                    Difference::OnlyInSecond(tx) => ("only_in_second", tx),
+                    Difference::Changed(change) => {
+                        write!(w, "{{\"kind\":\"changed\",{}}}", json_change_fields(key, change))?;
+                        self.written += 1;
+                        return Ok(());
+                    }
+                };
+                write!(w, "{{\"kind\":\"{}\",\"transaction\":", kind)?;
+                json::write_transaction(&mut *w, tx)?;
+                write!(w, "}}")?;
+            }
+            (ReportFormat::Csv, Difference::OnlyInFirst(tx)) => {
+                writeln!(w, "ONLY_IN_FIRST,{},,,", csv_quote(&describe_key(key, tx)))?
+            }
+            (ReportFormat::Csv, Difference::OnlyInSecond(tx)) => {
+                writeln!(w, "ONLY_IN_SECOND,{},,,", csv_quote(&describe_key(key, tx)))?
+            }
+            (ReportFormat::Csv, Difference::Changed(change)) => write_csv_change(w, key, change)?,
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Пишет сводку и сбрасывает буфер.
+    pub fn finish(mut self, summary: &Summary) -> Result<()> {
+        match self.format {
+            ReportFormat::Text if summary.is_identical() => {
+                write_text_identical(&mut self.writer, self.first_name, self.second_name)?
+            }
+            ReportFormat::Text => write_text_summary(&mut self.writer, summary)?,
+            ReportFormat::Json => writeln!(self.writer, "],\"summary\":{}}}", json_summary(summary))?,
+            ReportFormat::Csv => {}
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+const CSV_HEADER: &str = "KIND,KEY,FIELD,FIRST,SECOND";
+
+/// `TX_ID=1001` или `TX_ID=1001, TIMESTAMP=...` для составного ключа.
+fn describe_key(key: &[Field], tx: &Transaction) -> String {
+    key.iter()
+        .map(|&f| format!("{}={}", f, tx.field(f)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn write_text_identical<W: Write>(mut writer: W, first_name: &str, second_name: &str) -> Result<()> {
+    writeln!(writer, "The transaction records in '{}' and '{}' are identical.", first_name, second_name)?;
+    Ok(())
+}
+
+fn write_text_change<W: Write>(mut writer: W, key: &[Field], change: &Change) -> Result<()> {
+    writeln!(writer, "Transaction {} does not match:", describe_key(key, &change.first))?;
+    for diff in &change.diffs {
+        writeln!(writer, "  {}", diff)?;
+    }
+    Ok(())
+}
+
+fn write_text_summary<W: Write>(mut writer: W, summary: &Summary) -> Result<()> {
+    writeln!(
+        writer,
+        "Summary: {} identical, {} only in first file, {} only in second file, {} changed.",
+        summary.matched, summary.only_in_first, summary.only_in_second, summary.changed
+    )?;
+    Ok(())
+}
+
+fn write_json_header<W: Write>(mut writer: W, key: &[Field], first_name: &str, second_name: &str) -> Result<()> {
+    let key: Vec<String> = key.iter().map(|f| json::string(f.name())).collect();
+    write!(
+        writer,
+        "{{\"file1\":{},\"file2\":{},\"key\":[{}],",
+        json::string(first_name),
+        json::string(second_name),
+        key.join(",")
+    )?;
+    Ok(())
+}
+
+fn json_summary(summary: &Summary) -> String {
+    format!(
+        "{{\"identical\":{},\"only_in_first\":{},\"only_in_second\":{},\"changed\":{}}}",
+        summary.matched, summary.only_in_first, summary.only_in_second, summary.changed
+    )
+}
+
+/// `"key":{...},"diffs":[...]` без внешних скобок.
+fn json_change_fields(key: &[Field], change: &Change) -> String {
+    let key: Vec<String> = key
+        .iter()
+        .map(|&f| format!("{}:{}", json::string(f.name()), json::value(&change.first.field(f))))
+        .collect();
+    let diffs: Vec<String> = change
+        .diffs
+        .iter()
+        .map(|d| {
+            format!(
+                "{{\"field\":{},\"first\":{},\"second\":{}}}",
+                json::string(d.field.name()),
+                json::value(&d.first),
+                json::value(&d.second)
+            )
+        })
+        .collect();
+    format!("\"key\":{{{}}},\"diffs\":[{}]", key.join(","), diffs.join(","))
+}
+
+fn write_csv_change<W: Write>(mut writer: W, key: &[Field], change: &Change) -> Result<()> {
+    let key = csv_quote(&describe_key(key, &change.first));
+    for diff in &change.diffs {
+        writeln!(
+            writer,
+            "CHANGED,{},{},{},{}",
+            key,
+            diff.field,
+            csv_quote(&plain(&diff.first)),
+            csv_quote(&plain(&diff.second))
+        )?;
+    }
+    Ok(())
+}
+
+/// Значение без кавычек вокруг текста.
+fn plain(value: &FieldValue) -> String {
+    match value {
+        FieldValue::Text(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Поле CSV в кавычках, если в нём есть запятая, кавычка или перевод строки.
+fn csv_quote(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
@@ -1,7 +1,10 @@
 //! CSV с заголовком.
 
-use std::io::{BufRead, BufReader, Write, Read};
-use crate::{Transaction, TxType, TxStatus, Result, ParserError};
+use std::io::{BufRead, BufReader, Lines, Write, Read};
+use crate::{Transaction, Result, ParserError};
+
+/// Строка заголовка.
+const HEADER: &str = "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION";
 
 /// Парсит одну строку CSV с учётом кавычек: внутри "..." запятые не разделяют поля.
 fn parse_csv_line(line: &str) -> Vec<String> {
@@ -24,80 +27,140 @@ fn parse_csv_line(line: &str) -> Vec<String> {
 
 /// Читаем CSV: заголовок, затем по строке на транзакцию.
 pub fn from_read<R: Read>(reader: R) -> Result<Vec<Transaction>> {
-    let mut transactions = Vec::new();
-    let buf_reader = BufReader::new(reader);
-    let mut lines = buf_reader.lines();
+    Reader::new(reader).collect()
+}
 
-    if let Some(header_result) = lines.next() {
-        header_result?;
+/// Потоковое чтение: по строке за шаг, после ошибки итерация заканчивается.
+pub struct Reader<R: Read> {
+    lines: Lines<BufReader<R>>,
+    header_skipped: bool,
+    done: bool,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Reader { lines: BufReader::new(reader).lines(), header_skipped: false, done: false }
     }
 
-    for line_result in lines {
-        let line = line_result?;
-        if line.trim().is_empty() {
-            continue;
+    fn next_record(&mut self) -> Result<Option<Transaction>> {
+        if !self.header_skipped {
+            self.header_skipped = true;
+            if let Some(header_result) = self.lines.next() {
+                header_result?;
+            }
         }
 
-        let fields = parse_csv_line(&line);
+        for line_result in self.lines.by_ref() {
+            let line = line_result?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            return parse_record(&line).map(Some);
+        }
+        Ok(None)
+    }
+}
 
-        if fields.len() < 8 {
-            return Err(ParserError::Format(format!("Недостаточно полей в CSV: {}", line)));
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Transaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_record().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
         }
+        result
+    }
+}
 
-        let description = fields[7]
-            .strip_prefix('"')
-            .and_then(|s| s.strip_suffix('"'))
-            .unwrap_or(&fields[7])
-            .to_string();
-
-        let tx = Transaction {
-            tx_id: fields[0].parse()?,
-            tx_type: match fields[1].as_str() {
-                "DEPOSIT" => TxType::Deposit,
-                "TRANSFER" => TxType::Transfer,
-                "WITHDRAWAL" => TxType::Withdrawal,
-                _ => return Err(ParserError::Format(format!("Тип: {}", fields[1]))),
-            },
-            from_user_id: fields[2].parse()?,
-            to_user_id: fields[3].parse()?,
-            amount: fields[4].parse()?,
-            timestamp: fields[5].parse()?,
-            status: match fields[6].as_str() {
-                "SUCCESS" => TxStatus::Success,
-                "FAILURE" => TxStatus::Failure,
-                "PENDING" => TxStatus::Pending,
-                _ => return Err(ParserError::Format(format!("Статус: {}", fields[6]))),
-            },
-            description,
-        };
-
-        transactions.push(tx);
+/// Строка CSV → Transaction.
+fn parse_record(line: &str) -> Result<Transaction> {
+    let fields = parse_csv_line(line);
+
+    if fields.len() < 8 {
+        return Err(ParserError::Format(format!("Недостаточно полей в CSV: {}", line)));
     }
 
-    Ok(transactions)
+    let description = fields[7]
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(&fields[7])
+        .to_string();
+
+    Ok(Transaction {
+        tx_id: fields[0].parse()?,
+        tx_type: fields[1].parse()?,
+        from_user_id: fields[2].parse()?,
+        to_user_id: fields[3].parse()?,
+        amount: fields[4].parse()?,
+        timestamp: fields[5].parse()?,
+        status: fields[6].parse()?,
+        description,
+    })
 }
 
 /// Пишем CSV с заголовком.
-pub fn write_to<W: Write>(mut writer: W, transactions: &[Transaction]) -> Result<()> {
-    writeln!(writer, "TX_ID,TX_TYPE,FROM_USER_ID,TO_USER_ID,AMOUNT,TIMESTAMP,STATUS,DESCRIPTION")?;
-
+pub fn write_to<W: Write>(writer: W, transactions: &[Transaction]) -> Result<()> {
+    let mut writer = Writer::new(writer);
     for tx in transactions {
-        let type_str = match tx.tx_type {
-            TxType::Deposit => "DEPOSIT",
-            TxType::Transfer => "TRANSFER",
-            TxType::Withdrawal => "WITHDRAWAL",
-        };
-        let status_str = match tx.status {
-            TxStatus::Success => "SUCCESS",
-            TxStatus::Failure => "FAILURE",
-            TxStatus::Pending => "PENDING",
-        };
-
-        writeln!(
-            writer,
-            "{},{},{},{},{},{},{},\"{}\"",
-            tx.tx_id, type_str, tx.from_user_id, tx.to_user_id, tx.amount, tx.timestamp, status_str, tx.description
-        )?;
+        writer.write(tx)?;
     }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Потоковая запись; заголовок пишется один раз, даже если записей нет.
+pub struct Writer<W: Write> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Writer { writer, header_written: false }
+    }
+
+    /// Продолжает файл, в котором уже `written` записей: заголовок не повторяется.
+    pub fn appending(writer: W, written: usize) -> Self {
+        Writer { writer, header_written: written > 0 }
+    }
+
+    pub fn write(&mut self, tx: &Transaction) -> Result<()> {
+        self.write_header()?;
+        write_record(&mut self.writer, tx)
+    }
+
+    /// Дописывает заголовок, если записей не было, и возвращает исходный writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.write_header()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_header(&mut self) -> Result<()> {
+        if !self.header_written {
+            self.header_written = true;
+            writeln!(self.writer, "{}", HEADER)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_record<W: Write>(writer: &mut W, tx: &Transaction) -> Result<()> {
+    writeln!(
+        writer,
+        "{},{},{},{},{},{},{},\"{}\"",
+        tx.tx_id,
+        tx.tx_type.as_str(),
+        tx.from_user_id,
+        tx.to_user_id,
+        tx.amount,
+        tx.timestamp,
+        tx.status.as_str(),
+        tx.description
+    )?;
     Ok(())
 }
@@ -9,6 +9,32 @@ pub enum ParserError {
     Format(String),
     /// Невалидная UTF-8.
     Utf8(std::string::FromUtf8Error),
+    /// Файл YPBankBin оборвался внутри записи.
+    Truncated(Truncation),
+}
+
+/// Где оборвался файл YPBankBin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncation {
+    /// Номер записи с 1; `0` — неизвестен (чтение с произвольным доступом).
+    pub record: u64,
+    /// Смещение `MAGIC` записи от начала файла.
+    pub offset: u64,
+    /// Поле, на котором кончились данные: `MAGIC`, `RECORD_SIZE`, поле тела или `CRC32`.
+    pub field: &'static str,
+    /// Сколько байт не хватило до конца записи; при обрыве в `MAGIC` или
+    /// `RECORD_SIZE`, когда размер записи неизвестен, — до конца поля.
+    pub missing: u64,
+}
+
+impl fmt::Display for Truncation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "запись {} (смещение {}) оборвалась в поле {}, не хватает {} байт",
+            self.record, self.offset, self.field, self.missing
+        )
+    }
 }
 
 impl fmt::Display for ParserError {
@@ -18,6 +44,7 @@ impl fmt::Display for ParserError {
             ParserError::Parse(e) => write!(f, "Ошибка парсинга: {}", e),
             ParserError::Format(e) => write!(f, "Ошибка формата: {}", e),
             ParserError::Utf8(e) => write!(f, "Ошибка кодировки: {}", e),
+            ParserError::Truncated(t) => write!(f, "Файл оборвался: {}", t),
         }
     }
 }
@@ -0,0 +1,260 @@
+//! Внешняя сортировка: куски в пределах бюджета памяти сортируются и
+//! сбрасываются во временные бинарные файлы, затем сливаются потоком.
+//! Вместе с записью хранится её номер во входе, поэтому равные ключи в
+//! результате идут в исходном порядке — так же, как при сортировке в памяти.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::field::Field;
+use crate::bin_view::TransactionRef;
+use crate::{bin_format, Transaction, Result, ParserError};
+
+/// Бюджет памяти по умолчанию — 64 МиБ.
+pub const DEFAULT_MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+
+/// Сколько файлов сливается за один проход (ограничение на открытые файлы).
+const MAX_FAN_IN: usize = 64;
+
+static SPILL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Параметры внешней сортировки.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortOptions {
+    /// Сколько байт транзакций держать в памяти до сброса на диск.
+    pub memory_budget: usize,
+    /// Каталог для временных файлов.
+    pub temp_dir: PathBuf,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        SortOptions { memory_budget: DEFAULT_MEMORY_BUDGET, temp_dir: env::temp_dir() }
+    }
+}
+
+/// Размер с необязательным суффиксом: `1048576`, `512K`, `64M`, `2G`.
+pub fn parse_size(s: &str) -> Result<usize> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&s[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&s[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    digits
+        .parse::<usize>()?
+        .checked_mul(multiplier)
+        .ok_or_else(|| ParserError::Format(format!("Слишком большой размер: {}", s)))
+}
+
+/// Транзакция с номером во входном потоке: по нему упорядочиваются равные ключи.
+pub type Sequenced = (u64, Transaction);
+
+/// Примерный объём транзакции в памяти.
+fn estimate(tx: &Transaction) -> usize {
+    mem::size_of::<Sequenced>() + tx.description.capacity()
+}
+
+/// Порядок по `key`, при равенстве — по номеру во входе.
+fn compare(a: &Sequenced, b: &Sequenced, key: &[Field]) -> std::cmp::Ordering {
+    a.1.cmp_by(&b.1, key).then(a.0.cmp(&b.0))
+}
+
+/// Временный файл, удаляемый при освобождении.
+struct SpillFile(PathBuf);
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Пишет записи во временный файл: номер (u64), `RECORD_SIZE` и тело
+/// записи YPBankBin.
+fn spill<I>(records: I, options: &SortOptions) -> Result<SpillFile>
+where
+    I: IntoIterator<Item = Result<Sequenced>>,
+{
+    let n = SPILL_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let path = options.temp_dir.join(format!("ypbank_sort_{}_{}.tmp", process::id(), n));
+    let file = SpillFile(path);
+    let mut writer = BufWriter::new(File::create(&file.0)?);
+    let mut body = Vec::new();
+    for record in records {
+        let (seq, tx) = record?;
+        body.clear();
+        bin_format::encode_body(&mut body, &tx);
+        writer.write_all(&seq.to_be_bytes())?;
+        writer.write_all(&(body.len() as u32).to_be_bytes())?;
+        writer.write_all(&body)?;
+    }
+    writer.flush()?;
+    Ok(file)
+}
+
+/// Чтение временного файла, записанного [`spill`].
+struct SpillReader {
+    reader: BufReader<File>,
+    body: Vec<u8>,
+}
+
+impl SpillReader {
+    fn open(run: &SpillFile) -> Result<Self> {
+        Ok(SpillReader { reader: BufReader::new(File::open(&run.0)?), body: Vec::new() })
+    }
+
+    fn read(&mut self) -> Result<Option<Sequenced>> {
+        let mut head = [0u8; 12];
+        match self.reader.read_exact(&mut head) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let seq = u64::from_be_bytes(head[..8].try_into().unwrap_or_default());
+        let size = u32::from_be_bytes(head[8..].try_into().unwrap_or_default());
+        self.body.resize(size as usize, 0);
+        self.reader.read_exact(&mut self.body)?;
+        Ok(Some((seq, TransactionRef::new(&self.body)?.to_transaction()?)))
+    }
+}
+
+/// Сортирует поток по полям `key` (устойчиво: равные ключи сохраняют
+/// исходный порядок). Если всё уместилось в бюджет, диск не используется.
+pub fn sort<I>(input: I, key: &[Field], options: &SortOptions) -> Result<Sorted>
+where
+    I: IntoIterator<Item = Result<Transaction>>,
+{
+    let numbered = input.into_iter().zip(0u64..).map(|(tx, seq)| tx.map(|tx| (seq, tx)));
+    Ok(Sorted { inner: sort_sequenced(numbered, key, options)? })
+}
+
+/// Сортирует пронумерованные транзакции по `key`, равные — по номеру.
+/// С пустым `key` восстанавливает порядок номеров.
+pub fn sort_sequenced<I>(input: I, key: &[Field], options: &SortOptions) -> Result<SortedSequenced>
+where
+    I: IntoIterator<Item = Result<Sequenced>>,
+{
+    let mut chunk = Vec::new();
+    let mut used = 0;
+    let mut runs = Vec::new();
+
+    for record in input {
+        let record = record?;
+        used += estimate(&record.1);
+        chunk.push(record);
+        if used >= options.memory_budget {
+            chunk.sort_by(|a, b| compare(a, b, key));
+            runs.push(spill(chunk.drain(..).map(Ok), options)?);
+            used = 0;
+        }
+    }
+    chunk.sort_by(|a, b| compare(a, b, key));
+
+    if runs.is_empty() {
+        return Ok(SortedSequenced { source: Source::Memory(chunk.into_iter()) });
+    }
+    if !chunk.is_empty() {
+        runs.push(spill(chunk.drain(..).map(Ok), options)?);
+    }
+
+    while runs.len() > MAX_FAN_IN {
+        let mut next = Vec::new();
+        for group in runs.chunks(MAX_FAN_IN) {
+            next.push(spill(Merger::open(group, key)?, options)?);
+        }
+        runs = next;
+    }
+
+    let merger = Merger::open(&runs, key)?;
+    Ok(SortedSequenced { source: Source::Merge { merger, _runs: runs } })
+}
+
+/// Отсортированный поток транзакций.
+pub struct Sorted {
+    inner: SortedSequenced,
+}
+
+impl Iterator for Sorted {
+    type Item = Result<Transaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|record| record.map(|(_, tx)| tx))
+    }
+}
+
+/// Отсортированный поток вместе с номерами.
+pub struct SortedSequenced {
+    source: Source,
+}
+
+enum Source {
+    Memory(std::vec::IntoIter<Sequenced>),
+    /// Файлы держим, чтобы удалить их после чтения.
+    Merge { merger: Merger, _runs: Vec<SpillFile> },
+}
+
+impl Iterator for SortedSequenced {
+    type Item = Result<Sequenced>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.source {
+            Source::Memory(iter) => iter.next().map(Ok),
+            Source::Merge { merger, .. } => merger.next(),
+        }
+    }
+}
+
+/// Слияние отсортированных файлов по ключу и номеру.
+struct Merger {
+    readers: Vec<SpillReader>,
+    heads: Vec<Option<Sequenced>>,
+    key: Vec<Field>,
+    failed: bool,
+}
+
+impl Merger {
+    fn open(runs: &[SpillFile], key: &[Field]) -> Result<Self> {
+        let mut readers = Vec::with_capacity(runs.len());
+        let mut heads = Vec::with_capacity(runs.len());
+        for run in runs {
+            let mut reader = SpillReader::open(run)?;
+            heads.push(reader.read()?);
+            readers.push(reader);
+        }
+        Ok(Merger { readers, heads, key: key.to_vec(), failed: false })
+    }
+}
+
+impl Iterator for Merger {
+    type Item = Result<Sequenced>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mut best: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some(record) = head else { continue };
+            let better = match best.and_then(|b| self.heads[b].as_ref()) {
+                Some(current) => compare(record, current, &self.key).is_lt(),
+                None => true,
+            };
+            if better {
+                best = Some(i);
+            }
+        }
+        let i = best?;
+        match self.readers[i].read() {
+            Ok(next) => mem::replace(&mut self.heads[i], next).map(Ok),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
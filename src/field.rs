@@ -0,0 +1,135 @@
+//! Поля транзакции: имена как в форматах (`TX_ID`, `AMOUNT`, …) и значения.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use crate::{Transaction, TxType, TxStatus, Result, ParserError};
+
+/// Поле транзакции.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    TxId,
+    TxType,
+    FromUserId,
+    ToUserId,
+    Amount,
+    Timestamp,
+    Status,
+    Description,
+}
+
+impl Field {
+    /// Все поля в порядке форматов.
+    pub const ALL: [Field; 8] = [
+        Field::TxId,
+        Field::TxType,
+        Field::FromUserId,
+        Field::ToUserId,
+        Field::Amount,
+        Field::Timestamp,
+        Field::Status,
+        Field::Description,
+    ];
+
+    /// Имя поля, как в заголовке CSV.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::TxId => "TX_ID",
+            Field::TxType => "TX_TYPE",
+            Field::FromUserId => "FROM_USER_ID",
+            Field::ToUserId => "TO_USER_ID",
+            Field::Amount => "AMOUNT",
+            Field::Timestamp => "TIMESTAMP",
+            Field::Status => "STATUS",
+            Field::Description => "DESCRIPTION",
+        }
+    }
+
+    /// Список полей через запятую: `tx_id,timestamp`.
+    pub fn parse_list(s: &str) -> Result<Vec<Field>> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Field {
+    type Err = ParserError;
+
+    /// Регистр не важен: `tx_id` и `TX_ID` — одно поле.
+    fn from_str(s: &str) -> Result<Self> {
+        Field::ALL
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParserError::Format(format!("Неизвестное поле: {}", s)))
+    }
+}
+
+/// Значение одного поля.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FieldValue {
+    Unsigned(u64),
+    Signed(i64),
+    Type(TxType),
+    Status(TxStatus),
+    Text(String),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Unsigned(v) => write!(f, "{}", v),
+            FieldValue::Signed(v) => write!(f, "{}", v),
+            FieldValue::Type(t) => f.write_str(t.as_str()),
+            FieldValue::Status(s) => f.write_str(s.as_str()),
+            FieldValue::Text(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+impl Transaction {
+    /// Значение поля.
+    pub fn field(&self, field: Field) -> FieldValue {
+        match field {
+            Field::TxId => FieldValue::Unsigned(self.tx_id),
+            Field::TxType => FieldValue::Type(self.tx_type),
+            Field::FromUserId => FieldValue::Unsigned(self.from_user_id),
+            Field::ToUserId => FieldValue::Unsigned(self.to_user_id),
+            Field::Amount => FieldValue::Signed(self.amount),
+            Field::Timestamp => FieldValue::Unsigned(self.timestamp),
+            Field::Status => FieldValue::Status(self.status),
+            Field::Description => FieldValue::Text(self.description.clone()),
+        }
+    }
+
+    /// Составной ключ из значений полей.
+    pub fn key(&self, fields: &[Field]) -> Vec<FieldValue> {
+        fields.iter().map(|&f| self.field(f)).collect()
+    }
+
+    /// Сравнение по полям по порядку, без копирования описаний.
+    pub fn cmp_by(&self, other: &Transaction, fields: &[Field]) -> Ordering {
+        fields
+            .iter()
+            .map(|&field| match field {
+                Field::TxId => self.tx_id.cmp(&other.tx_id),
+                Field::TxType => self.tx_type.cmp(&other.tx_type),
+                Field::FromUserId => self.from_user_id.cmp(&other.from_user_id),
+                Field::ToUserId => self.to_user_id.cmp(&other.to_user_id),
+                Field::Amount => self.amount.cmp(&other.amount),
+                Field::Timestamp => self.timestamp.cmp(&other.timestamp),
+                Field::Status => self.status.cmp(&other.status),
+                Field::Description => self.description.cmp(&other.description),
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
@@ -0,0 +1,477 @@
+//! Язык фильтров для отбора транзакций.
+//!
+//! ```text
+//! STATUS = FAILURE AND TX_TYPE = TRANSFER AND AMOUNT > 10000
+//!     AND FROM_USER_ID = 501 AND TIMESTAMP BETWEEN 2024-03-01 AND 2024-03-31
+//! ```
+//!
+//! Сравнения `=`, `!=`, `<`, `<=`, `>`, `>=` есть для всех полей, кроме того
+//! `поле BETWEEN a AND b` (включительно), `поле IN (a, b, …)` и
+//! `DESCRIPTION CONTAINS "подстрока"`. Условия объединяются `AND`, `OR`, `NOT`
+//! и скобками; ключевые слова и имена полей — без учёта регистра.
+//!
+//! `TIMESTAMP` можно задать числом (мс), датой `2024-03-01` или моментом
+//! `2024-03-01T10:30[:15]` в UTC. Дата означает весь день: `= 2024-03-01` —
+//! в течение дня, `<= 2024-03-01` — до конца дня, `> 2024-03-01` — начиная
+//! со следующего дня.
+
+use std::str::FromStr;
+use crate::calendar::Date;
+use crate::field::{Field, FieldValue};
+use crate::format::Transactions;
+use crate::{Transaction, Result, ParserError};
+
+/// Разобранный фильтр.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Self> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(error(format!("лишний текст после выражения: {}", token.describe())));
+        }
+        Ok(Filter { expr })
+    }
+
+    /// Подходит ли транзакция.
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        self.expr.eval(tx)
+    }
+
+    /// Поля, которые читает фильтр.
+    pub fn fields(&self) -> Vec<Field> {
+        let mut fields = Vec::new();
+        self.expr.collect_fields(&mut fields);
+        fields
+    }
+
+    /// Может ли подойти хоть одна транзакция, если поля лежат в границах
+    /// `bounds` (включительно; `None` — границы неизвестны). `false` — точно нет.
+    pub fn may_match<F>(&self, bounds: F) -> bool
+    where
+        F: Fn(Field) -> Option<(FieldValue, FieldValue)>,
+    {
+        self.expr.eval_bounds(&bounds) != Tri::Never
+    }
+
+    /// Оставляет в потоке только подходящие транзакции; ошибки проходят как есть.
+    pub fn apply<'a, I>(self, input: I) -> Transactions<'a>
+    where
+        I: IntoIterator<Item = Result<Transaction>>,
+        I::IntoIter: 'a,
+    {
+        Box::new(input.into_iter().filter(move |tx| tx.as_ref().map_or(true, |tx| self.matches(tx))))
+    }
+}
+
+impl FromStr for Filter {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Filter::parse(s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare { field: Field, op: Op, value: FieldValue },
+    /// `low <= поле <= high`.
+    Between { field: Field, low: FieldValue, high: FieldValue },
+    Contains(String),
+}
+
+impl Expr {
+    fn eval(&self, tx: &Transaction) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(tx) && b.eval(tx),
+            Expr::Or(a, b) => a.eval(tx) || b.eval(tx),
+            Expr::Not(e) => !e.eval(tx),
+            Expr::Compare { field, op, value } => {
+                let ord = compare(tx, *field, value);
+                match op {
+                    Op::Eq => ord.is_eq(),
+                    Op::Ne => ord.is_ne(),
+                    Op::Lt => ord.is_lt(),
+                    Op::Le => ord.is_le(),
+                    Op::Gt => ord.is_gt(),
+                    Op::Ge => ord.is_ge(),
+                }
+            }
+            Expr::Between { field, low, high } => {
+                compare(tx, *field, low).is_ge() && compare(tx, *field, high).is_le()
+            }
+            Expr::Contains(needle) => tx.description.contains(needle.as_str()),
+        }
+    }
+}
+
+/// Результат условия для диапазона значений.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tri {
+    Never,
+    Maybe,
+    Always,
+}
+
+impl Tri {
+    fn from(always: bool, never: bool) -> Tri {
+        match (always, never) {
+            (true, _) => Tri::Always,
+            (_, true) => Tri::Never,
+            _ => Tri::Maybe,
+        }
+    }
+
+    fn not(self) -> Tri {
+        match self {
+            Tri::Never => Tri::Always,
+            Tri::Maybe => Tri::Maybe,
+            Tri::Always => Tri::Never,
+        }
+    }
+}
+
+impl Expr {
+    fn collect_fields(&self, fields: &mut Vec<Field>) {
+        let field = match self {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.collect_fields(fields);
+                b.collect_fields(fields);
+                return;
+            }
+            Expr::Not(e) => return e.collect_fields(fields),
+            Expr::Compare { field, .. } | Expr::Between { field, .. } => *field,
+            Expr::Contains(_) => Field::Description,
+        };
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+
+    fn eval_bounds<F>(&self, bounds: &F) -> Tri
+    where
+        F: Fn(Field) -> Option<(FieldValue, FieldValue)>,
+    {
+        match self {
+            Expr::And(a, b) => match (a.eval_bounds(bounds), b.eval_bounds(bounds)) {
+                (Tri::Never, _) | (_, Tri::Never) => Tri::Never,
+                (Tri::Always, Tri::Always) => Tri::Always,
+                _ => Tri::Maybe,
+            },
+            Expr::Or(a, b) => match (a.eval_bounds(bounds), b.eval_bounds(bounds)) {
+                (Tri::Always, _) | (_, Tri::Always) => Tri::Always,
+                (Tri::Never, Tri::Never) => Tri::Never,
+                _ => Tri::Maybe,
+            },
+            Expr::Not(e) => e.eval_bounds(bounds).not(),
+            Expr::Compare { field, op, value } => {
+                let Some((min, max)) = bounds(*field) else { return Tri::Maybe };
+                let eq = Tri::from(min == *value && max == *value, *value < min || *value > max);
+                match op {
+                    Op::Eq => eq,
+                    Op::Ne => eq.not(),
+                    Op::Lt => Tri::from(max < *value, min >= *value),
+                    Op::Le => Tri::from(max <= *value, min > *value),
+                    Op::Gt => Tri::from(min > *value, max <= *value),
+                    Op::Ge => Tri::from(min >= *value, max < *value),
+                }
+            }
+            Expr::Between { field, low, high } => {
+                let Some((min, max)) = bounds(*field) else { return Tri::Maybe };
+                Tri::from(min >= *low && max <= *high, max < *low || min > *high)
+            }
+            Expr::Contains(_) => Tri::Maybe,
+        }
+    }
+}
+
+/// Сравнение поля с литералом; описание не копируется.
+fn compare(tx: &Transaction, field: Field, value: &FieldValue) -> std::cmp::Ordering {
+    match (field, value) {
+        (Field::Description, FieldValue::Text(s)) => tx.description.as_str().cmp(s.as_str()),
+        _ => tx.field(field).cmp(value),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(Op),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(w) => w.clone(),
+            Token::Str(s) => format!("\"{}\"", s),
+            Token::Op(op) => format!("{:?}", op),
+            Token::LParen => "(".into(),
+            Token::RParen => ")".into(),
+            Token::Comma => ",".into(),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Word(w) if w.eq_ignore_ascii_case(keyword))
+    }
+}
+
+fn error(message: String) -> ParserError {
+    ParserError::Format(format!("Ошибка в фильтре: {}", message))
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ',' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    _ => Token::Comma,
+                });
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let next_eq = chars.next_if_eq(&'=').is_some();
+                let op = match (c, next_eq) {
+                    ('=', _) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', true) => Op::Le,
+                    ('<', false) if chars.next_if_eq(&'>').is_some() => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('>', true) => Op::Ge,
+                    ('>', false) => Op::Gt,
+                    _ => return Err(error("ожидался оператор после '!'".into())),
+                };
+                tokens.push(Token::Op(op));
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => text.push(escaped),
+                            None => return Err(error("незакрытая строка".into())),
+                        },
+                        Some(ch) => text.push(ch),
+                        None => return Err(error("незакрытая строка".into())),
+                    }
+                }
+                tokens.push(Token::Str(text));
+            }
+            c if is_word_char(c) => {
+                let mut word = String::new();
+                while let Some(ch) = chars.next_if(|&ch| is_word_char(ch)) {
+                    word.push(ch);
+                }
+                tokens.push(Token::Word(word));
+            }
+            _ => return Err(error(format!("неожиданный символ '{}'", c))),
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | ':' | '.')
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| error("неожиданный конец".into()))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek().is_some_and(|t| t.is_keyword(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(error(format!("ожидалось {}, найдено {}", expected.describe(), token.describe())));
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat_keyword("OR") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.not()?;
+        while self.eat_keyword("AND") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.eat_keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        let field = match self.next()? {
+            Token::LParen => {
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                return Ok(expr);
+            }
+            Token::Word(name) => name.parse::<Field>().map_err(|_| error(format!("неизвестное поле {}", name)))?,
+            other => return Err(error(format!("ожидалось поле, найдено {}", other.describe()))),
+        };
+
+        match self.next()? {
+            Token::Op(op) => {
+                let (low, high) = self.literal(field)?;
+                Ok(match op {
+                    Op::Eq if low == high => Expr::Compare { field, op, value: low },
+                    Op::Ne if low == high => Expr::Compare { field, op, value: low },
+                    Op::Eq => Expr::Between { field, low, high },
+                    Op::Ne => Expr::Not(Box::new(Expr::Between { field, low, high })),
+                    Op::Lt | Op::Ge => Expr::Compare { field, op, value: low },
+                    Op::Le | Op::Gt => Expr::Compare { field, op, value: high },
+                })
+            }
+            t if t.is_keyword("BETWEEN") => {
+                let (low, _) = self.literal(field)?;
+                if !self.eat_keyword("AND") {
+                    return Err(error("после BETWEEN ожидалось AND".into()));
+                }
+                let (_, high) = self.literal(field)?;
+                Ok(Expr::Between { field, low, high })
+            }
+            t if t.is_keyword("IN") => {
+                self.expect(Token::LParen)?;
+                let mut expr: Option<Expr> = None;
+                loop {
+                    let (low, high) = self.literal(field)?;
+                    let item = Expr::Between { field, low, high };
+                    expr = Some(match expr {
+                        Some(e) => Expr::Or(Box::new(e), Box::new(item)),
+                        None => item,
+                    });
+                    match self.next()? {
+                        Token::Comma => continue,
+                        Token::RParen => break,
+                        other => return Err(error(format!("ожидалось ',' или ')', найдено {}", other.describe()))),
+                    }
+                }
+                expr.ok_or_else(|| error("пустой список IN".into()))
+            }
+            t if t.is_keyword("CONTAINS") => {
+                if field != Field::Description {
+                    return Err(error(format!("CONTAINS применим только к DESCRIPTION, не к {}", field)));
+                }
+                match self.next()? {
+                    Token::Str(s) => Ok(Expr::Contains(s)),
+                    other => Err(error(format!("после CONTAINS ожидалась строка, найдено {}", other.describe()))),
+                }
+            }
+            other => Err(error(format!("после {} ожидался оператор, найдено {}", field, other.describe()))),
+        }
+    }
+
+    /// Литерал для поля как диапазон `[low, high]`; для всего, кроме дат, `low == high`.
+    fn literal(&mut self, field: Field) -> Result<(FieldValue, FieldValue)> {
+        let token = self.next()?;
+        let invalid = |token: &Token| error(format!("неверное значение для {}: {}", field, token.describe()));
+        let single = |v: FieldValue| (v.clone(), v);
+        match (field, &token) {
+            (Field::Description, Token::Str(s)) => Ok(single(FieldValue::Text(s.clone()))),
+            (Field::Timestamp, Token::Word(w)) => {
+                let (low, high) = parse_time(w).ok_or_else(|| invalid(&token))?;
+                Ok((FieldValue::Unsigned(low), FieldValue::Unsigned(high)))
+            }
+            (Field::TxId | Field::FromUserId | Field::ToUserId, Token::Word(w)) => {
+                Ok(single(FieldValue::Unsigned(w.parse().map_err(|_| invalid(&token))?)))
+            }
+            (Field::Amount, Token::Word(w)) => Ok(single(FieldValue::Signed(w.parse().map_err(|_| invalid(&token))?))),
+            (Field::TxType, Token::Word(w) | Token::Str(w)) => {
+                Ok(single(FieldValue::Type(w.to_ascii_uppercase().parse().map_err(|_| invalid(&token))?)))
+            }
+            (Field::Status, Token::Word(w) | Token::Str(w)) => {
+                Ok(single(FieldValue::Status(w.to_ascii_uppercase().parse().map_err(|_| invalid(&token))?)))
+            }
+            _ => Err(invalid(&token)),
+        }
+    }
+}
+
+/// Время в мс как диапазон: число — точка, дата — сутки, `YYYY-MM-DDTHH:MM[:SS]` — минута или секунда (UTC).
+fn parse_time(s: &str) -> Option<(u64, u64)> {
+    if let Ok(ms) = s.parse::<u64>() {
+        return Some((ms, ms));
+    }
+    let (date, time) = match s.split_once('T') {
+        Some((d, t)) => (d, Some(t)),
+        None => (s, None),
+    };
+    let start = date.parse::<Date>().ok()?.start_timestamp(0);
+    let (offset, span) = match time {
+        None => (0, 86_400_000),
+        Some(t) => {
+            let parts: Vec<&str> = t.split(':').collect();
+            let num = |p: &str, max: i64| p.parse::<i64>().ok().filter(|v| (0..max).contains(v));
+            match parts.as_slice() {
+                [h, m] => ((num(h, 24)? * 60 + num(m, 60)?) * 60_000, 60_000),
+                [h, m, sec] => ((num(h, 24)? * 3600 + num(m, 60)? * 60 + num(sec, 60)?) * 1000, 1000),
+                _ => return None,
+            }
+        }
+    };
+    // Дата за пределами i64 мс насыщается, поэтому время к ней прибавляется с проверкой.
+    let low = u64::try_from(start.checked_add(offset)?).ok()?;
+    Some((low, low + span - 1))
+}
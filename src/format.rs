@@ -0,0 +1,182 @@
+//! Выбор формата по имени или расширению файла.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::filter::Filter;
+use crate::{bin_format, columnar_format, compact_format, csv_format, text_format, Transaction, Result, ParserError};
+
+/// Поток транзакций из любого формата.
+pub type Transactions<'a> = Box<dyn Iterator<Item = Result<Transaction>> + 'a>;
+
+/// Потоковая запись, общая для всех форматов.
+pub trait TransactionWrite {
+    /// Пишет одну транзакцию.
+    fn write(&mut self, tx: &Transaction) -> Result<()>;
+
+    /// Завершает запись (заголовок пустого CSV, сброс буферов).
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+macro_rules! impl_transaction_write {
+    ($($module:ident),*) => {$(
+        impl<W: Write> TransactionWrite for $module::Writer<W> {
+            fn write(&mut self, tx: &Transaction) -> Result<()> {
+                $module::Writer::write(self, tx)
+            }
+
+            fn finish(self: Box<Self>) -> Result<()> {
+                $module::Writer::finish(*self).map(drop)
+            }
+        }
+    )*};
+}
+
+impl_transaction_write!(csv_format, text_format, bin_format, compact_format, columnar_format);
+
+/// Поддерживаемый формат файла.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Text,
+    Bin,
+    /// Компактный бинарник с varint и словарём описаний.
+    Compact,
+    /// Колоночный формат с группами строк для аналитики.
+    Columnar,
+}
+
+impl Format {
+    /// Расширение файла без точки.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Text => "txt",
+            Format::Bin => "bin",
+            Format::Compact => "ypbc",
+            Format::Columnar => "ypcol",
+        }
+    }
+
+    /// Формат по расширению файла (`csv`, `txt`/`text`, `bin`, `ypbc`, `ypcol`).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "csv" => Some(Format::Csv),
+            "txt" | "text" => Some(Format::Text),
+            "bin" => Some(Format::Bin),
+            "ypbc" => Some(Format::Compact),
+            "ypcol" => Some(Format::Columnar),
+            _ => None,
+        }
+    }
+
+    /// Читает все транзакции в этом формате.
+    pub fn read<R: Read>(self, reader: R) -> Result<Vec<Transaction>> {
+        match self {
+            Format::Csv => csv_format::from_read(reader),
+            Format::Text => text_format::from_read(reader),
+            Format::Bin => bin_format::from_read(reader),
+            Format::Compact => compact_format::from_read(reader),
+            Format::Columnar => columnar_format::from_read(reader),
+        }
+    }
+
+    /// Потоковое чтение по одной транзакции.
+    pub fn reader<'a, R: Read + 'a>(self, reader: R) -> Transactions<'a> {
+        match self {
+            Format::Csv => Box::new(csv_format::Reader::new(reader)),
+            Format::Text => Box::new(text_format::Reader::new(reader)),
+            Format::Bin => Box::new(bin_format::Reader::new(reader)),
+            Format::Compact => Box::new(compact_format::Reader::new(reader)),
+            Format::Columnar => Box::new(columnar_format::Reader::new(reader)),
+        }
+    }
+
+    /// Потоковое чтение файла с фильтром. Колоночный формат пропускает группы
+    /// строк, где по статистике нет подходящих транзакций.
+    pub fn file_reader(self, file: File, filter: Option<Filter>) -> Transactions<'static> {
+        match (self, filter) {
+            (Format::Columnar, Some(filter)) => Box::new(columnar_format::Reader::seekable(file).filter(filter)),
+            (Format::Columnar, None) => Box::new(columnar_format::Reader::seekable(file)),
+            (_, Some(filter)) => filter.apply(self.reader(BufReader::new(file))),
+            (_, None) => self.reader(BufReader::new(file)),
+        }
+    }
+
+    /// Потоковая запись по одной транзакции.
+    pub fn writer<'a, W: Write + 'a>(self, writer: W) -> Box<dyn TransactionWrite + 'a> {
+        match self {
+            Format::Csv => Box::new(csv_format::Writer::new(writer)),
+            Format::Text => Box::new(text_format::Writer::new(writer)),
+            Format::Bin => Box::new(bin_format::Writer::new(writer)),
+            Format::Compact => Box::new(compact_format::Writer::new(writer)),
+            Format::Columnar => Box::new(columnar_format::Writer::new(writer)),
+        }
+    }
+
+    /// Как [`Format::writer`], но бинарный формат пишется с параметрами `bin`.
+    pub fn writer_with<'a, W: Write + 'a>(
+        self,
+        writer: W,
+        bin: &bin_format::WriteOptions,
+    ) -> Result<Box<dyn TransactionWrite + 'a>> {
+        Ok(match self {
+            Format::Bin => Box::new(bin_format::Writer::with_options(writer, bin)?),
+            _ => self.writer(writer),
+        })
+    }
+
+    /// Потоковая запись в конец файла, где уже `written` записей этого формата.
+    pub fn appending_writer<'a, W: Write + 'a>(self, writer: W, written: usize) -> Box<dyn TransactionWrite + 'a> {
+        match self {
+            Format::Csv => Box::new(csv_format::Writer::appending(writer, written)),
+            Format::Text => Box::new(text_format::Writer::appending(writer, written)),
+            Format::Bin => Box::new(bin_format::Writer::new(writer)),
+            Format::Compact => Box::new(compact_format::Writer::new(writer)),
+            Format::Columnar if written == 0 => Box::new(columnar_format::Writer::new(writer)),
+            Format::Columnar => Box::new(columnar_format::Writer::appending(writer)),
+        }
+    }
+
+    /// Пишет транзакции в этом формате.
+    pub fn write<W: Write>(self, writer: W, transactions: &[Transaction]) -> Result<()> {
+        match self {
+            Format::Csv => csv_format::write_to(writer, transactions),
+            Format::Text => text_format::write_to(writer, transactions),
+            Format::Bin => bin_format::write_to(writer, transactions),
+            Format::Compact => compact_format::write_to(writer, transactions),
+            Format::Columnar => columnar_format::write_to(writer, transactions),
+        }
+    }
+}
+
+/// Вход вида `путь` или `формат:путь`. Без префикса формат берётся по
+/// расширению, затем `default`.
+pub fn parse_input(spec: &str, default: Option<Format>) -> Result<(PathBuf, Format)> {
+    if let Some((prefix, path)) = spec.split_once(':') {
+        if let Ok(format) = prefix.parse() {
+            return Ok((PathBuf::from(path), format));
+        }
+    }
+    let path = PathBuf::from(spec);
+    let format = Format::from_path(&path)
+        .or(default)
+        .ok_or_else(|| ParserError::Format(format!("Не удалось определить формат: {}", spec)))?;
+    Ok((path, format))
+}
+
+impl FromStr for Format {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "text" => Ok(Format::Text),
+            "bin" | "binary" => Ok(Format::Bin),
+            "compact" => Ok(Format::Compact),
+            "columnar" => Ok(Format::Columnar),
+            _ => Err(ParserError::Format(format!("Неизвестный формат: {}", s))),
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Разбор YPBankBin по байтам: смещение, байты и значение каждого поля.
+//!
+//! Печатается одна строка на поле, как в таблицах `examples/YPBankBinFormat_ru.md`.
+//! Несоответствия (размер записи, байты перечислений, время вне `i64`, CRC,
+//! UTF-8, трейлер) помечаются `!!` в конце строки. После мусора вместо
+//! `MAGIC` разбор продолжается со следующей сигнатуры записи. Файл читается
+//! потоком: в памяти держатся поля одной записи, текст читается кусками
+//! (длинный показывается началом), пропускаемые участки не копятся.
+
+use std::io::{Read, Write};
+use crate::bin_format::{self, FLAG_INDEX, FLAG_RECORD_CRC, FLAG_TRAILER};
+use crate::bin_window::Window;
+use crate::calendar::Date;
+use crate::crc32::{crc32, Crc32};
+use crate::{json, Result};
+
+/// Сколько байт поля показывать в шестнадцатеричном виде.
+const HEX_BYTES: usize = 8;
+
+/// Сколько байт текстового поля показывать; длиннее — обрезается.
+const TEXT_BYTES: usize = 1024;
+
+/// По сколько байт читается текстовое поле.
+const TEXT_CHUNK: usize = 64 * 1024;
+
+/// Какие записи печатать; заголовок, трейлер и итог печатаются всегда.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InspectOptions {
+    /// Пропустить столько записей с начала.
+    pub skip: u64,
+    /// Напечатать не больше стольких записей.
+    pub limit: Option<u64>,
+}
+
+/// Итог разбора.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InspectSummary {
+    pub records: u64,
+    /// Сколько строк помечено `!!`.
+    pub issues: u64,
+}
+
+/// Печатает разбор файла из `input` в `writer`.
+pub fn inspect<R: Read, W: Write>(input: R, writer: W, options: &InspectOptions) -> Result<InspectSummary> {
+    let data = Window::new(input);
+    let mut dump = Dump { data, writer, options: *options, summary: InspectSummary::default(), quiet: false };
+    dump.run()?;
+    writeln!(dump.writer, "{} records, {} issues", dump.summary.records, dump.summary.issues)?;
+    Ok(dump.summary)
+}
+
+struct Dump<R, W: Write> {
+    data: Window<R>,
+    writer: W,
+    options: InspectOptions,
+    summary: InspectSummary,
+    /// Текущая запись вне диапазона печати.
+    quiet: bool,
+}
+
+impl<R: Read, W: Write> Dump<R, W> {
+    fn run(&mut self) -> Result<()> {
+        let mut flags = 0;
+        if self.data.peek(4)? == bin_format::FILE_MAGIC {
+            flags = self.header()?;
+        }
+        let crc = flags & FLAG_RECORD_CRC != 0;
+        let trailer = flags & FLAG_TRAILER != 0;
+        let (mut count, mut sum) = (0u64, 0i128);
+
+        while !self.data.is_at_end()? {
+            let magic = self.data.peek(4)?;
+            let is_record = magic == bin_format::MAGIC;
+            self.quiet = false;
+            if trailer && magic == bin_format::TRAILER_MAGIC {
+                // CRC трейлера считается по его байтам, поэтому они держатся в окне.
+                self.data.set_mark();
+                let result = self.trailer(flags, count, sum);
+                self.data.clear_mark();
+                return result;
+            }
+            let number = self.summary.records + 1;
+            self.quiet = number <= self.options.skip
+                || self.options.limit.is_some_and(|limit| number > self.options.skip + limit);
+            if !is_record {
+                let start = self.data.pos();
+                let (len, head, _) = self.data.skip_to_record(trailer, HEX_BYTES + 1)?;
+                let issue = Some("no MAGIC, skipped to next record".into());
+                self.write_line(start, &head, len, "???", format!("{} bytes", len), issue)?;
+                continue;
+            }
+            self.summary.records += 1;
+            if !self.quiet {
+                writeln!(self.writer, "Record {} @ {:#010x}", number, self.data.pos())?;
+            }
+            self.data.set_mark();
+            let amount = self.record(crc);
+            self.data.clear_mark();
+            if let Some(amount) = amount? {
+                count += 1;
+                sum += amount as i128;
+            }
+        }
+        if trailer {
+            self.quiet = false;
+            self.issue("no trailer")?;
+        }
+        Ok(())
+    }
+
+    /// Заголовок v2; возвращает флаги.
+    fn header(&mut self) -> Result<u16> {
+        writeln!(self.writer, "File header @ 0x00000000")?;
+        self.line(4, "FILE_MAGIC", "\"YPBF\"".into(), None)?;
+        let Some(version) = self.u16_field("VERSION")? else { return Ok(0) };
+        let issue = (version != 2).then(|| "unsupported version".to_string());
+        self.line(2, "VERSION", version.to_string(), issue)?;
+        let Some(flags) = self.u16_field("FLAGS")? else { return Ok(0) };
+        let names: Vec<&str> = [(FLAG_RECORD_CRC, "RECORD_CRC"), (FLAG_TRAILER, "TRAILER"), (FLAG_INDEX, "INDEX")]
+            .iter()
+            .filter(|(flag, _)| flags & flag != 0)
+            .map(|&(_, name)| name)
+            .collect();
+        let known = FLAG_RECORD_CRC | FLAG_TRAILER | FLAG_INDEX;
+        let issue = (flags & !known != 0).then(|| format!("unknown flags {:#06x}", flags & !known));
+        self.line(2, "FLAGS", format!("{:#06x} ({})", flags, names.join(" | ")), issue)?;
+        let Some(created_at) = self.u64_field("CREATED_AT")? else { return Ok(flags) };
+        self.line(8, "CREATED_AT", timestamp(created_at), timestamp_issue(created_at))?;
+        let Some(len) = self.u16_field("PRODUCER_LEN")? else { return Ok(flags) };
+        self.line(2, "PRODUCER_LEN", len.to_string(), None)?;
+        self.text_field("PRODUCER", len as usize, &mut Crc32::new())?;
+        Ok(flags)
+    }
+
+    /// Запись с MAGIC в текущей позиции; возвращает `AMOUNT` целой записи.
+    /// Поля до описания держатся в окне от метки для проверки CRC.
+    fn record(&mut self, crc: bool) -> Result<Option<i64>> {
+        self.line(4, "MAGIC", "\"YPBN\"".into(), None)?;
+        let Some(size) = self.u32_field("RECORD_SIZE")? else { return Ok(None) };
+        self.line(4, "RECORD_SIZE", size.to_string(), None)?;
+
+        let Some(tx_id) = self.u64_field("TX_ID")? else { return Ok(None) };
+        self.line(8, "TX_ID", tx_id.to_string(), None)?;
+        if !self.enum_field("TX_TYPE", |b| bin_format::decode_type(b).map(|t| t.as_str()))? {
+            return Ok(None);
+        }
+        let Some(from) = self.u64_field("FROM_USER_ID")? else { return Ok(None) };
+        self.line(8, "FROM_USER_ID", from.to_string(), None)?;
+        let Some(to) = self.u64_field("TO_USER_ID")? else { return Ok(None) };
+        self.line(8, "TO_USER_ID", to.to_string(), None)?;
+        let Some(amount) = self.u64_field("AMOUNT")? else { return Ok(None) };
+        self.line(8, "AMOUNT", (amount as i64).to_string(), None)?;
+        let Some(ts) = self.u64_field("TIMESTAMP")? else { return Ok(None) };
+        self.line(8, "TIMESTAMP", timestamp(ts), timestamp_issue(ts))?;
+        if !self.enum_field("STATUS", |b| bin_format::decode_status(b).map(|s| s.as_str()))? {
+            return Ok(None);
+        }
+        let Some(desc_len) = self.u32_field("DESC_LEN")? else { return Ok(None) };
+        let issue = (size as u64 != 46 + desc_len as u64)
+            .then(|| format!("RECORD_SIZE {} != 46 + DESC_LEN {}", size, desc_len));
+        self.line(4, "DESC_LEN", desc_len.to_string(), issue)?;
+        // Описание может быть любой длины: CRC досчитывается по ходу чтения,
+        // а метка снимается, чтобы окно не копило его байты.
+        let mut body_crc = Crc32::new();
+        body_crc.update(&self.data.marked()[8..]);
+        self.data.clear_mark();
+        if !self.text_field("DESCRIPTION", desc_len as usize, &mut body_crc)? {
+            return Ok(None);
+        }
+
+        if crc {
+            let Some(expected) = self.u32_field("CRC32")? else { return Ok(None) };
+            let actual = body_crc.finish();
+            let issue = (expected != actual).then(|| format!("computed {:#010x}", actual));
+            self.line(4, "CRC32", format!("{:#010x}", expected), issue)?;
+        }
+        Ok(Some(amount as i64))
+    }
+
+    /// Трейлер v2 и то, что за ним.
+    fn trailer(&mut self, flags: u16, count: u64, sum: i128) -> Result<()> {
+        writeln!(self.writer, "Trailer @ {:#010x}", self.data.pos())?;
+        self.line(4, "TRAILER_MAGIC", "\"YPBT\"".into(), None)?;
+        let Some(record_count) = self.u64_field("RECORD_COUNT")? else { return Ok(()) };
+        let issue = (record_count != count).then(|| format!("{} records in file", count));
+        self.line(8, "RECORD_COUNT", record_count.to_string(), issue)?;
+        if self.missing(16, "AMOUNT_SUM")? {
+            return Ok(());
+        }
+        let amount_sum = i128::from_be_bytes(self.data.peek(16)?.try_into().unwrap_or_default());
+        let issue = (amount_sum != sum).then(|| format!("records sum to {}", sum));
+        self.line(16, "AMOUNT_SUM", amount_sum.to_string(), issue)?;
+        let Some(expected) = self.u32_field("TRAILER_CRC")? else { return Ok(()) };
+        let actual = crc32(&self.data.marked()[4..]);
+        let issue = (expected != actual).then(|| format!("computed {:#010x}", actual));
+        self.line(4, "TRAILER_CRC", format!("{:#010x}", expected), issue)?;
+
+        self.data.clear_mark();
+        let index = self.data.peek(4)? == crate::bin_index::INDEX_MAGIC;
+        let start = self.data.pos();
+        let (rest, head) = self.data.skip_to_end(HEX_BYTES + 1)?;
+        if rest == 0 {
+            if flags & FLAG_INDEX != 0 {
+                self.issue("no index after trailer")?;
+            }
+        } else if flags & FLAG_INDEX != 0 && index {
+            writeln!(self.writer, "Index @ {:#010x}", start)?;
+            self.write_line(start, &head, rest, "INDEX", format!("{} bytes", rest), None)?;
+        } else {
+            self.write_line(start, &head, rest, "???", format!("{} bytes", rest), Some("data after trailer".into()))?;
+        }
+        Ok(())
+    }
+
+    /// Печатает строку поля длиной `len` с текущей позиции и сдвигает позицию;
+    /// байты поля уже в окне.
+    fn line(&mut self, len: usize, field: &str, value: String, issue: Option<String>) -> Result<()> {
+        let start = self.data.pos();
+        let head = self.data.peek(len.min(HEX_BYTES + 1))?.to_vec();
+        self.data.advance(len);
+        self.write_line(start, &head, len as u64, field, value, issue)
+    }
+
+    /// Строка поля со смещения `start`; `head` — его первые байты.
+    fn write_line(
+        &mut self,
+        start: u64,
+        head: &[u8],
+        len: u64,
+        field: &str,
+        value: String,
+        issue: Option<String>,
+    ) -> Result<()> {
+        if issue.is_some() {
+            self.summary.issues += 1;
+        }
+        if self.quiet {
+            return Ok(());
+        }
+        let mut hex: Vec<String> = head.iter().take(HEX_BYTES).map(|b| format!("{:02X}", b)).collect();
+        if len > HEX_BYTES as u64 {
+            hex.push("..".into());
+        }
+        write!(self.writer, "  {:08x}  {:<26}  {:<13} {}", start, hex.join(" "), field, value)?;
+        if let Some(issue) = issue {
+            write!(self.writer, "  !! {}", issue)?;
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn issue(&mut self, message: &str) -> Result<()> {
+        self.summary.issues += 1;
+        if !self.quiet {
+            writeln!(self.writer, "  {:08x}  !! {}", self.data.pos(), message)?;
+        }
+        Ok(())
+    }
+
+    /// Данные кончились раньше поля: печатает остаток и сообщает об обрыве.
+    fn missing(&mut self, len: usize, field: &str) -> Result<bool> {
+        let rest = self.data.peek(len)?.len();
+        if rest >= len {
+            return Ok(false);
+        }
+        let issue = format!("truncated: {} bytes missing", len - rest);
+        self.line(rest, field, String::new(), Some(issue))?;
+        Ok(true)
+    }
+
+    fn bytes<const N: usize>(&mut self, field: &str) -> Result<Option<[u8; N]>> {
+        if self.missing(N, field)? {
+            return Ok(None);
+        }
+        Ok(self.data.peek(N)?.try_into().ok())
+    }
+
+    fn u16_field(&mut self, field: &str) -> Result<Option<u16>> {
+        Ok(self.bytes(field)?.map(u16::from_be_bytes))
+    }
+
+    fn u32_field(&mut self, field: &str) -> Result<Option<u32>> {
+        Ok(self.bytes(field)?.map(u32::from_be_bytes))
+    }
+
+    fn u64_field(&mut self, field: &str) -> Result<Option<u64>> {
+        Ok(self.bytes(field)?.map(u64::from_be_bytes))
+    }
+
+    /// Байт перечисления; `false` — данные кончились.
+    fn enum_field<F>(&mut self, field: &str, decode: F) -> Result<bool>
+    where
+        F: Fn(u8) -> Result<&'static str>,
+    {
+        let Some([byte]) = self.bytes::<1>(field)? else { return Ok(false) };
+        match decode(byte) {
+            Ok(name) => self.line(1, field, name.to_string(), None)?,
+            Err(_) => self.line(1, field, byte.to_string(), Some(format!("invalid {} byte", field)))?,
+        }
+        Ok(true)
+    }
+
+    /// Строка UTF-8 длиной `len`; `false` — данные кончились. Поле читается
+    /// кусками и добавляется в `crc`; в памяти остаётся только начало для показа.
+    fn text_field(&mut self, field: &str, len: usize, crc: &mut Crc32) -> Result<bool> {
+        let start = self.data.pos();
+        let head = self.data.peek(len.min(HEX_BYTES + 1))?.to_vec();
+        let mut preview = Vec::new();
+        // Незаконченная последовательность UTF-8 с конца прошлого куска.
+        let mut pending = Vec::new();
+        let mut issue = None;
+        let mut done = 0;
+        while done < len {
+            let chunk = self.data.peek((len - done).min(TEXT_CHUNK))?;
+            if chunk.is_empty() {
+                break;
+            }
+            crc.update(chunk);
+            let room = TEXT_BYTES.saturating_sub(preview.len());
+            preview.extend_from_slice(&chunk[..chunk.len().min(room)]);
+            if issue.is_none() {
+                let from = done - pending.len();
+                pending.extend_from_slice(chunk);
+                match std::str::from_utf8(&pending) {
+                    Ok(_) => pending.clear(),
+                    Err(e) if e.error_len().is_none() => {
+                        pending.drain(..e.valid_up_to());
+                    }
+                    Err(e) => issue = Some(utf8_issue(from + e.valid_up_to(), e.error_len())),
+                }
+            }
+            let n = chunk.len();
+            self.data.advance(n);
+            done += n;
+        }
+        if done < len {
+            let issue = format!("truncated: {} bytes missing", len - done);
+            self.write_line(start, &head, done as u64, field, String::new(), Some(issue))?;
+            return Ok(false);
+        }
+        if issue.is_none() && !pending.is_empty() {
+            issue = Some(utf8_issue(len - pending.len(), None));
+        }
+        let value = match issue {
+            Some(_) => format!("{} bytes", len),
+            None if len <= TEXT_BYTES => json::string(std::str::from_utf8(&preview).unwrap_or_default()),
+            None => {
+                let cut = match std::str::from_utf8(&preview[..TEXT_BYTES]) {
+                    Ok(text) => text,
+                    Err(e) => std::str::from_utf8(&preview[..e.valid_up_to()]).unwrap_or_default(),
+                };
+                format!("{}.. ({} bytes)", json::string(cut), len)
+            }
+        };
+        self.write_line(start, &head, len as u64, field, value, issue)?;
+        Ok(true)
+    }
+}
+
+/// Сообщение о битом UTF-8 в том же виде, что у `std::str::Utf8Error`.
+fn utf8_issue(index: usize, error_len: Option<usize>) -> String {
+    match error_len {
+        Some(n) => format!("invalid UTF-8: invalid utf-8 sequence of {} bytes from index {}", n, index),
+        None => format!("invalid UTF-8: incomplete utf-8 byte sequence from index {}", index),
+    }
+}
+
+/// Миллисекунды и момент UTC; момент — только для значений в пределах `i64`.
+fn timestamp(ms: u64) -> String {
+    if timestamp_issue(ms).is_some() {
+        return ms.to_string();
+    }
+    let time = ms % 86_400_000 / 1000;
+    format!(
+        "{} ({}T{:02}:{:02}:{:02}Z)",
+        ms,
+        Date::from_timestamp(ms, 0),
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Время больше `i64::MAX` миллисекунд не представить в знаковых
+/// миллисекундах, которыми с ним работают остальные системы.
+fn timestamp_issue(ms: u64) -> Option<String> {
+    (ms > i64::MAX as u64).then(|| "beyond i64::MAX ms".to_string())
+}
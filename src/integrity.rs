@@ -0,0 +1,217 @@
+//! Проверка целостности потока: повторяющиеся `TX_ID`, убывание `TX_ID` и
+//! `TIMESTAMP`, пропуски в диапазоне `TX_ID`.
+//!
+//! Позиции — номера записей с 1, как `# Запись N` в текстовом формате.
+//! Для поиска повторов и пропусков хранится каждый `TX_ID` с позицией.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use crate::compare::ReportFormat;
+use crate::field::Field;
+use crate::{json, Transaction, Result, ParserError};
+
+/// Какие проверки выполнять.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntegrityOptions {
+    pub tx_id_order: bool,
+    pub timestamp_order: bool,
+    pub gaps: bool,
+}
+
+impl Default for IntegrityOptions {
+    fn default() -> Self {
+        IntegrityOptions { tx_id_order: true, timestamp_order: true, gaps: true }
+    }
+}
+
+impl IntegrityOptions {
+    /// Отключает проверки из списка через запятую: `tx-order,timestamp-order,gaps`.
+    pub fn skip(mut self, list: &str) -> Result<Self> {
+        for item in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match item {
+                "tx-order" => self.tx_id_order = false,
+                "timestamp-order" => self.timestamp_order = false,
+                "gaps" => self.gaps = false,
+                _ => return Err(ParserError::Format(format!("Неизвестная проверка: {}", item))),
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// `TX_ID`, встретившийся несколько раз.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    pub tx_id: u64,
+    pub positions: Vec<usize>,
+}
+
+/// Значение поля меньше, чем в предыдущей записи.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderViolation {
+    pub field: Field,
+    pub position: usize,
+    pub previous: u64,
+    pub current: u64,
+}
+
+/// Отсутствующие `TX_ID` от `from` до `to` включительно.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    pub from: u64,
+    pub to: u64,
+}
+
+impl Gap {
+    pub fn missing(&self) -> u64 {
+        self.to - self.from + 1
+    }
+}
+
+/// Результат проверки.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub records: usize,
+    /// По возрастанию `TX_ID`.
+    pub duplicates: Vec<Duplicate>,
+    /// В порядке записей.
+    pub order_violations: Vec<OrderViolation>,
+    pub gaps: Vec<Gap>,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.duplicates.is_empty() && self.order_violations.is_empty() && self.gaps.is_empty()
+    }
+
+    /// Отчёт текстом или JSON.
+    pub fn write<W: Write>(&self, mut writer: W, format: ReportFormat) -> Result<()> {
+        match format {
+            ReportFormat::Text => {
+                for d in &self.duplicates {
+                    let positions: Vec<String> = d.positions.iter().map(usize::to_string).collect();
+                    writeln!(writer, "DUPLICATE TX_ID {} at records {}", d.tx_id, positions.join(", "))?;
+                }
+                for v in &self.order_violations {
+                    writeln!(
+                        writer,
+                        "{} decreases at record {}: {} after {}",
+                        v.field, v.position, v.current, v.previous
+                    )?;
+                }
+                for g in &self.gaps {
+                    writeln!(writer, "GAP TX_ID {}..{} ({} missing)", g.from, g.to, g.missing())?;
+                }
+                if self.is_clean() {
+                    writeln!(writer, "Checked {} records, no integrity issues found.", self.records)?;
+                } else {
+                    writeln!(
+                        writer,
+                        "Checked {} records: {} duplicate ids, {} order violations, {} gaps.",
+                        self.records,
+                        self.duplicates.len(),
+                        self.order_violations.len(),
+                        self.gaps.len()
+                    )?;
+                }
+            }
+            ReportFormat::Json => {
+                let duplicates: Vec<String> = self
+                    .duplicates
+                    .iter()
+                    .map(|d| {
+                        let positions: Vec<String> = d.positions.iter().map(usize::to_string).collect();
+                        format!("{{\"tx_id\":{},\"positions\":[{}]}}", d.tx_id, positions.join(","))
+                    })
+                    .collect();
+                let violations: Vec<String> = self
+                    .order_violations
+                    .iter()
+                    .map(|v| {
+                        format!(
+                            "{{\"field\":{},\"position\":{},\"previous\":{},\"current\":{}}}",
+                            json::string(v.field.name()),
+                            v.position,
+                            v.previous,
+                            v.current
+                        )
+                    })
+                    .collect();
+                let gaps: Vec<String> = self
+                    .gaps
+                    .iter()
+                    .map(|g| format!("{{\"from\":{},\"to\":{},\"missing\":{}}}", g.from, g.to, g.missing()))
+                    .collect();
+                writeln!(
+                    writer,
+                    "{{\"records\":{},\"duplicates\":[{}],\"order_violations\":[{}],\"gaps\":[{}]}}",
+                    self.records,
+                    duplicates.join(","),
+                    violations.join(","),
+                    gaps.join(",")
+                )?;
+            }
+            ReportFormat::Csv => {
+                return Err(ParserError::Format("Отчёт о целостности выводится как text или json".into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Проверяет поток транзакций.
+pub fn check<I>(transactions: I, options: &IntegrityOptions) -> Result<IntegrityReport>
+where
+    I: IntoIterator<Item = Result<Transaction>>,
+{
+    let mut first_seen: HashMap<u64, usize> = HashMap::new();
+    let mut repeats: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    let mut order_violations = Vec::new();
+    let mut previous: Option<(u64, u64)> = None;
+    let mut records = 0;
+
+    for tx in transactions {
+        let tx = tx?;
+        records += 1;
+        let position = records;
+
+        if let Some(&first) = first_seen.get(&tx.tx_id) {
+            repeats.entry(tx.tx_id).or_insert_with(|| vec![first]).push(position);
+        } else {
+            first_seen.insert(tx.tx_id, position);
+        }
+
+        if let Some((prev_id, prev_ts)) = previous {
+            if options.tx_id_order && tx.tx_id < prev_id {
+                order_violations.push(OrderViolation { field: Field::TxId, position, previous: prev_id, current: tx.tx_id });
+            }
+            if options.timestamp_order && tx.timestamp < prev_ts {
+                order_violations.push(OrderViolation {
+                    field: Field::Timestamp,
+                    position,
+                    previous: prev_ts,
+                    current: tx.timestamp,
+                });
+            }
+        }
+        previous = Some((tx.tx_id, tx.timestamp));
+    }
+
+    let mut gaps = Vec::new();
+    if options.gaps {
+        let mut ids: Vec<u64> = first_seen.into_keys().collect();
+        ids.sort_unstable();
+        for pair in ids.windows(2) {
+            if pair[1] - pair[0] > 1 {
+                gaps.push(Gap { from: pair[0] + 1, to: pair[1] - 1 });
+            }
+        }
+    }
+
+    Ok(IntegrityReport {
+        records,
+        duplicates: repeats.into_iter().map(|(tx_id, positions)| Duplicate { tx_id, positions }).collect(),
+        order_violations,
+        gaps,
+    })
+}
@@ -0,0 +1,52 @@
+//! Минимальная запись JSON для отчётов.
+
+use std::io::Write;
+use crate::field::FieldValue;
+use crate::{Transaction, Result};
+
+/// Строка JSON в кавычках с экранированием.
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Числа — как есть, остальное — строкой.
+pub(crate) fn value(v: &FieldValue) -> String {
+    match v {
+        FieldValue::Unsigned(n) => n.to_string(),
+        FieldValue::Signed(n) => n.to_string(),
+        FieldValue::Type(t) => string(t.as_str()),
+        FieldValue::Status(s) => string(s.as_str()),
+        FieldValue::Text(s) => string(s),
+    }
+}
+
+/// Транзакция объектом с именами полей как в CSV.
+pub(crate) fn write_transaction<W: Write>(writer: &mut W, tx: &Transaction) -> Result<()> {
+    write!(
+        writer,
+        "{{\"TX_ID\":{},\"TX_TYPE\":{},\"FROM_USER_ID\":{},\"TO_USER_ID\":{},\"AMOUNT\":{},\"TIMESTAMP\":{},\"STATUS\":{},\"DESCRIPTION\":{}}}",
+        tx.tx_id,
+        string(tx.tx_type.as_str()),
+        tx.from_user_id,
+        tx.to_user_id,
+        tx.amount,
+        tx.timestamp,
+        string(tx.status.as_str()),
+        string(&tx.description)
+    )?;
+    Ok(())
+}
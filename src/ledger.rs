@@ -0,0 +1,349 @@
+//! Балансы пользователей по потоку транзакций.
+//!
+//! `DEPOSIT` зачисляет `AMOUNT` на `TO_USER_ID`, `WITHDRAWAL` списывает с
+//! `FROM_USER_ID`, `TRANSFER` делает и то и другое. Пользователь `0` — внешний
+//! мир, его баланс не ведётся.
+//!
+//! Снимок ([`Snapshot`]) фиксирует балансы и позицию `(TIMESTAMP, TX_ID)`, до
+//! которой включительно учтены транзакции; поверх него применяются только
+//! более поздние. Неучтённые `PENDING` до этой позиции снимок помнит
+//! отдельно: если та же запись придёт позже как `SUCCESS`, она применяется.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, BufReader, Read, Write};
+use crate::compare::ReportFormat;
+use crate::{Transaction, TxType, TxStatus, Result, ParserError};
+
+/// Внешний счёт (пополнение извне, вывод наружу).
+pub const EXTERNAL_USER: u64 = 0;
+
+/// Какие транзакции учитывать.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LedgerOptions {
+    /// Учитывать ли `PENDING` вместе с `SUCCESS`.
+    pub include_pending: bool,
+    /// Учитывать только транзакции с `TIMESTAMP <= as_of`.
+    pub as_of: Option<u64>,
+}
+
+/// Что пошло не так при применении транзакции.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// Баланс вышел бы за пределы `i64`; транзакция не применена.
+    Overflow,
+    /// После списания баланс отрицательный; транзакция применена.
+    Overdraft { balance: i64 },
+}
+
+/// Проблема с конкретной транзакцией и пользователем.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Issue {
+    pub tx_id: u64,
+    pub user_id: u64,
+    pub kind: IssueKind,
+}
+
+/// Балансы и найденные проблемы.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ledger {
+    options: LedgerOptions,
+    balances: BTreeMap<u64, i64>,
+    issues: Vec<Issue>,
+    applied: usize,
+    /// Позиция загруженного снимка: всё, что не позже, уже учтено.
+    covered: Option<(u64, u64)>,
+    /// Самая поздняя применённая позиция `(TIMESTAMP, TX_ID)`.
+    position: Option<(u64, u64)>,
+    /// Позиции неучтённых `PENDING`: их ещё может закрыть `SUCCESS`.
+    pending: BTreeSet<(u64, u64)>,
+}
+
+impl Ledger {
+    pub fn new(options: LedgerOptions) -> Self {
+        Ledger { options, ..Default::default() }
+    }
+
+    /// Применяет транзакцию, если она проходит по статусу и времени.
+    /// Возвращает `true`, если балансы изменились.
+    pub fn apply(&mut self, tx: &Transaction) -> bool {
+        let counted = match tx.status {
+            TxStatus::Success => true,
+            TxStatus::Pending => self.options.include_pending,
+            TxStatus::Failure => false,
+        };
+        if self.options.as_of.is_some_and(|t| tx.timestamp > t) {
+            return false;
+        }
+        let position = (tx.timestamp, tx.tx_id);
+        if self.covered.is_some_and(|c| position <= c) && !self.pending.contains(&position) {
+            return false;
+        }
+        if !counted {
+            if tx.status == TxStatus::Pending {
+                self.pending.insert(position);
+            } else {
+                self.pending.remove(&position);
+            }
+            return false;
+        }
+
+        let Some(debit) = tx.amount.checked_neg() else {
+            self.issues.push(Issue { tx_id: tx.tx_id, user_id: tx.from_user_id, kind: IssueKind::Overflow });
+            return false;
+        };
+        let moves: &[(u64, i64)] = match tx.tx_type {
+            TxType::Deposit => &[(tx.to_user_id, tx.amount)],
+            TxType::Withdrawal => &[(tx.from_user_id, debit)],
+            TxType::Transfer => &[(tx.from_user_id, debit), (tx.to_user_id, tx.amount)],
+        };
+
+        // Сначала считаем все новые балансы, чтобы при переполнении не применить половину.
+        let mut updates: Vec<(u64, i64, bool)> = Vec::with_capacity(moves.len());
+        for &(user, delta) in moves {
+            if user == EXTERNAL_USER {
+                continue;
+            }
+            let current = updates
+                .iter()
+                .rev()
+                .find(|(u, _, _)| *u == user)
+                .map(|(_, b, _)| *b)
+                .unwrap_or_else(|| self.balance(user));
+            match current.checked_add(delta) {
+                Some(next) => updates.push((user, next, delta < 0)),
+                None => {
+                    self.issues.push(Issue { tx_id: tx.tx_id, user_id: user, kind: IssueKind::Overflow });
+                    return false;
+                }
+            }
+        }
+
+        for (user, balance, debited) in updates {
+            self.balances.insert(user, balance);
+            if debited && balance < 0 {
+                self.issues.push(Issue { tx_id: tx.tx_id, user_id: user, kind: IssueKind::Overdraft { balance } });
+            }
+        }
+        self.pending.remove(&position);
+        self.position = self.position.max(Some(position));
+        self.applied += 1;
+        true
+    }
+
+    /// Применяет поток транзакций по порядку.
+    pub fn apply_all<I>(&mut self, transactions: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<Transaction>>,
+    {
+        for tx in transactions {
+            self.apply(&tx?);
+        }
+        Ok(())
+    }
+
+    /// Баланс пользователя (0, если операций не было).
+    pub fn balance(&self, user_id: u64) -> i64 {
+        self.balances.get(&user_id).copied().unwrap_or(0)
+    }
+
+    /// Все балансы по возрастанию `USER_ID`.
+    pub fn balances(&self) -> &BTreeMap<u64, i64> {
+        &self.balances
+    }
+
+    /// Переполнения и уходы в минус.
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    /// Сколько транзакций изменили балансы.
+    pub fn applied(&self) -> usize {
+        self.applied
+    }
+
+    pub fn options(&self) -> &LedgerOptions {
+        &self.options
+    }
+
+    /// Продолжает расчёт со снимка. `include_pending` берётся из снимка.
+    pub fn from_snapshot(snapshot: Snapshot, as_of: Option<u64>) -> Self {
+        let position = Some((snapshot.as_of, snapshot.last_tx_id));
+        Ledger {
+            options: LedgerOptions { include_pending: snapshot.include_pending, as_of },
+            balances: snapshot.balances,
+            covered: position,
+            position,
+            pending: snapshot.pending,
+            ..Default::default()
+        }
+    }
+
+    /// Снимок текущих балансов и позиции.
+    pub fn snapshot(&self) -> Snapshot {
+        let (as_of, last_tx_id) = self.position.unwrap_or((0, 0));
+        Snapshot {
+            as_of,
+            last_tx_id,
+            include_pending: self.options.include_pending,
+            balances: self.balances.clone(),
+            pending: self.pending.range(..=(as_of, last_tx_id)).copied().collect(),
+        }
+    }
+}
+
+/// Снимок балансов.
+///
+/// Текстовый файл: строки `AS_OF`, `LAST_TX_ID`, `INCLUDE_PENDING`, затем
+/// по строке `USER <id>: <баланс>` и `PENDING: <TIMESTAMP> <TX_ID>`;
+/// `#` — комментарий.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// `TIMESTAMP` последней учтённой транзакции.
+    pub as_of: u64,
+    /// `TX_ID` последней учтённой транзакции.
+    pub last_tx_id: u64,
+    pub include_pending: bool,
+    pub balances: BTreeMap<u64, i64>,
+    /// Неучтённые `PENDING` не позже позиции снимка.
+    pub pending: BTreeSet<(u64, u64)>,
+}
+
+impl Snapshot {
+    pub fn read<R: Read>(reader: R) -> Result<Self> {
+        let mut as_of = None;
+        let mut last_tx_id = None;
+        let mut include_pending = None;
+        let mut balances = BTreeMap::new();
+        let mut pending = BTreeSet::new();
+
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let (key, value) = trimmed
+                .split_once(':')
+                .ok_or_else(|| ParserError::Format(format!("Неверная строка снимка: {}", line)))?;
+            let value = value.trim();
+            match key.trim() {
+                "AS_OF" => as_of = Some(value.parse()?),
+                "LAST_TX_ID" => last_tx_id = Some(value.parse()?),
+                "INCLUDE_PENDING" => include_pending = Some(match value {
+                    "true" => true,
+                    "false" => false,
+                    _ => return Err(ParserError::Format(format!("INCLUDE_PENDING: {}", value))),
+                }),
+                "PENDING" => {
+                    let invalid = || ParserError::Format(format!("PENDING: {}", value));
+                    let (timestamp, tx_id) = value.split_once(' ').ok_or_else(invalid)?;
+                    pending.insert((timestamp.trim().parse()?, tx_id.trim().parse()?));
+                }
+                other => {
+                    let user = other
+                        .strip_prefix("USER ")
+                        .ok_or_else(|| ParserError::Format(format!("Неизвестный ключ снимка: {}", other)))?;
+                    balances.insert(user.trim().parse()?, value.parse()?);
+                }
+            }
+        }
+
+        Ok(Snapshot {
+            as_of: as_of.ok_or_else(|| ParserError::Format("Отсутствует AS_OF".into()))?,
+            last_tx_id: last_tx_id.ok_or_else(|| ParserError::Format("Отсутствует LAST_TX_ID".into()))?,
+            include_pending: include_pending.unwrap_or(false),
+            balances,
+            pending,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "# YPBank balance snapshot")?;
+        writeln!(writer, "AS_OF: {}", self.as_of)?;
+        writeln!(writer, "LAST_TX_ID: {}", self.last_tx_id)?;
+        writeln!(writer, "INCLUDE_PENDING: {}", self.include_pending)?;
+        for (user, balance) in &self.balances {
+            writeln!(writer, "USER {}: {}", user, balance)?;
+        }
+        for (timestamp, tx_id) in &self.pending {
+            writeln!(writer, "PENDING: {} {}", timestamp, tx_id)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Расхождение снимка с пересчётом.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    pub user_id: u64,
+    pub snapshot: i64,
+    pub recomputed: i64,
+}
+
+/// Пересчитывает балансы по полной истории до позиции снимка и сравнивает.
+pub fn verify_snapshot<I>(snapshot: &Snapshot, history: I) -> Result<Vec<Mismatch>>
+where
+    I: IntoIterator<Item = Result<Transaction>>,
+{
+    let limit = (snapshot.as_of, snapshot.last_tx_id);
+    let mut ledger = Ledger::new(LedgerOptions { include_pending: snapshot.include_pending, as_of: None });
+    for tx in history {
+        let tx = tx?;
+        if (tx.timestamp, tx.tx_id) <= limit {
+            ledger.apply(&tx);
+        }
+    }
+
+    let mut users: Vec<u64> = snapshot.balances.keys().chain(ledger.balances.keys()).copied().collect();
+    users.sort_unstable();
+    users.dedup();
+    Ok(users
+        .into_iter()
+        .filter_map(|user_id| {
+            let expected = snapshot.balances.get(&user_id).copied().unwrap_or(0);
+            let actual = ledger.balance(user_id);
+            (expected != actual).then_some(Mismatch { user_id, snapshot: expected, recomputed: actual })
+        })
+        .collect())
+}
+
+/// Пишет балансы: таблица, CSV (`USER_ID,BALANCE`) или JSON.
+pub fn write_balances<W: Write>(mut writer: W, balances: &BTreeMap<u64, i64>, format: ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Text => {
+            writeln!(writer, "{:<20} {:>20}", "USER_ID", "BALANCE")?;
+            for (user, balance) in balances {
+                writeln!(writer, "{:<20} {:>20}", user, balance)?;
+            }
+        }
+        ReportFormat::Csv => {
+            writeln!(writer, "USER_ID,BALANCE")?;
+            for (user, balance) in balances {
+                writeln!(writer, "{},{}", user, balance)?;
+            }
+        }
+        ReportFormat::Json => {
+            let items: Vec<String> = balances
+                .iter()
+                .map(|(user, balance)| format!("{{\"user_id\":{},\"balance\":{}}}", user, balance))
+                .collect();
+            writeln!(writer, "{{\"balances\":[{}]}}", items.join(","))?;
+        }
+    }
+    Ok(())
+}
+
+/// Проблема одной строкой для вывода в stderr.
+pub fn describe_issue(issue: &Issue) -> String {
+    match issue.kind {
+        IssueKind::Overflow => format!(
+            "TX_ID {}: overflow of balance of user {}, transaction skipped",
+            issue.tx_id, issue.user_id
+        ),
+        IssueKind::Overdraft { balance } => format!(
+            "TX_ID {}: user {} overdrawn, balance {}",
+            issue.tx_id, issue.user_id, balance
+        ),
+    }
+}
@@ -6,11 +6,41 @@ pub mod error;
 pub mod text_format;
 pub mod csv_format;
 pub mod bin_format;
+pub mod bin_index;
+pub mod bin_view;
+pub mod compact_format;
+pub mod columnar_format;
+pub mod append;
+pub mod repair;
+pub mod inspect;
+pub mod patch;
+pub mod format;
+pub mod batch;
+pub mod field;
+pub mod compare;
+pub mod reconcile;
+pub mod external_sort;
+pub mod ledger;
+pub mod stats;
+pub mod calendar;
+pub mod aggregate;
+pub mod filter;
+pub mod sort;
+pub mod merge;
+pub mod split;
+pub mod integrity;
+pub mod lifecycle;
+pub mod cli;
+mod json;
+mod crc32;
+mod bin_window;
 
-pub use error::{ParserError, Result};
+pub use error::{ParserError, Result, Truncation};
+pub use format::Format;
+pub use field::{Field, FieldValue};
 
 /// Тип операции.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TxType {
     Deposit,
     Transfer,
@@ -18,15 +48,63 @@ pub enum TxType {
 }
 
 /// Статус транзакции.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TxStatus {
     Success,
     Failure,
     Pending,
 }
 
+impl TxType {
+    /// Имя, как в текстовых форматах.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TxType::Deposit => "DEPOSIT",
+            TxType::Transfer => "TRANSFER",
+            TxType::Withdrawal => "WITHDRAWAL",
+        }
+    }
+}
+
+impl std::str::FromStr for TxType {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "DEPOSIT" => Ok(TxType::Deposit),
+            "TRANSFER" => Ok(TxType::Transfer),
+            "WITHDRAWAL" => Ok(TxType::Withdrawal),
+            _ => Err(ParserError::Format(format!("Неизвестный тип: {}", s))),
+        }
+    }
+}
+
+impl TxStatus {
+    /// Имя, как в текстовых форматах.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TxStatus::Success => "SUCCESS",
+            TxStatus::Failure => "FAILURE",
+            TxStatus::Pending => "PENDING",
+        }
+    }
+}
+
+impl std::str::FromStr for TxStatus {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "SUCCESS" => Ok(TxStatus::Success),
+            "FAILURE" => Ok(TxStatus::Failure),
+            "PENDING" => Ok(TxStatus::Pending),
+            _ => Err(ParserError::Format(format!("Неизвестный статус: {}", s))),
+        }
+    }
+}
+
 /// Одна транзакция (операция).
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Transaction {
     pub tx_id: u64,
     pub tx_type: TxType,
@@ -116,6 +194,32 @@ mod tests {
         Ok(())
     }
 
+    /// Временный каталог теста; удаляется при выходе из теста, даже если тест упал.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Result<Self> {
+            let path = std::env::temp_dir().join(format!("ypbank_{}_{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path)?;
+            Ok(Self(path))
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = std::path::Path;
+
+        fn deref(&self) -> &std::path::Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
     /// CSV: записали — прочитали, данные совпадают.
     #[test]
     fn test_csv_roundtrip() -> Result<()> {
@@ -187,4 +291,1053 @@ mod tests {
         assert_eq!(txs, from_text);
         Ok(())
     }
+
+    /// Шаблон имени: `*` и `?` сопоставляются с именами файлов.
+    #[test]
+    fn test_batch_wildcard_match() {
+        assert!(batch::wildcard_match("*.csv", "day_01.csv"));
+        assert!(batch::wildcard_match("day_??.csv", "day_01.csv"));
+        assert!(!batch::wildcard_match("day_?.csv", "day_01.csv"));
+        assert!(!batch::wildcard_match("*.csv", "day_01.bin"));
+    }
+
+    /// Пакет: битый файл не мешает конвертации остальных.
+    #[test]
+    fn test_batch_continues_after_failure() -> Result<()> {
+        let dir = TempDir::new("batch")?;
+        let out_dir = dir.join("out");
+        std::fs::create_dir_all(&out_dir)?;
+        let mut buf = Vec::new();
+        Transaction::to_csv(&mut buf, &create_test_txs())?;
+        std::fs::write(dir.join("good.csv"), &buf)?;
+        std::fs::write(dir.join("bad.csv"), "TX_ID\nnot,a,record")?;
+
+        let inputs = batch::collect_inputs(&[dir.to_string_lossy().into_owned()], Format::Csv)?;
+        let jobs = batch::plan(&inputs, &out_dir, Format::Bin)?;
+        let outcomes = batch::run(jobs, Format::Csv, Format::Bin, 2, &batch::ConvertOptions::default());
+
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes[0].result.is_err());
+        assert_eq!(outcomes[1].result.as_ref().ok(), Some(&1));
+        let converted = Transaction::from_bin(std::fs::File::open(out_dir.join("good.bin"))?)?;
+        assert_eq!(converted, create_test_txs());
+        assert!(!out_dir.join("bad.bin").exists());
+        assert!(!out_dir.join("bad.bin.tmp").exists());
+
+        let dated = [dir.join("tx.2024-01-01.csv"), dir.join("tx.2024-01-02.csv")];
+        let jobs = batch::plan(&dated, &out_dir, Format::Bin)?;
+        assert_eq!(jobs[0].output, out_dir.join("tx.2024-01-01.bin"));
+        assert_eq!(jobs[1].output, out_dir.join("tx.2024-01-02.bin"));
+        Ok(())
+    }
+
+    /// Сравнение по TX_ID: вставленная запись не сдвигает остальные.
+    #[test]
+    fn test_compare_by_key_ignores_order() {
+        let base = create_test_txs().remove(0);
+        let make = |tx_id, amount| Transaction { tx_id, amount, ..base.clone() };
+        let first = vec![make(1, 10), make(2, 20), make(3, 30)];
+        let second = vec![make(3, 30), make(9, 90), make(1, 10), make(2, 25)];
+
+        let cmp = compare::compare_by_key(first, second, &[Field::TxId], &Default::default());
+        assert_eq!(cmp.matched, 2);
+        assert!(cmp.only_in_first.is_empty());
+        assert_eq!(cmp.only_in_second, vec![make(9, 90)]);
+        assert_eq!(cmp.changed.len(), 1);
+        assert_eq!((&cmp.changed[0].first, &cmp.changed[0].second), (&make(2, 20), &make(2, 25)));
+    }
+
+    /// Имена полей не зависят от регистра.
+    #[test]
+    fn test_field_parse_list() -> Result<()> {
+        assert_eq!(Field::parse_list("tx_id, TIMESTAMP")?, vec![Field::TxId, Field::Timestamp]);
+        assert!(Field::parse_list("tx_id,unknown").is_err());
+        Ok(())
+    }
+
+    /// Различия выводятся по полям: `STATUS: PENDING -> SUCCESS`.
+    #[test]
+    fn test_compare_field_diff() {
+        let first = create_test_txs().remove(0);
+        let second = Transaction { status: TxStatus::Pending, amount: 1, ..first.clone() };
+        let diffs: Vec<String> = compare::diff_fields(&second, &first)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(diffs, vec!["AMOUNT: 1 -> 50000", "STATUS: PENDING -> SUCCESS"]);
+    }
+
+    /// JSON-отчёт содержит сводку и изменённые поля.
+    #[test]
+    fn test_compare_json_report() -> Result<()> {
+        let first = create_test_txs();
+        let mut second = first.clone();
+        second[0].description = "Quote \" inside".to_string();
+        let cmp = compare::compare_by_key(first, second, &[Field::TxId], &Default::default());
+        let report = compare::Report {
+            comparison: &cmp,
+            key: &[Field::TxId],
+            first_name: "a.csv",
+            second_name: "b.csv",
+        };
+        let mut out = Vec::new();
+        report.write(&mut out, compare::ReportFormat::Json)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.contains("\"summary\":{\"identical\":0,\"only_in_first\":0,\"only_in_second\":0,\"changed\":1}"));
+        assert!(out.contains("{\"field\":\"DESCRIPTION\",\"first\":\"Initial funding\",\"second\":\"Quote \\\" inside\"}"));
+        Ok(())
+    }
+
+    /// Допуски и нормализация убирают ложные расхождения.
+    #[test]
+    fn test_compare_options_tolerances() -> Result<()> {
+        let first = create_test_txs().remove(0);
+        let second = Transaction {
+            timestamp: first.timestamp + 999,
+            amount: first.amount - 5,
+            status: TxStatus::Pending,
+            description: "  INITIAL\u{A0}  funding ".to_string(),
+            ..first.clone()
+        };
+        let options = compare::CompareOptions {
+            ignore: vec![Field::Status],
+            timestamp_tolerance: 1000,
+            amount_tolerance: 5,
+            normalize: compare::Normalization::parse("trim,case,accent-fold")?,
+        };
+        assert!(compare::diff_fields_with(&first, &second, &options).is_empty());
+        let strict = compare::CompareOptions { timestamp_tolerance: 998, ..options };
+        assert_eq!(compare::diff_fields_with(&first, &second, &strict)[0].field, Field::Timestamp);
+        Ok(())
+    }
+
+    /// Буква с комбинируемым знаком из таблицы равна составной.
+    #[test]
+    fn test_accent_fold() {
+        let norm = compare::Normalization { accent_fold: true, ..Default::default() };
+        assert_eq!(norm.apply("Е\u{308}лка и\u{306}"), "Ёлка й");
+    }
+
+    /// Сверка без общего TX_ID: пара по пользователям и времени, разбиение 1 → 2.
+    #[test]
+    fn test_reconcile_pairs_and_splits() {
+        let base = create_test_txs().remove(0);
+        let make = |tx_id, amount, timestamp| Transaction { tx_id, amount, timestamp, ..base.clone() };
+        let first = vec![make(1, 100, 10_000), make(2, 300, 50_000), make(3, 999, 90_000)];
+        let second = vec![make(71, 100, 12_000), make(72, 200, 50_500), make(73, 100, 51_000)];
+
+        let result = reconcile::reconcile(&first, &second, &reconcile::ReconcileOptions::default());
+        assert_eq!(result.matched.len(), 2);
+        assert_eq!(result.matched[0].second, vec![make(71, 100, 12_000)]);
+        let split_ids: Vec<u64> = result.matched[1].second.iter().map(|t| t.tx_id).collect();
+        assert_eq!(split_ids, vec![72, 73]);
+        assert_eq!(result.unmatched_first, vec![make(3, 999, 90_000)]);
+        assert!(result.unmatched_second.is_empty());
+    }
+
+    /// Два одинаково подходящих кандидата — неоднозначный случай, а не случайный выбор.
+    #[test]
+    fn test_reconcile_ambiguous() {
+        let base = create_test_txs().remove(0);
+        let make = |tx_id, timestamp| Transaction { tx_id, timestamp, ..base.clone() };
+        let first = vec![make(1, 10_000)];
+        let second = vec![make(71, 9_000), make(72, 11_000)];
+
+        let result = reconcile::reconcile(&first, &second, &reconcile::ReconcileOptions::default());
+        assert!(result.matched.is_empty());
+        assert_eq!(result.ambiguous.len(), 1);
+        assert_eq!(result.ambiguous[0].candidates.len(), 2);
+        // Кандидаты не дублируются среди несопоставленных.
+        assert!(result.unmatched_second.is_empty());
+
+        let huge = reconcile::ReconcileOptions { amount_tolerance: u64::MAX, max_split: usize::MAX, ..Default::default() };
+        assert_eq!(reconcile::reconcile(&first, &second, &huge).ambiguous.len(), 1);
+    }
+
+    /// Внешняя сортировка с крошечным бюджетом: несколько временных файлов, порядок устойчивый.
+    #[test]
+    fn test_external_sort_spills_and_merges() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let input: Vec<Transaction> = (0..50u64)
+            .map(|i| Transaction { tx_id: (i * 37) % 50, amount: i as i64, ..base.clone() })
+            .chain((0..3).map(|i| Transaction { tx_id: 7, amount: 100 + i, ..base.clone() }))
+            .collect();
+        let options = external_sort::SortOptions { memory_budget: 1024, ..Default::default() };
+
+        let sorted: Vec<Transaction> =
+            external_sort::sort(input.clone().into_iter().map(Ok), &[Field::TxId], &options)?
+                .collect::<Result<_>>()?;
+        let mut expected = input;
+        expected.sort_by_key(|tx| tx.tx_id);
+        assert_eq!(sorted, expected);
+        Ok(())
+    }
+
+    /// Потоковое сравнение отсортированных наборов даёт тот же итог, что и по ключу в памяти.
+    #[test]
+    fn test_compare_sorted_matches_in_memory() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let make = |tx_id, amount| Transaction { tx_id, amount, ..base.clone() };
+        let first = vec![make(3, 30), make(1, 10), make(2, 20)];
+        let second = vec![make(4, 40), make(2, 25), make(1, 10)];
+        let options = external_sort::SortOptions { memory_budget: 1, ..Default::default() };
+        let key = [Field::TxId];
+
+        let streamed = compare::compare_sorted(
+            external_sort::sort(first.clone().into_iter().map(Ok), &key, &options)?,
+            external_sort::sort(second.clone().into_iter().map(Ok), &key, &options)?,
+            &key,
+            &Default::default(),
+        )?;
+        let in_memory = compare::compare_by_key(first.clone(), second.clone(), &key, &Default::default());
+        assert_eq!(streamed, in_memory);
+
+        let mut out = Vec::new();
+        let mut report = compare::StreamReport::new(&mut out, compare::ReportFormat::Text, &key, "a", "b")?;
+        let summary = compare::compare_sorted_each(
+            external_sort::sort(first.into_iter().map(Ok), &key, &options)?,
+            external_sort::sort(second.into_iter().map(Ok), &key, &options)?,
+            &key,
+            &Default::default(),
+            |diff| report.difference(&diff),
+        )?;
+        report.finish(&summary)?;
+        assert_eq!(summary, in_memory.summary());
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("Transaction TX_ID=2 does not match:"));
+        assert!(text.ends_with("Summary: 1 identical, 1 only in first file, 1 only in second file, 1 changed.\n"));
+        Ok(())
+    }
+
+    /// Размеры с суффиксами для --memory-budget.
+    #[test]
+    fn test_parse_size() -> Result<()> {
+        assert_eq!(external_sort::parse_size("512")?, 512);
+        assert_eq!(external_sort::parse_size("64M")?, 64 << 20);
+        assert!(external_sort::parse_size("12X").is_err());
+        Ok(())
+    }
+
+    /// Балансы: FAILURE и PENDING по умолчанию не учитываются, уход в минус отмечается.
+    #[test]
+    fn test_ledger_balances_and_overdraft() {
+        let base = create_test_txs().remove(0);
+        let txs = [
+            Transaction { tx_id: 1, tx_type: TxType::Deposit, from_user_id: 0, to_user_id: 1, amount: 100, ..base.clone() },
+            Transaction { tx_id: 2, tx_type: TxType::Transfer, from_user_id: 1, to_user_id: 2, amount: 30, ..base.clone() },
+            Transaction { tx_id: 3, tx_type: TxType::Transfer, from_user_id: 1, to_user_id: 2, amount: 500, status: TxStatus::Failure, ..base.clone() },
+            Transaction { tx_id: 4, tx_type: TxType::Withdrawal, from_user_id: 2, to_user_id: 0, amount: 50, ..base.clone() },
+            Transaction { tx_id: 5, tx_type: TxType::Withdrawal, from_user_id: 1, to_user_id: 0, amount: 70, status: TxStatus::Pending, ..base.clone() },
+        ];
+
+        let mut ledger = ledger::Ledger::new(Default::default());
+        txs.iter().for_each(|tx| { ledger.apply(tx); });
+        assert_eq!((ledger.balance(1), ledger.balance(2)), (70, -20));
+        assert_eq!(ledger.issues(), &[ledger::Issue { tx_id: 4, user_id: 2, kind: ledger::IssueKind::Overdraft { balance: -20 } }]);
+
+        let mut with_pending = ledger::Ledger::new(ledger::LedgerOptions { include_pending: true, as_of: None });
+        txs.iter().for_each(|tx| { with_pending.apply(tx); });
+        assert_eq!(with_pending.balance(1), 0);
+    }
+
+    /// Переполнение не применяет транзакцию частично.
+    #[test]
+    fn test_ledger_overflow_is_atomic() {
+        let base = create_test_txs().remove(0);
+        let mut ledger = ledger::Ledger::new(Default::default());
+        ledger.apply(&Transaction { tx_id: 1, to_user_id: 2, amount: i64::MAX, ..base.clone() });
+        let transfer = Transaction { tx_id: 2, tx_type: TxType::Transfer, from_user_id: 1, to_user_id: 2, amount: 1, ..base };
+        assert!(!ledger.apply(&transfer));
+        assert_eq!((ledger.balance(1), ledger.balance(2)), (0, i64::MAX));
+        assert_eq!(ledger.issues()[0].kind, ledger::IssueKind::Overflow);
+    }
+
+    /// Снимок + новые транзакции = полный пересчёт; уже учтённые не применяются повторно.
+    #[test]
+    fn test_ledger_snapshot_incremental() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let deposit = |tx_id, timestamp, amount| Transaction { tx_id, timestamp, amount, ..base.clone() };
+        let day1 = vec![deposit(1, 1_000, 10), deposit(2, 2_000, 20)];
+        let day2 = vec![deposit(2, 2_000, 20), deposit(3, 3_000, 30)];
+
+        let mut ledger = ledger::Ledger::new(Default::default());
+        ledger.apply_all(day1.clone().into_iter().map(Ok))?;
+        let mut buf = Vec::new();
+        ledger.snapshot().write(&mut buf)?;
+        let snapshot = ledger::Snapshot::read(Cursor::new(&buf))?;
+        assert_eq!((snapshot.as_of, snapshot.last_tx_id), (2_000, 2));
+
+        let mut resumed = ledger::Ledger::from_snapshot(snapshot, None);
+        resumed.apply_all(day2.clone().into_iter().map(Ok))?;
+        assert_eq!(resumed.balance(501), 60);
+
+        let history = day1.into_iter().chain(day2.into_iter().skip(1)).map(Ok);
+        assert!(ledger::verify_snapshot(&resumed.snapshot(), history)?.is_empty());
+        Ok(())
+    }
+
+    /// PENDING в снимке не закрывает позицию: SUCCESS из следующего файла учитывается.
+    #[test]
+    fn test_ledger_snapshot_pending_then_success() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let deposit = |tx_id, timestamp, status| Transaction { tx_id, timestamp, amount: 10, status, ..base.clone() };
+        let day1 = vec![deposit(1, 1_000, TxStatus::Success), deposit(2, 2_000, TxStatus::Pending)];
+        let day2 = vec![deposit(2, 2_000, TxStatus::Success), deposit(3, 3_000, TxStatus::Success)];
+
+        let mut ledger = ledger::Ledger::new(Default::default());
+        ledger.apply_all(day1.clone().into_iter().map(Ok))?;
+        let mut buf = Vec::new();
+        ledger.snapshot().write(&mut buf)?;
+        let mut resumed = ledger::Ledger::from_snapshot(ledger::Snapshot::read(Cursor::new(&buf))?, None);
+        resumed.apply_all(day2.clone().into_iter().map(Ok))?;
+
+        let mut full = ledger::Ledger::new(Default::default());
+        full.apply_all(day1.into_iter().chain(day2).map(Ok))?;
+        assert_eq!(resumed.balance(501), 30);
+        assert_eq!(resumed.snapshot(), full.snapshot());
+        Ok(())
+    }
+
+    /// Проверка снимка находит подправленный баланс.
+    #[test]
+    fn test_ledger_snapshot_verify_mismatch() -> Result<()> {
+        let txs = create_test_txs();
+        let mut ledger = ledger::Ledger::new(Default::default());
+        ledger.apply_all(txs.clone().into_iter().map(Ok))?;
+        let mut snapshot = ledger.snapshot();
+        snapshot.balances.insert(501, 1);
+        let mismatches = ledger::verify_snapshot(&snapshot, txs.into_iter().map(Ok))?;
+        assert_eq!(mismatches, vec![ledger::Mismatch { user_id: 501, snapshot: 1, recomputed: 50000 }]);
+        Ok(())
+    }
+
+    /// Статистика: разбивка по типам, медиана, диапазон времени, лидеры.
+    #[test]
+    fn test_stats_summary() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let txs = [
+            Transaction { tx_id: 1, amount: 10, timestamp: 300, to_user_id: 7, ..base.clone() },
+            Transaction { tx_id: 2, amount: 40, timestamp: 100, tx_type: TxType::Transfer, from_user_id: 7, to_user_id: 8, ..base.clone() },
+            Transaction { tx_id: 3, amount: 30, timestamp: 200, status: TxStatus::Failure, to_user_id: 8, ..base.clone() },
+            Transaction { tx_id: 4, amount: 20, timestamp: 250, to_user_id: 9, ..base },
+        ];
+        let mut plain = stats::Stats::new();
+        plain.add_all(txs.iter().cloned().map(Ok))?;
+        assert_eq!((plain.min_amount(), plain.median_amount()), (Some(10), None));
+        let mut stats = stats::Stats::with_median();
+        stats.add_all(txs.into_iter().map(Ok))?;
+
+        assert_eq!(stats.total(), stats::Totals { count: 4, sum: 100 });
+        assert_eq!(stats.by_type()[&TxType::Deposit], stats::Totals { count: 3, sum: 60 });
+        assert_eq!(stats.by_status()[&TxStatus::Failure].count, 1);
+        assert_eq!((stats.min_amount(), stats.max_amount()), (Some(10), Some(40)));
+        assert_eq!((stats.mean_amount(), stats.median_amount()), (Some(25), Some(25)));
+        assert_eq!(stats.time_range(), Some((100, 300)));
+        assert_eq!(stats.distinct_users(), 3);
+        assert_eq!(stats.top_receivers(1)[0].user_id, 8);
+        Ok(())
+    }
+
+    /// Даты: дни от эпохи туда и обратно, начало недели, сдвиг от UTC.
+    #[test]
+    fn test_calendar_dates() -> Result<()> {
+        let date: calendar::Date = "2024-02-29".parse()?;
+        assert_eq!(calendar::Date::from_days(date.to_days()), date);
+        assert_eq!(date.start_of_week().to_string(), "2024-02-26");
+        assert!("2023-02-29".parse::<calendar::Date>().is_err());
+        assert_eq!(calendar::parse_utc_offset("+03:00")?, aggregate::MOSCOW_OFFSET_MINUTES);
+        // 2021-09-30 22:30 UTC — уже 1 октября в Москве.
+        assert_eq!(calendar::Date::from_timestamp(1_633_041_000_000, 180).to_string(), "2021-10-01");
+        assert!(calendar::parse_utc_offset("+03:-30").is_err());
+        assert!(calendar::parse_utc_offset("+03:75").is_err());
+        assert!(calendar::Date::from_timestamp(u64::MAX, 840).year > 0);
+        Ok(())
+    }
+
+    /// Помесячные итоги по московскому времени с группировкой по статусу.
+    #[test]
+    fn test_aggregate_monthly_by_status() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let txs = [
+            Transaction { amount: 10, timestamp: 1_633_041_000_000, ..base.clone() },
+            Transaction { amount: 20, timestamp: 1_633_000_000_000, tx_type: TxType::Transfer, ..base.clone() },
+            Transaction { amount: 5, timestamp: 1_633_000_000_000, status: TxStatus::Failure, ..base },
+        ];
+        let options = aggregate::AggregateOptions {
+            period: aggregate::Period::Month,
+            group_by: aggregate::GroupBy::Status,
+            utc_offset_minutes: aggregate::MOSCOW_OFFSET_MINUTES,
+            all_statuses: true,
+        };
+        let mut agg = aggregate::Aggregation::new(options);
+        agg.add_all(txs.clone().into_iter().map(Ok))?;
+        let mut out = Vec::new();
+        agg.write(&mut out, compare::ReportFormat::Csv)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "PERIOD_START,GROUP,DEPOSIT_COUNT,DEPOSIT_SUM,WITHDRAWAL_COUNT,WITHDRAWAL_SUM,TRANSFER_COUNT,TRANSFER_SUM\n\
+             2021-09-01,SUCCESS,0,0,0,0,1,20\n\
+             2021-09-01,FAILURE,1,5,0,0,0,0\n\
+             2021-10-01,SUCCESS,1,10,0,0,0,0\n"
+        );
+
+        // По умолчанию учитываются только SUCCESS.
+        let mut agg = aggregate::Aggregation::new(aggregate::AggregateOptions { all_statuses: false, ..options });
+        agg.add_all(txs.into_iter().map(Ok))?;
+        assert_eq!(agg.rows().len(), 2);
+        Ok(())
+    }
+
+    /// Фильтр: сравнения, логика, диапазон дат и подстрока в описании.
+    #[test]
+    fn test_filter_expressions() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let march = Transaction {
+            tx_type: TxType::Transfer,
+            status: TxStatus::Failure,
+            from_user_id: 501,
+            amount: 15_000,
+            timestamp: 1_710_460_800_000, // 2024-03-15
+            description: "Rent for March".into(),
+            ..base.clone()
+        };
+        let expr = "status = failure AND TX_TYPE = TRANSFER AND AMOUNT > 10000 AND FROM_USER_ID = 501 \
+                    AND TIMESTAMP BETWEEN 2024-03-01 AND 2024-03-31";
+        let filter: filter::Filter = expr.parse()?;
+        assert!(filter.matches(&march));
+        assert!(!filter.matches(&Transaction { amount: 10_000, ..march.clone() }));
+        assert!(!filter.matches(&Transaction { timestamp: 1_711_929_600_000, ..march.clone() })); // 2024-04-01
+
+        let filter = filter::Filter::parse("NOT (STATUS IN (SUCCESS, PENDING)) OR DESCRIPTION CONTAINS \"bonus\"")?;
+        assert!(filter.matches(&march));
+        assert!(!filter.matches(&base));
+
+        let filter = filter::Filter::parse("TIMESTAMP <= 2024-03-15 AND DESCRIPTION != \"x\"")?;
+        assert!(filter.matches(&Transaction { timestamp: 1_710_460_800_000 + 86_399_999, ..march.clone() }));
+        assert!(!filter.matches(&Transaction { timestamp: 1_710_460_800_000 + 86_400_000, ..march.clone() }));
+
+        let kept: Vec<Transaction> = filter::Filter::parse("AMOUNT < 20000")?
+            .apply(vec![Ok(base), Ok(march.clone())])
+            .collect::<Result<_>>()?;
+        assert_eq!(kept, vec![march]);
+
+        for bad in ["AMOUNT >", "BALANCE = 1", "STATUS = DONE", "AMOUNT CONTAINS \"1\"", "(TX_ID = 1", "TX_ID = 1 2", "TIMESTAMP >= 2147483647-01-01T00:01"] {
+            assert!(filter::Filter::parse(bad).is_err(), "{}", bad);
+        }
+        Ok(())
+    }
+
+    /// Дубликаты по TX_ID: keep-first/keep-last сохраняют порядок, error — ошибка.
+    #[test]
+    fn test_dedup_policies() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let tx = |tx_id, amount| Transaction { tx_id, amount, ..base.clone() };
+        let input = vec![tx(3, 1), tx(1, 2), tx(3, 3), tx(2, 4)];
+        let amounts = |policy| -> Result<Vec<i64>> {
+            let options = sort::DedupOptions { key: sort::DedupKey::TxId, policy };
+            Ok(sort::dedup(input.clone(), &options)?.iter().map(|t| t.amount).collect())
+        };
+        assert_eq!(amounts(sort::DuplicatePolicy::KeepFirst)?, [1, 2, 4]);
+        assert_eq!(amounts(sort::DuplicatePolicy::KeepLast)?, [2, 3, 4]);
+        assert!(amounts(sort::DuplicatePolicy::Error).is_err());
+
+        let options = sort::DedupOptions { key: sort::DedupKey::Record, ..Default::default() };
+        assert_eq!(sort::dedup(vec![tx(1, 1), tx(1, 2), tx(1, 1)], &options)?, [tx(1, 1), tx(1, 2)]);
+        Ok(())
+    }
+
+    /// Внешний путь даёт тот же результат, что и сортировка в памяти.
+    #[test]
+    fn test_sort_dedup_external_matches_memory() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let input: Vec<Transaction> = (0..200u64)
+            .map(|i| Transaction { tx_id: i % 70, amount: i as i64, timestamp: (i * 7919) % 101, ..base.clone() })
+            .collect();
+        let key = [Field::Timestamp, Field::TxId];
+        let dedup = Some(sort::DedupOptions { policy: sort::DuplicatePolicy::KeepLast, ..Default::default() });
+        let in_memory: Vec<Transaction> =
+            sort::sort_dedup(input.clone().into_iter().map(Ok), &key, dedup, None)?.collect::<Result<_>>()?;
+        let options = external_sort::SortOptions { memory_budget: 1024, ..Default::default() };
+        let external: Vec<Transaction> =
+            sort::sort_dedup(input.clone().into_iter().map(Ok), &key, dedup, Some(&options))?.collect::<Result<_>>()?;
+        assert_eq!(in_memory.len(), 70);
+        assert!(in_memory.iter().all(|t| t.amount >= 130));
+        assert_eq!(in_memory, external);
+
+        // Без --by и с равными значениями --by порядок тоже совпадает.
+        for key in [&[][..], &[Field::Timestamp][..]] {
+            for dedup in [None, dedup, Some(sort::DedupOptions { key: sort::DedupKey::Record, ..Default::default() })] {
+                let in_memory: Vec<Transaction> =
+                    sort::sort_dedup(input.clone().into_iter().map(Ok), key, dedup, None)?.collect::<Result<_>>()?;
+                let external: Vec<Transaction> =
+                    sort::sort_dedup(input.clone().into_iter().map(Ok), key, dedup, Some(&options))?.collect::<Result<_>>()?;
+                assert_eq!(in_memory, external);
+            }
+        }
+        Ok(())
+    }
+
+    /// Слияние: точные повторы отбрасываются, конфликты по TX_ID обнаруживаются.
+    #[test]
+    fn test_merge_duplicates_and_conflicts() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let tx = |tx_id, amount| Transaction { tx_id, amount, ..base.clone() };
+        let inputs = || -> Vec<format::Transactions<'static>> {
+            vec![
+                Box::new(vec![Ok(tx(1, 10)), Ok(tx(2, 20))].into_iter()),
+                Box::new(vec![Ok(tx(2, 20)), Ok(tx(1, 11)), Ok(tx(3, 30))].into_iter()),
+            ]
+        };
+
+        let mut merge = merge::Merge::new(inputs(), merge::ConflictPolicy::KeepFirst);
+        let merged: Vec<Transaction> = merge.by_ref().collect::<Result<_>>()?;
+        assert_eq!(merged, [tx(1, 10), tx(2, 20), tx(3, 30)]);
+        assert_eq!(merge.duplicates(), 1);
+        assert_eq!(merge.conflicts(), [merge::Conflict { tx_id: 1, first_input: 0, second_input: 1 }]);
+
+        let strict = merge::Merge::new(inputs(), merge::ConflictPolicy::Error);
+        assert!(strict.collect::<Result<Vec<_>>>().is_err());
+
+        let (path, fmt) = format::parse_input("bin:day.dat", None)?;
+        assert_eq!((path.to_str(), fmt), (Some("day.dat"), Format::Bin));
+        assert_eq!(format::parse_input("day.csv", Some(Format::Bin))?.1, Format::Csv);
+        assert!(format::parse_input("day.dat", None).is_err());
+        Ok(())
+    }
+
+    /// Разбиение по статусу с лимитом записей: части читаются обратно целиком,
+    /// в том числе после закрытия и повторного открытия файлов.
+    #[test]
+    fn test_split_by_key_and_limit() -> Result<()> {
+        let dir = TempDir::new("split")?;
+        let base = create_test_txs().remove(0);
+        let input: Vec<Transaction> = (0..10u64)
+            .map(|i| Transaction {
+                tx_id: i,
+                status: if i % 3 == 0 { TxStatus::Failure } else { TxStatus::Success },
+                ..base.clone()
+            })
+            .collect();
+        let options = split::SplitOptions {
+            key: split::SplitKey::Status,
+            max_records: Some(3),
+            format: Format::Text,
+            max_open: 1,
+            ..Default::default()
+        };
+        let files = split::split(input.clone().into_iter().map(Ok), &dir, &options)?;
+        let names: Vec<String> = files.iter().map(|f| f.path.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["FAILURE_1.txt", "SUCCESS_1.txt", "SUCCESS_2.txt", "FAILURE_2.txt"]);
+
+        let mut back = Vec::new();
+        for file in &files {
+            let part = Format::Text.read(std::fs::File::open(&file.path)?)?;
+            assert_eq!(part.len() as u64, file.records);
+            assert_eq!(std::fs::metadata(&file.path)?.len(), file.bytes);
+            back.extend(part);
+        }
+        back.sort_by_key(|t| t.tx_id);
+        assert_eq!(back, input);
+
+        let bad = split::SplitOptions { max_records: Some(3), template: Some("{key}.csv".into()), ..options };
+        assert!(split::split(std::iter::empty(), &dir, &bad).is_err());
+        let columnar = split::SplitOptions { format: Format::Columnar, ..bad };
+        assert!(split::split(std::iter::empty(), &dir, &columnar).is_err());
+        Ok(())
+    }
+
+    /// Целостность: позиции повторов, убывание полей и пропуски TX_ID.
+    #[test]
+    fn test_integrity_check() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let tx = |tx_id, timestamp| Transaction { tx_id, timestamp, ..base.clone() };
+        let input = vec![tx(1, 100), tx(2, 200), tx(5, 150), tx(2, 300), tx(4, 400)];
+        let report = integrity::check(input.clone().into_iter().map(Ok), &Default::default())?;
+        assert_eq!(report.records, 5);
+        assert_eq!(report.duplicates, [integrity::Duplicate { tx_id: 2, positions: vec![2, 4] }]);
+        let violations: Vec<(Field, usize)> = report.order_violations.iter().map(|v| (v.field, v.position)).collect();
+        assert_eq!(violations, [(Field::Timestamp, 3), (Field::TxId, 4)]);
+        assert_eq!(report.gaps, [integrity::Gap { from: 3, to: 3 }]);
+
+        let mut out = Vec::new();
+        report.write(&mut out, compare::ReportFormat::Text)?;
+        assert!(String::from_utf8(out)?.starts_with("DUPLICATE TX_ID 2 at records 2, 4\n"));
+
+        let options = integrity::IntegrityOptions::default().skip("tx-order,gaps")?;
+        let report = integrity::check(input.into_iter().map(Ok), &options)?;
+        assert_eq!(report.order_violations.len(), 1);
+        assert!(report.gaps.is_empty());
+        Ok(())
+    }
+
+    /// YPBankBin v2: заголовок, CRC записей и трейлер; v1 читается тем же читателем.
+    #[test]
+    fn test_bin_v2_roundtrip_and_checks() -> Result<()> {
+        let txs = create_test_txs();
+        let header = bin_format::FileHeader { created_at: 1_700_000_000_000, ..bin_format::FileHeader::new("test") };
+        let mut writer = bin_format::Writer::v2(Vec::new(), header.clone());
+        for tx in &txs {
+            writer.write(tx)?;
+        }
+        let data = writer.finish()?;
+        assert_eq!(&data[..4], b"YPBF");
+
+        let mut reader = bin_format::Reader::new(Cursor::new(&data));
+        let back: Vec<Transaction> = reader.by_ref().collect::<Result<_>>()?;
+        assert_eq!(back, txs);
+        assert_eq!(reader.header(), Some(&header));
+        assert_eq!(reader.version(), bin_format::Version::V2);
+        assert_eq!(reader.trailer(), Some(&bin_format::Trailer { record_count: 1, amount_sum: 50000 }));
+
+        // Испорченный байт описания ловится CRC записи.
+        let mut corrupted = data.clone();
+        let pos = data.windows(7).position(|w| w == b"Initial").unwrap();
+        corrupted[pos] = b'X';
+        assert!(bin_format::from_read(Cursor::new(corrupted)).is_err());
+        // CRC проверяется раньше разбора: неверный TX_TYPE — тоже ошибка CRC.
+        let mut corrupted = data.clone();
+        let record = data.windows(4).position(|w| w == bin_format::MAGIC).unwrap();
+        corrupted[record + 16] = 9;
+        let err = bin_format::from_read(Cursor::new(&corrupted)).unwrap_err();
+        assert!(err.to_string().contains("Контрольная сумма"), "{}", err);
+        let err = bin_view::SliceReader::new(&corrupted).find_map(|r| r.err()).unwrap();
+        assert!(err.to_string().contains("Контрольная сумма"), "{}", err);
+
+        // Без трейлера файл считается оборванным.
+        let truncated = &data[..data.len() - 32];
+        assert!(bin_format::from_read(Cursor::new(truncated)).is_err());
+
+        // Пустой файл v2 — заголовок и трейлер.
+        let v2 = bin_format::WriteOptions { version: bin_format::Version::V2, index: false };
+        let empty = bin_format::Writer::with_options(Vec::new(), &v2)?.finish()?;
+        assert!(bin_format::from_read(Cursor::new(empty))?.is_empty());
+
+        let mut v1 = Vec::new();
+        bin_format::write_to(&mut v1, &txs)?;
+        let mut reader = bin_format::Reader::new(Cursor::new(&v1));
+        assert_eq!(reader.by_ref().collect::<Result<Vec<_>>>()?, txs);
+        assert_eq!(reader.version(), bin_format::Version::V1);
+        Ok(())
+    }
+
+    /// Индекс: поиск по TX_ID и по времени, поток читает индексный файл и проверяет индекс, без индекса — просмотр.
+    #[test]
+    fn test_bin_index_get_and_range() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        // 600 записей — три блока; TX_ID идут не по порядку.
+        let txs: Vec<Transaction> = (0..600u64)
+            .map(|i| Transaction { tx_id: (i * 7) % 600 + 1, timestamp: 1_000 + i * 10, ..base.clone() })
+            .collect();
+        let options = bin_format::WriteOptions { version: bin_format::Version::V2, index: true };
+        let mut writer = bin_format::Writer::with_options(Vec::new(), &options)?;
+        for tx in &txs {
+            writer.write(tx)?;
+        }
+        let indexed = writer.finish()?;
+        assert_eq!(bin_format::from_read(Cursor::new(&indexed))?, txs);
+        // Поток индекс не хранит, но проверяет его CRC и футер.
+        let mut damaged = indexed.clone();
+        let at = damaged.len() - 40;
+        damaged[at] ^= 0xFF;
+        assert!(bin_format::from_read(Cursor::new(&damaged)).is_err());
+        assert!(bin_view::SliceReader::new(&damaged).collect::<Result<Vec<_>>>().is_err());
+        assert!(bin_format::from_read(Cursor::new(&indexed[..indexed.len() - 1])).is_err());
+
+        let mut reader = bin_index::IndexedReader::open(Cursor::new(&indexed))?;
+        assert_eq!(reader.index().map(|i| i.blocks.len()), Some(3));
+        assert_eq!(reader.get(txs[450].tx_id)?, Some(txs[450].clone()));
+        assert_eq!(reader.get(601)?, None);
+        let range: Vec<Transaction> = reader.range(3_000, 3_090).collect::<Result<_>>()?;
+        assert_eq!(range, txs[200..210]);
+        // Испорченное смещение в футере — ошибка, а не переполнение.
+        let mut footer = indexed.clone();
+        let at = footer.len() - 12;
+        footer[at..at + 8].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(matches!(bin_index::IndexedReader::open(Cursor::new(&footer)), Err(ParserError::Format(_))));
+
+        let mut plain = Vec::new();
+        bin_format::write_to(&mut plain, &txs)?;
+        let mut reader = bin_index::IndexedReader::open(Cursor::new(&plain))?;
+        assert!(reader.index().is_none());
+        assert_eq!(reader.get(txs[450].tx_id)?, Some(txs[450].clone()));
+        let range: Vec<Transaction> = reader.range(3_000, 3_090).collect::<Result<_>>()?;
+        assert_eq!(range, txs[200..210]);
+
+        let v1 = bin_format::WriteOptions { version: bin_format::Version::V1, index: true };
+        assert!(bin_format::Writer::with_options(Vec::new(), &v1).is_err());
+        Ok(())
+    }
+
+    /// Чтение из среза: поля совпадают с обычным чтением, UTF-8 проверяется только в описании.
+    #[test]
+    fn test_bin_view_slice_reader() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let txs: Vec<Transaction> = (0..5u64)
+            .map(|i| Transaction { tx_id: i, amount: 100 * i as i64, description: format!("Запись {}", i), ..base.clone() })
+            .collect();
+        let options = bin_format::WriteOptions { version: bin_format::Version::V2, index: true };
+        let mut writer = bin_format::Writer::with_options(Vec::new(), &options)?;
+        for tx in &txs {
+            writer.write(tx)?;
+        }
+        let data = writer.finish()?;
+
+        let mut reader = bin_view::SliceReader::new(&data);
+        let refs: Vec<bin_view::TransactionRef> = reader.by_ref().collect::<Result<_>>()?;
+        let owned: Vec<Transaction> = refs.iter().map(|tx| tx.to_transaction()).collect::<Result<_>>()?;
+        assert_eq!(owned, txs);
+        assert_eq!(reader.trailer().map(|t| t.amount_sum), Some(1000));
+
+        let mut by_ref = stats::Stats::new();
+        refs.iter().for_each(|tx| by_ref.add_ref(tx));
+        let mut by_value = stats::Stats::new();
+        by_value.add_all(txs.iter().cloned().map(Ok))?;
+        assert_eq!(by_ref.total(), by_value.total());
+
+        // Битый UTF-8 в описании мешает только обращению к описанию.
+        let mut v1 = Vec::new();
+        bin_format::write_to(&mut v1, &txs[..1])?;
+        let last = v1.len() - 1;
+        v1[last] = 0xFF;
+        assert!(bin_format::from_read(Cursor::new(&v1)).is_err());
+        let tx = bin_view::SliceReader::new(&v1).next().unwrap()?;
+        assert_eq!(tx.amount(), 0);
+        assert!(tx.description().is_err());
+
+        // Оборванный файл — ошибка, а не паника.
+        assert!(bin_view::SliceReader::new(&data[..data.len() / 2]).any(|r| r.is_err()));
+        Ok(())
+    }
+
+    /// Компактный формат: точный roundtrip, склейка сегментов и выигрыш на примере.
+    #[test]
+    fn test_compact_roundtrip_and_size() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let txs = vec![
+            base.clone(),
+            Transaction { tx_id: 999, timestamp: base.timestamp - 5000, amount: -1, description: String::new(), ..base.clone() },
+            Transaction { tx_id: u64::MAX, from_user_id: u64::MAX, timestamp: 0, amount: i64::MIN, ..base.clone() },
+            Transaction { tx_id: 0, status: TxStatus::Pending, tx_type: TxType::Withdrawal, ..base.clone() },
+        ];
+        let mut data = Vec::new();
+        compact_format::write_to(&mut data, &txs)?;
+        assert_eq!(compact_format::from_read(Cursor::new(&data))?, txs);
+
+        // Дописанный файл — второй сегмент со своими дельтами и словарём.
+        let mut appended = data.clone();
+        let mut writer = Format::Compact.appending_writer(&mut appended, txs.len());
+        writer.write(&txs[2])?;
+        writer.finish()?;
+        let read = compact_format::from_read(Cursor::new(&appended))?;
+        assert_eq!((read.len(), &read[4]), (5, &txs[2]));
+
+        let bin = include_bytes!("../examples/records_example.bin");
+        let records = bin_format::from_read(Cursor::new(&bin[..]))?;
+        let mut compact = Vec::new();
+        compact_format::write_to(&mut compact, &records)?;
+        assert!(compact.len() * 10 < bin.len() * 6, "{} vs {}", compact.len(), bin.len());
+        let mut back = Vec::new();
+        bin_format::write_to(&mut back, &compact_format::from_read(Cursor::new(&compact))?)?;
+        assert_eq!(back, bin);
+
+        assert!(compact_format::from_read(Cursor::new(&compact[..compact.len() - 3])).is_err());
+        Ok(())
+    }
+
+    /// Колоночный формат: roundtrip, чтение части полей и пропуск групп по статистике.
+    #[test]
+    fn test_columnar_projection_and_pruning() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let txs: Vec<Transaction> = (0..1000u64)
+            .map(|i| Transaction { tx_id: i, amount: i as i64, timestamp: 1_000 + i, ..base.clone() })
+            .collect();
+        let mut writer = columnar_format::Writer::with_row_group(Vec::new(), 100);
+        for tx in &txs {
+            writer.write(tx)?;
+        }
+        let data = writer.finish()?;
+        assert_eq!(columnar_format::from_read(Cursor::new(&data))?, txs);
+
+        // TIMESTAMP 1150..1249 — две группы из десяти.
+        let filter = filter::Filter::parse("TIMESTAMP BETWEEN 1150 AND 1249 AND NOT STATUS = PENDING")?;
+        let mut reader = columnar_format::Reader::seekable(Cursor::new(&data)).filter(filter);
+        let found: Vec<Transaction> = reader.by_ref().collect::<Result<_>>()?;
+        assert_eq!(found, txs[150..250]);
+        assert_eq!((reader.groups_read(), reader.groups_skipped()), (2, 8));
+        assert!(!filter::Filter::parse("STATUS = PENDING")?.may_match(|f| Some((base.field(f), base.field(f)))));
+
+        // Только поля статистики: описание и TX_ID не читаются.
+        let projected: Vec<Transaction> =
+            columnar_format::Reader::new(Cursor::new(&data)).columns(&stats::COLUMNS).collect::<Result<_>>()?;
+        assert!(projected.iter().all(|tx| tx.tx_id == 0 && tx.description.is_empty()));
+        let mut from_columns = stats::Stats::with_median();
+        from_columns.add_columnar(Cursor::new(&data), None)?;
+        let mut from_rows = stats::Stats::with_median();
+        from_rows.add_all(txs.iter().cloned().map(Ok))?;
+        assert_eq!(from_columns.total(), from_rows.total());
+        assert_eq!(from_columns.median_amount(), from_rows.median_amount());
+
+        // Дописанные группы читаются вместе с исходными.
+        let mut appended = data.clone();
+        let mut writer = Format::Columnar.appending_writer(&mut appended, txs.len());
+        writer.write(&base)?;
+        writer.finish()?;
+        assert_eq!(columnar_format::from_read(Cursor::new(&appended))?.len(), 1001);
+
+        let mut corrupted = data.clone();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 1;
+        assert!(columnar_format::from_read(Cursor::new(&corrupted)).is_err());
+
+        // ROWS с верной CRC заголовка, но не сходящийся с длинами блоков.
+        let mut inflated = data.clone();
+        inflated[10..14].copy_from_slice(&u32::MAX.to_be_bytes());
+        let crc_at = 10 + 4 + 8 * 12 + 2 * 42;
+        let crc = crc32::crc32(&inflated[10..crc_at]);
+        inflated[crc_at..crc_at + 4].copy_from_slice(&crc.to_be_bytes());
+        assert!(columnar_format::from_read(Cursor::new(&inflated)).is_err());
+        Ok(())
+    }
+
+    /// Дописывание: трейлер v2 переписывается, оборванная запись обрезается, нумерация текста продолжается.
+    #[test]
+    fn test_append_truncates_torn_tail() -> Result<()> {
+        let dir = TempDir::new("append")?;
+        let base = create_test_txs().remove(0);
+        let txs: Vec<Transaction> = (1..=3).map(|i| Transaction { tx_id: i, ..base.clone() }).collect();
+
+        let bin = dir.join("day.bin");
+        let options = append::AppendOptions { bin_version: bin_format::Version::V2, ..Default::default() };
+        let mut appender = append::Appender::open(&bin, Format::Bin, &options)?;
+        appender.append(&txs[..2])?;
+        appender.append(&txs[2..])?;
+        drop(appender);
+        assert_eq!(bin_format::from_read(std::fs::File::open(&bin)?)?, txs);
+
+        // Сбой посреди последней записи: трейлера нет, от записи осталась часть.
+        let len = std::fs::metadata(&bin)?.len();
+        std::fs::OpenOptions::new().write(true).open(&bin)?.set_len(len - 32 - 3)?;
+        assert!(append::Appender::open(&bin, Format::Bin, &options).is_err());
+        let repair = append::AppendOptions { truncate_torn: true, ..options };
+        let appender = append::Appender::open(&bin, Format::Bin, &repair)?;
+        assert_eq!(appender.records(), 2);
+        assert!(appender.truncated() > 0);
+        drop(appender);
+        assert_eq!(bin_format::from_read(std::fs::File::open(&bin)?)?, txs[..2]);
+
+        let text = dir.join("day.txt");
+        let mut data = Vec::new();
+        text_format::write_to(&mut data, &txs[..2])?;
+        data.extend_from_slice(b"# \xd0\x97\xd0\xb0\xd0\xbf\xd0\xb8\xd1\x81\xd1\x8c 3\nTX_ID: 3\nTX_TY");
+        std::fs::write(&text, &data)?;
+        assert!(append::Appender::open(&text, Format::Text, &append::AppendOptions::default()).is_err());
+        let mut appender = append::Appender::open(&text, Format::Text, &repair)?;
+        appender.append(&txs[2..])?;
+        drop(appender);
+        let written = std::fs::read_to_string(&text)?;
+        assert!(written.contains("# Запись 3\nTX_ID: 3\n"));
+        assert_eq!(text_format::from_read(written.as_bytes())?, txs);
+
+        // Испорченный RECORD_SIZE и неразбираемый последний блок — не обрыв.
+        let mut data = Vec::new();
+        bin_format::write_to(&mut data, &txs)?;
+        let last = data.len() - bin_format::record_len(&txs[2], false) as usize;
+        data[last + 4..last + 8].copy_from_slice(&1000u32.to_be_bytes());
+        std::fs::write(&bin, &data)?;
+        assert!(append::Appender::open(&bin, Format::Bin, &append::AppendOptions { truncate_torn: true, ..Default::default() }).is_err());
+        let mut data = Vec::new();
+        text_format::write_to(&mut data, &txs[..2])?;
+        data.extend_from_slice(b"TX_ID: 3\nAMOUNT: x\n");
+        std::fs::write(&text, &data)?;
+        assert!(append::Appender::open(&text, Format::Text, &repair).is_err());
+
+        Ok(())
+    }
+
+    /// Обрыв файла указывает запись и поле; repair сохраняет целые записи.
+    #[test]
+    fn test_bin_truncation_and_repair() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let txs: Vec<Transaction> = (1..=3).map(|i| Transaction { tx_id: i, ..base.clone() }).collect();
+        let mut data = Vec::new();
+        bin_format::write_to(&mut data, &txs)?;
+        let record_len = data.len() / 3;
+
+        // Третья запись оборвана в AMOUNT: MAGIC + размер + 30 байт тела.
+        let cut = &data[..2 * record_len + 8 + 30];
+        match bin_format::from_read(cut) {
+            Err(ParserError::Truncated(t)) => {
+                assert_eq!((t.record, t.offset, t.field), (3, 2 * record_len as u64, "AMOUNT"));
+                assert_eq!(t.missing, (record_len - 8 - 30) as u64);
+            }
+            other => panic!("ожидался обрыв, получено {:?}", other),
+        }
+        match bin_format::from_read(&data[..record_len + 2]) {
+            Err(ParserError::Truncated(t)) => assert_eq!((t.record, t.field, t.missing), (2, "MAGIC", 2)),
+            other => panic!("ожидался обрыв, получено {:?}", other),
+        }
+
+        // Мусор вместо первой записи и обрыв третьей.
+        let mut damaged = cut.to_vec();
+        damaged[..4].copy_from_slice(b"JUNK");
+        let mut out = Vec::new();
+        let report = repair::repair(&damaged[..], &mut out)?;
+        assert_eq!(report.kept, 1);
+        assert_eq!(report.dropped.len(), 2);
+        assert_eq!(report.dropped[0].reason, repair::DropReason::Garbage);
+        assert_eq!(report.truncation.as_ref().map(|t| t.field), Some("AMOUNT"));
+        assert_eq!(bin_format::from_read(out.as_slice())?, txs[1..2]);
+        // Мусор длиннее окна чтения пропускается одним участком.
+        let mut padded = vec![0u8; 100_000];
+        padded.extend_from_slice(&damaged);
+        let report = repair::repair(padded.as_slice(), std::io::sink())?;
+        assert_eq!((report.kept, report.dropped[0].len), (1, 100_000 + record_len as u64));
+        // Испорченный RECORD_SIZE не сходится с DESC_LEN: запись пропускается, тело не читается.
+        let mut oversized = data.clone();
+        oversized[4..8].copy_from_slice(&0xFFFF_FF00u32.to_be_bytes());
+        let report = repair::repair(oversized.as_slice(), std::io::sink())?;
+        assert_eq!((report.kept, report.dropped[0].reason), (2, repair::DropReason::InvalidRecord));
+
+        // v2: битая CRC второй записи, трейлера нет.
+        let mut v2 = Vec::new();
+        let options = bin_format::WriteOptions { version: bin_format::Version::V2, index: false };
+        let mut writer = bin_format::Writer::with_options(&mut v2, &options)?;
+        for tx in &txs {
+            writer.write(tx)?;
+        }
+        writer.finish()?;
+        v2.truncate(v2.len() - 32);
+        let header_len = v2.len() - 3 * (record_len + 4);
+        v2[header_len + record_len + 4 + 20] ^= 0xFF;
+        let mut out = Vec::new();
+        let report = repair::repair(&v2[..], &mut out)?;
+        assert!(report.trailer_missing);
+        assert_eq!(report.dropped[0].reason, repair::DropReason::CrcMismatch);
+        assert_eq!(report.dropped[0].tx_id, Some(2));
+        let mut reader = bin_format::Reader::new(out.as_slice());
+        let repaired: Vec<Transaction> = reader.by_ref().collect::<Result<_>>()?;
+        assert_eq!(repaired, vec![txs[0].clone(), txs[2].clone()]);
+        assert_eq!(reader.trailer().map(|t| t.record_count), Some(2));
+        Ok(())
+    }
+
+    /// Разбор по байтам помечает неверный тип, несовпадение размера и время за пределами i64.
+    #[test]
+    fn test_inspect_marks_inconsistencies() -> Result<()> {
+        let txs = create_test_txs();
+        let mut data = Vec::new();
+        bin_format::write_to(&mut data, &txs)?;
+        let mut out = Vec::new();
+        let summary = inspect::inspect(&data[..], &mut out, &inspect::InspectOptions::default())?;
+        assert_eq!(summary, inspect::InspectSummary { records: 1, issues: 0 });
+        let text = String::from_utf8(out)?;
+        assert!(text.contains("00000010  00                          TX_TYPE       DEPOSIT"));
+        assert!(text.contains("DESCRIPTION   \"Initial funding\""));
+
+        data[16] = 7;
+        data[7] += 1;
+        data[41] = 0x80;
+        let mut out = Vec::new();
+        let summary = inspect::inspect(&data[..], &mut out, &inspect::InspectOptions::default())?;
+        assert_eq!(summary.issues, 3);
+        let text = String::from_utf8(out)?;
+        assert!(text.contains("TX_TYPE       7  !! invalid TX_TYPE byte"));
+        assert!(text.contains("!! RECORD_SIZE 62 != 46 + DESC_LEN 15"));
+        assert!(text.contains("!! beyond i64::MAX ms"));
+
+        // Длинное описание показывается началом и длиной.
+        let long = Transaction { description: "é".repeat(1500), ..txs[0].clone() };
+        let mut data = Vec::new();
+        bin_format::write_to(&mut data, &[long])?;
+        let mut out = Vec::new();
+        let summary = inspect::inspect(&data[..], &mut out, &inspect::InspectOptions::default())?;
+        assert_eq!(summary.issues, 0);
+        assert!(String::from_utf8(out)?.contains("é\".. (3000 bytes)"));
+
+        // v2 с CRC, трейлером и индексом проверяется без замечаний.
+        let mut v2 = Vec::new();
+        let options = bin_format::WriteOptions { version: bin_format::Version::V2, index: true };
+        let mut writer = bin_format::Writer::with_options(&mut v2, &options)?;
+        for tx in &txs {
+            writer.write(tx)?;
+        }
+        writer.finish()?;
+        let mut out = Vec::new();
+        let summary = inspect::inspect(v2.as_slice(), &mut out, &inspect::InspectOptions::default())?;
+        assert_eq!(summary, inspect::InspectSummary { records: 1, issues: 0 });
+        assert!(String::from_utf8(out)?.contains("INDEX"));
+        Ok(())
+    }
+
+    /// Статус и сумма меняются на месте, CRC и трейлер v2 пересчитываются.
+    #[test]
+    fn test_patch_in_place() -> Result<()> {
+        let dir = TempDir::new("patch")?;
+        let base = create_test_txs().remove(0);
+        let txs: Vec<Transaction> =
+            (1..=3).map(|i| Transaction { tx_id: i, status: TxStatus::Pending, ..base.clone() }).collect();
+        let path = dir.join("pending.bin");
+        let mut file = std::fs::File::create(&path)?;
+        let options = bin_format::WriteOptions { version: bin_format::Version::V2, index: false };
+        let mut writer = bin_format::Writer::with_options(&mut file, &options)?;
+        for tx in &txs {
+            writer.write(tx)?;
+        }
+        writer.finish()?;
+        drop(file);
+        let before = std::fs::read(&path)?;
+
+        let assignments: Vec<patch::Assignment> = vec!["status=success".parse()?, "AMOUNT=10".parse()?];
+        assert!("TX_ID=5".parse::<patch::Assignment>().is_err());
+        let updates: std::collections::BTreeMap<u64, Vec<patch::Assignment>> =
+            [(2, assignments), (9, vec!["STATUS=FAILURE".parse()?])].into_iter().collect();
+        let plan = patch::plan(&path, &updates)?;
+        assert_eq!(plan.changes.len(), 2);
+        assert_eq!(plan.changes[0].old, FieldValue::Status(TxStatus::Pending));
+        assert_eq!(plan.not_found, vec![9]);
+        let mut audit = Vec::new();
+        plan.write_audit(&mut audit, "pending.bin", 0, patch::AuditState::DryRun)?;
+        assert!(String::from_utf8(audit)?.contains("\"field\":\"AMOUNT\",\"old\":50000,\"new\":10,\"state\":\"dry-run\""));
+        assert_eq!(std::fs::read(&path)?, before);
+
+        plan.apply(&path)?;
+        let after = std::fs::read(&path)?;
+        assert_eq!(after.len(), before.len());
+        let mut reader = bin_format::Reader::new(after.as_slice());
+        let read: Vec<Transaction> = reader.by_ref().collect::<Result<_>>()?;
+        assert_eq!(read[1], Transaction { status: TxStatus::Success, amount: 10, ..txs[1].clone() });
+        assert_eq!(read[0], txs[0]);
+        assert_eq!(reader.trailer().map(|t| t.amount_sum), Some(50000 * 2 + 10));
+
+        Ok(())
+    }
+
+    /// История статусов по дням: недопустимый переход и зависший PENDING.
+    #[test]
+    fn test_lifecycle_tracking() -> Result<()> {
+        let base = create_test_txs().remove(0);
+        let tx = |tx_id, status, hours: u64| Transaction { tx_id, status, timestamp: hours * 3_600_000, ..base.clone() };
+        let day1 = vec![tx(1, TxStatus::Pending, 0), tx(2, TxStatus::Success, 1), tx(3, TxStatus::Pending, 2)];
+        let day2 = vec![tx(1, TxStatus::Success, 24), tx(2, TxStatus::Pending, 25), tx(3, TxStatus::Pending, 26)];
+
+        let mut tracker = lifecycle::Tracker::new();
+        tracker.add_source("day1", day1.into_iter().map(Ok))?;
+        tracker.add_source("day2", day2.into_iter().map(Ok))?;
+        let statuses: Vec<TxStatus> = tracker.history(1).unwrap_or_default().iter().map(|c| c.status).collect();
+        assert_eq!(statuses, vec![TxStatus::Pending, TxStatus::Success]);
+        assert_eq!(tracker.history(3).map(<[_]>::len), Some(1));
+
+        let options = lifecycle::LifecycleOptions { stuck_after: lifecycle::parse_duration("12h")?, as_of: None };
+        let report = tracker.report(&options);
+        assert_eq!((report.success, report.pending, report.as_of), (1, 2, 26 * 3_600_000));
+        assert_eq!(report.illegal.len(), 1);
+        assert_eq!((report.illegal[0].tx_id, report.illegal[0].from.source, report.illegal[0].to.source), (2, 0, 1));
+        // TX 3 в PENDING с первого дня (24 ч), TX 2 вернулся в PENDING час назад.
+        assert_eq!(report.stuck.iter().map(|s| (s.tx_id, s.age)).collect::<Vec<_>>(), vec![(3, 24 * 3_600_000)]);
+
+        let mut out = Vec::new();
+        report.write(&mut out, compare::ReportFormat::Text)?;
+        assert!(String::from_utf8(out)?.contains("ILLEGAL TX_ID 2: SUCCESS (day1) -> PENDING (day2)"));
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,250 @@
+//! Жизненный цикл статусов по последовательным файлам.
+//!
+//! Файлы подаются по порядку (например, по дням). Для каждого `TX_ID`
+//! хранится история смен статуса. Из `PENDING` можно перейти в `SUCCESS`
+//! или `FAILURE`; `SUCCESS` и `FAILURE` — конечные, любой выход из них —
+//! недопустимый переход. Зависшие — те, что остаются в `PENDING` дольше
+//! порога на момент `as_of`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use crate::compare::ReportFormat;
+use crate::{json, Transaction, TxStatus, Result, ParserError};
+
+/// Параметры отслеживания.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LifecycleOptions {
+    /// Сколько миллисекунд транзакция может оставаться в `PENDING`.
+    pub stuck_after: u64,
+    /// Момент проверки, мс; `None` — наибольший `TIMESTAMP` во всех файлах.
+    pub as_of: Option<u64>,
+}
+
+impl Default for LifecycleOptions {
+    fn default() -> Self {
+        LifecycleOptions { stuck_after: DAY_MS, as_of: None }
+    }
+}
+
+const DAY_MS: u64 = 86_400_000;
+
+/// Длительность с необязательным суффиксом: `90000` (мс), `30s`, `15m`, `12h`, `3d`.
+pub fn parse_duration(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let (digits, multiplier) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1000),
+        Some((i, 'm')) => (&s[..i], 60_000),
+        Some((i, 'h')) => (&s[..i], 3_600_000),
+        Some((i, 'd')) => (&s[..i], DAY_MS),
+        _ => (s, 1),
+    };
+    digits
+        .parse::<u64>()?
+        .checked_mul(multiplier)
+        .ok_or_else(|| ParserError::Format(format!("Слишком большая длительность: {}", s)))
+}
+
+/// Статус, в котором транзакция встретилась впервые или сменила прежний.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusChange {
+    pub status: TxStatus,
+    /// Номер файла в порядке подачи, с 0.
+    pub source: usize,
+    /// `TIMESTAMP` записи с этим статусом.
+    pub timestamp: u64,
+}
+
+/// Переход из конечного статуса.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IllegalTransition {
+    pub tx_id: u64,
+    pub from: StatusChange,
+    pub to: StatusChange,
+}
+
+/// Транзакция, оставшаяся в `PENDING` дольше порога.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stuck {
+    pub tx_id: u64,
+    /// С какого файла и `TIMESTAMP` транзакция в `PENDING`.
+    pub since: StatusChange,
+    /// `as_of - since.timestamp`, мс.
+    pub age: u64,
+}
+
+/// Итог отслеживания.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LifecycleReport {
+    /// Имена файлов в порядке подачи.
+    pub sources: Vec<String>,
+    pub transactions: usize,
+    pub pending: usize,
+    pub success: usize,
+    pub failure: usize,
+    pub as_of: u64,
+    /// В порядке обнаружения.
+    pub illegal: Vec<IllegalTransition>,
+    /// По возрастанию `TX_ID`.
+    pub stuck: Vec<Stuck>,
+}
+
+/// Накопитель истории статусов.
+#[derive(Debug, Default)]
+pub struct Tracker {
+    sources: Vec<String>,
+    history: HashMap<u64, Vec<StatusChange>>,
+    illegal: Vec<IllegalTransition>,
+    max_timestamp: u64,
+}
+
+impl Tracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Добавляет следующий файл; `name` — для отчёта.
+    pub fn add_source<I>(&mut self, name: &str, transactions: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<Transaction>>,
+    {
+        let source = self.sources.len();
+        self.sources.push(name.to_string());
+        for tx in transactions {
+            self.add(&tx?, source);
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, tx: &Transaction, source: usize) {
+        self.max_timestamp = self.max_timestamp.max(tx.timestamp);
+        let change = StatusChange { status: tx.status, source, timestamp: tx.timestamp };
+        let history = self.history.entry(tx.tx_id).or_default();
+        if let Some(&last) = history.last() {
+            if last.status == tx.status {
+                return;
+            }
+            if last.status != TxStatus::Pending {
+                self.illegal.push(IllegalTransition { tx_id: tx.tx_id, from: last, to: change });
+            }
+        }
+        history.push(change);
+    }
+
+    /// История смен статуса транзакции.
+    pub fn history(&self, tx_id: u64) -> Option<&[StatusChange]> {
+        self.history.get(&tx_id).map(Vec::as_slice)
+    }
+
+    pub fn report(&self, options: &LifecycleOptions) -> LifecycleReport {
+        let as_of = options.as_of.unwrap_or(self.max_timestamp);
+        let mut report = LifecycleReport {
+            sources: self.sources.clone(),
+            transactions: self.history.len(),
+            as_of,
+            illegal: self.illegal.clone(),
+            ..LifecycleReport::default()
+        };
+        for (&tx_id, history) in &self.history {
+            let Some(&last) = history.last() else { continue };
+            match last.status {
+                TxStatus::Success => report.success += 1,
+                TxStatus::Failure => report.failure += 1,
+                TxStatus::Pending => {
+                    report.pending += 1;
+                    let age = as_of.saturating_sub(last.timestamp);
+                    if age > options.stuck_after {
+                        report.stuck.push(Stuck { tx_id, since: last, age });
+                    }
+                }
+            }
+        }
+        report.stuck.sort_by_key(|s| s.tx_id);
+        report
+    }
+}
+
+impl LifecycleReport {
+    pub fn is_clean(&self) -> bool {
+        self.illegal.is_empty() && self.stuck.is_empty()
+    }
+
+    fn source(&self, change: &StatusChange) -> &str {
+        self.sources.get(change.source).map_or("", String::as_str)
+    }
+
+    /// Отчёт текстом или JSON.
+    pub fn write<W: Write>(&self, mut writer: W, format: ReportFormat) -> Result<()> {
+        match format {
+            ReportFormat::Text => {
+                for t in &self.illegal {
+                    writeln!(
+                        writer,
+                        "ILLEGAL TX_ID {}: {} ({}) -> {} ({})",
+                        t.tx_id,
+                        t.from.status.as_str(),
+                        self.source(&t.from),
+                        t.to.status.as_str(),
+                        self.source(&t.to)
+                    )?;
+                }
+                for s in &self.stuck {
+                    writeln!(
+                        writer,
+                        "STUCK TX_ID {}: PENDING for {} ms since {} ({})",
+                        s.tx_id,
+                        s.age,
+                        s.since.timestamp,
+                        self.source(&s.since)
+                    )?;
+                }
+                writeln!(
+                    writer,
+                    "Tracked {} transactions over {} files as of {}: {} success, {} failure, {} pending; {} illegal transitions, {} stuck.",
+                    self.transactions,
+                    self.sources.len(),
+                    self.as_of,
+                    self.success,
+                    self.failure,
+                    self.pending,
+                    self.illegal.len(),
+                    self.stuck.len()
+                )?;
+            }
+            ReportFormat::Json => {
+                let change = |c: &StatusChange| {
+                    format!(
+                        "{{\"status\":{},\"source\":{},\"timestamp\":{}}}",
+                        json::string(c.status.as_str()),
+                        json::string(self.source(c)),
+                        c.timestamp
+                    )
+                };
+                let illegal: Vec<String> = self
+                    .illegal
+                    .iter()
+                    .map(|t| format!("{{\"tx_id\":{},\"from\":{},\"to\":{}}}", t.tx_id, change(&t.from), change(&t.to)))
+                    .collect();
+                let stuck: Vec<String> = self
+                    .stuck
+                    .iter()
+                    .map(|s| format!("{{\"tx_id\":{},\"since\":{},\"age\":{}}}", s.tx_id, change(&s.since), s.age))
+                    .collect();
+                writeln!(
+                    writer,
+                    "{{\"transactions\":{},\"success\":{},\"failure\":{},\"pending\":{},\"as_of\":{},\"illegal\":[{}],\"stuck\":[{}]}}",
+                    self.transactions,
+                    self.success,
+                    self.failure,
+                    self.pending,
+                    self.as_of,
+                    illegal.join(","),
+                    stuck.join(",")
+                )?;
+            }
+            ReportFormat::Csv => {
+                return Err(ParserError::Format("Отчёт о статусах выводится как text или json".into()));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,70 +1,151 @@
-use std::env;
-use std::fs::File;
-use std::io;
-use parser::{text_format, csv_format, bin_format, Result};
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::process;
+use std::thread;
+use parser::append::{AppendOptions, Appender};
+use parser::batch::ConvertOptions;
+use parser::filter::Filter;
+use parser::format::Transactions;
+use parser::{batch, cli, Format, Result};
+
+/// Сколько записей дописывается (и синхронизируется с `--fsync`) за раз.
+const APPEND_BATCH: usize = 1024;
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut args = cli::Args::from_env();
 
-    let mut input_file = None;
+    let mut inputs = Vec::new();
     let mut input_format = None;
     let mut output_format = None;
+    let mut output_dir = None;
+    let mut jobs = None;
+    let mut options = ConvertOptions::default();
+    let mut append_to = None;
+    let mut append = AppendOptions::default();
 
-    let mut i = 1;
-    while i < args.len() {
-        match args[i].as_str() {
-            "--input" => {
-                if i + 1 < args.len() {
-                    input_file = Some(&args[i + 1]);
-                    i += 1;
-                }
-            }
-            "--input-format" => {
-                if i + 1 < args.len() {
-                    input_format = Some(&args[i + 1]);
-                    i += 1;
-                }
-            }
-            "--output-format" => {
-                if i + 1 < args.len() {
-                    output_format = Some(&args[i + 1]);
-                    i += 1;
-                }
-            }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => inputs.push(args.value()?),
+            "--input-format" => input_format = Some(args.value()?),
+            "--output-format" => output_format = Some(args.value()?),
+            "--output-dir" => output_dir = Some(args.value()?),
+            "--jobs" => jobs = Some(args.value()?),
+            "--filter" => options.filter = Some(Filter::parse(&args.value()?)?),
+            "--bin-version" => options.bin.version = args.value()?.parse()?,
+            "--bin-index" => options.bin.index = true,
+            "--append" => append_to = Some(args.value()?),
+            "--truncate-torn" => append.truncate_torn = true,
+            "--fsync" => append.sync = true,
             _ => {}
         }
-        i += 1;
     }
 
-    let input_path = input_file.ok_or_else(|| {
-        parser::ParserError::Format("Ошибка: Используйте --input <файл>".into())
-    })?;
-    
-    let in_fmt = input_format.map(|s| s.as_str()).unwrap_or("text");
-    let out_fmt = output_format.map(|s| s.as_str()).unwrap_or("text");
+    if inputs.is_empty() {
+        return Err(cli::usage("--input <файл>"));
+    }
 
-    let file = File::open(input_path).map_err(|e| {
-        parser::ParserError::Format(format!(
-            "Не удалось открыть входной файл (--input) '{}': {}",
-            input_path, e
-        ))
+    let in_fmt = input_format.as_deref().unwrap_or("text");
+    let out_fmt = output_format.as_deref().unwrap_or("text");
+    let in_fmt: Format = in_fmt.parse().map_err(|_| {
+        parser::ParserError::Format(format!("Неизвестный входной формат: {}", in_fmt))
+    })?;
+    let out_fmt: Format = out_fmt.parse().map_err(|_| {
+        parser::ParserError::Format(format!("Неизвестный выходной формат: {}", out_fmt))
     })?;
-    let transactions = match in_fmt {
-        "csv" => csv_format::from_read(file)?,
-        "bin" | "binary" => bin_format::from_read(file)?,
-        "text" => text_format::from_read(file)?,
-        _ => return Err(parser::ParserError::Format(format!("Неизвестный входной формат: {}", in_fmt))),
-    };
 
-    let stdout = io::stdout();
-    let handle = stdout.lock();
+    if let Some(dir) = output_dir {
+        let threads = match &jobs {
+            Some(n) => n.parse::<usize>()?,
+            None => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        };
+        let failed = run_batch(&inputs, Path::new(&dir), in_fmt, out_fmt, threads, &options)?;
+        if failed > 0 {
+            process::exit(1);
+        }
+        return Ok(());
+    }
 
-    match out_fmt {
-        "csv" => csv_format::write_to(handle, &transactions)?,
-        "bin" | "binary" => bin_format::write_to(handle, &transactions)?,
-        "text" => text_format::write_to(handle, &transactions)?,
-        _ => return Err(parser::ParserError::Format(format!("Неизвестный выходной формат: {}", out_fmt))),
+    if inputs.len() > 1 {
+        return Err(parser::ParserError::Format(
+            "Несколько --input допускаются только вместе с --output-dir".into(),
+        ));
     }
+    let input_path = &inputs[0];
 
+    let file = cli::open_file(input_path, "--input")?;
+    let transactions = in_fmt.file_reader(file, options.filter);
+
+    if let Some(path) = append_to {
+        append.bin_version = options.bin.version;
+        return append_file(Path::new(&path), out_fmt, &append, transactions);
+    }
+
+    let stdout = io::stdout();
+    let mut writer = out_fmt.writer_with(BufWriter::new(stdout.lock()), &options.bin)?;
+    for tx in transactions {
+        writer.write(&tx?)?;
+    }
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Дописывает поток в файл пачками по [`APPEND_BATCH`] записей.
+fn append_file(path: &Path, format: Format, options: &AppendOptions, transactions: Transactions) -> Result<()> {
+    let mut appender = Appender::open(path, format, options)?;
+    if appender.truncated() > 0 {
+        eprintln!("{}: обрезана оборванная запись ({} байт)", path.display(), appender.truncated());
+    }
+    let mut batch = Vec::with_capacity(APPEND_BATCH);
+    for tx in transactions {
+        batch.push(tx?);
+        if batch.len() == APPEND_BATCH {
+            appender.append(&batch)?;
+            batch.clear();
+        }
+    }
+    appender.append(&batch)?;
     Ok(())
 }
+
+/// Пакетный режим: печатает итог по каждому файлу, возвращает число неудач.
+fn run_batch(
+    inputs: &[String],
+    output_dir: &Path,
+    in_fmt: Format,
+    out_fmt: Format,
+    threads: usize,
+    options: &ConvertOptions,
+) -> Result<usize> {
+    let files = batch::collect_inputs(inputs, in_fmt)?;
+    if files.is_empty() {
+        return Err(parser::ParserError::Format("Не найдено ни одного входного файла".into()));
+    }
+    fs::create_dir_all(output_dir)?;
+    let jobs = batch::plan(&files, output_dir, out_fmt)?;
+    let outcomes = batch::run(jobs, in_fmt, out_fmt, threads, options);
+
+    let mut failed = 0;
+    for outcome in &outcomes {
+        match &outcome.result {
+            Ok(count) => println!(
+                "OK    {} -> {} ({} records)",
+                outcome.job.input.display(),
+                outcome.job.output.display(),
+                count
+            ),
+            Err(e) => {
+                failed += 1;
+                println!("FAIL  {}: {}", outcome.job.input.display(), e);
+            }
+        }
+    }
+    println!(
+        "Converted {} of {} files, {} failed.",
+        outcomes.len() - failed,
+        outcomes.len(),
+        failed
+    );
+    Ok(failed)
+}
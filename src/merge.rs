@@ -0,0 +1,117 @@
+//! Слияние нескольких потоков транзакций в один.
+//!
+//! Потоки читаются по очереди. Точный повтор уже встреченной транзакции
+//! отбрасывается; транзакция с тем же `TX_ID`, но другими полями — конфликт.
+//! Для обнаружения хранится первая запись с каждым `TX_ID` и номер её входа,
+//! поэтому память растёт с числом различных `TX_ID`.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::str::FromStr;
+use crate::format::Transactions;
+use crate::{Transaction, Result, ParserError};
+
+/// Что делать с конфликтующими дубликатами.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Остановиться с ошибкой на первом конфликте.
+    #[default]
+    Error,
+    /// Оставить первую транзакцию, конфликт запомнить.
+    KeepFirst,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "error" => Ok(ConflictPolicy::Error),
+            "keep-first" | "first" => Ok(ConflictPolicy::KeepFirst),
+            _ => Err(ParserError::Format(format!("Неизвестная политика конфликтов: {}", s))),
+        }
+    }
+}
+
+/// Две разные транзакции с одним `TX_ID`; входы нумеруются с нуля.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    pub tx_id: u64,
+    pub first_input: usize,
+    pub second_input: usize,
+}
+
+/// Поток слияния; после чтения доступны конфликты и число отброшенных повторов.
+pub struct Merge<'a> {
+    inputs: Vec<Transactions<'a>>,
+    current: usize,
+    policy: ConflictPolicy,
+    seen: HashMap<u64, (Transaction, usize)>,
+    conflicts: Vec<Conflict>,
+    duplicates: usize,
+    failed: bool,
+}
+
+impl<'a> Merge<'a> {
+    pub fn new(inputs: Vec<Transactions<'a>>, policy: ConflictPolicy) -> Self {
+        Merge {
+            inputs,
+            current: 0,
+            policy,
+            seen: HashMap::new(),
+            conflicts: Vec::new(),
+            duplicates: 0,
+            failed: false,
+        }
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Сколько точных повторов отброшено.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+}
+
+impl Iterator for Merge<'_> {
+    type Item = Result<Transaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed && self.current < self.inputs.len() {
+            let tx = match self.inputs[self.current].next() {
+                Some(Ok(tx)) => tx,
+                Some(Err(e)) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.current += 1;
+                    continue;
+                }
+            };
+            match self.seen.entry(tx.tx_id) {
+                Entry::Vacant(entry) => {
+                    entry.insert((tx.clone(), self.current));
+                    return Some(Ok(tx));
+                }
+                Entry::Occupied(entry) if entry.get().0 == tx => self.duplicates += 1,
+                Entry::Occupied(entry) => {
+                    let conflict = Conflict { tx_id: tx.tx_id, first_input: entry.get().1, second_input: self.current };
+                    self.conflicts.push(conflict);
+                    if self.policy == ConflictPolicy::Error {
+                        self.failed = true;
+                        return Some(Err(ParserError::Format(format!(
+                            "Конфликт: TX_ID {} различается во входах {} и {}",
+                            conflict.tx_id,
+                            conflict.first_input + 1,
+                            conflict.second_input + 1
+                        ))));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
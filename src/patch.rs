@@ -0,0 +1,233 @@
+//! Изменение полей фиксированного размера прямо в файле YPBankBin.
+//!
+//! [`plan`] читает файл целиком с проверкой CRC и трейлера и составляет
+//! список изменений, [`Plan::apply`] переписывает только тела найденных
+//! записей, их CRC32 и трейлер v2. Размер файла и описания не меняются.
+//! `TX_ID` и `DESCRIPTION` на месте не меняются; `TIMESTAMP` в файле с
+//! индексом — тоже, иначе индекс времени разойдётся с данными.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
+use crate::bin_format::{self, Trailer, FLAG_INDEX, FLAG_RECORD_CRC};
+use crate::crc32::crc32;
+use crate::field::{Field, FieldValue};
+use crate::{json, Transaction, Result, ParserError};
+
+/// Новое значение поля: `STATUS=SUCCESS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub field: Field,
+    pub value: FieldValue,
+}
+
+impl FromStr for Assignment {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (field, value) = s
+            .split_once('=')
+            .ok_or_else(|| ParserError::Format(format!("Ожидается ПОЛЕ=ЗНАЧЕНИЕ: {}", s)))?;
+        let field: Field = field.trim().parse()?;
+        let value = value.trim();
+        let invalid = || ParserError::Format(format!("Неверное значение для {}: {}", field, value));
+        let value = match field {
+            Field::FromUserId | Field::ToUserId | Field::Timestamp => {
+                FieldValue::Unsigned(value.parse().map_err(|_| invalid())?)
+            }
+            Field::Amount => FieldValue::Signed(value.parse().map_err(|_| invalid())?),
+            Field::TxType => FieldValue::Type(value.to_ascii_uppercase().parse().map_err(|_| invalid())?),
+            Field::Status => FieldValue::Status(value.to_ascii_uppercase().parse().map_err(|_| invalid())?),
+            Field::TxId | Field::Description => {
+                return Err(ParserError::Format(format!("Поле {} нельзя изменить на месте", field)));
+            }
+        };
+        Ok(Assignment { field, value })
+    }
+}
+
+impl Assignment {
+    fn apply(&self, tx: &mut Transaction) {
+        match (self.field, &self.value) {
+            (Field::FromUserId, FieldValue::Unsigned(v)) => tx.from_user_id = *v,
+            (Field::ToUserId, FieldValue::Unsigned(v)) => tx.to_user_id = *v,
+            (Field::Timestamp, FieldValue::Unsigned(v)) => tx.timestamp = *v,
+            (Field::Amount, FieldValue::Signed(v)) => tx.amount = *v,
+            (Field::TxType, FieldValue::Type(v)) => tx.tx_type = *v,
+            (Field::Status, FieldValue::Status(v)) => tx.status = *v,
+            _ => {}
+        }
+    }
+}
+
+/// Изменение одного поля одной записи.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub tx_id: u64,
+    /// Смещение `MAGIC` записи.
+    pub offset: u64,
+    pub field: Field,
+    pub old: FieldValue,
+    pub new: FieldValue,
+}
+
+/// Что будет изменено.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    /// В порядке файла; поля, где значение уже такое, не попадают.
+    pub changes: Vec<Change>,
+    /// Запрошенные `TX_ID`, которых нет в файле.
+    pub not_found: Vec<u64>,
+    /// Смещения и новые значения изменяемых записей.
+    records: Vec<(u64, Transaction)>,
+    crc: bool,
+    /// Смещение трейлера v2 и новые итоги.
+    trailer: Option<(u64, Trailer)>,
+    len: u64,
+}
+
+/// Состояние изменений в журнале. Перед записью в файл изменения
+/// заносятся как `Pending`, после — как `Committed` или `Failed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditState {
+    DryRun,
+    Pending,
+    Committed,
+    Failed,
+}
+
+impl AuditState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditState::DryRun => "dry-run",
+            AuditState::Pending => "pending",
+            AuditState::Committed => "committed",
+            AuditState::Failed => "failed",
+        }
+    }
+}
+
+/// Составляет изменения для записей с указанными `TX_ID` (все повторы).
+pub fn plan(path: &Path, updates: &BTreeMap<u64, Vec<Assignment>>) -> Result<Plan> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut reader = bin_format::Reader::new(BufReader::new(file));
+    let mut plan = Plan { len, ..Plan::default() };
+    let mut found = BTreeSet::new();
+    let mut amount_delta = 0i128;
+
+    while let Some(tx) = reader.next().transpose()? {
+        let Some(assignments) = updates.get(&tx.tx_id) else { continue };
+        let header = reader.header();
+        plan.crc = header.is_some_and(|h| h.has(FLAG_RECORD_CRC));
+        if header.is_some_and(|h| h.has(FLAG_INDEX)) && assignments.iter().any(|a| a.field == Field::Timestamp) {
+            return Err(ParserError::Format("TIMESTAMP нельзя изменить на месте в файле с индексом".into()));
+        }
+        let offset = reader.offset() - bin_format::record_len(&tx, plan.crc);
+        found.insert(tx.tx_id);
+
+        let mut updated = tx.clone();
+        for assignment in assignments {
+            let old = updated.field(assignment.field);
+            if old != assignment.value {
+                assignment.apply(&mut updated);
+                plan.changes.push(Change {
+                    tx_id: tx.tx_id,
+                    offset,
+                    field: assignment.field,
+                    old,
+                    new: assignment.value.clone(),
+                });
+            }
+        }
+        if updated != tx {
+            amount_delta += updated.amount as i128 - tx.amount as i128;
+            plan.records.push((offset, updated));
+        }
+    }
+
+    if let Some(&trailer) = reader.trailer() {
+        let totals = Trailer { amount_sum: trailer.amount_sum + amount_delta, ..trailer };
+        plan.trailer = Some((reader.offset(), totals));
+    }
+    plan.not_found = updates.keys().filter(|id| !found.contains(id)).copied().collect();
+    Ok(plan)
+}
+
+impl Plan {
+    /// Сколько записей будет изменено.
+    pub fn records(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Записывает изменения в файл и сбрасывает их на диск.
+    ///
+    /// Запись не атомарна: тела сбрасываются на диск до трейлера, и сбой
+    /// между ними оставляет новые записи со старым трейлером. Чтение такого
+    /// файла сообщит о несовпадении трейлера (или CRC оборванной записи);
+    /// `ypbank_repair` пересчитает трейлер, а журнал покажет изменения в
+    /// состоянии `pending` без `committed`.
+    pub fn apply(&self, path: &Path) -> Result<()> {
+        if self.records.is_empty() {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        if file.metadata()?.len() != self.len {
+            return Err(ParserError::Format("Файл изменился после чтения".into()));
+        }
+        let mut body = Vec::new();
+        for (offset, tx) in &self.records {
+            body.clear();
+            bin_format::encode_body(&mut body, tx);
+            if self.crc {
+                let crc = crc32(&body);
+                body.extend_from_slice(&crc.to_be_bytes());
+            }
+            file.seek(SeekFrom::Start(offset + 8))?;
+            file.write_all(&body)?;
+        }
+        if let Some((offset, totals)) = &self.trailer {
+            // Трейлер не должен попасть на диск раньше тел записей.
+            file.sync_data()?;
+            file.seek(SeekFrom::Start(*offset))?;
+            file.write_all(&bin_format::encode_trailer(totals))?;
+        }
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Журнал изменений: по строке JSON на изменённое поле.
+    pub fn write_audit<W: Write>(&self, mut writer: W, file: &str, at: u64, state: AuditState) -> Result<()> {
+        for c in &self.changes {
+            writeln!(
+                writer,
+                "{{\"at\":{},\"file\":{},\"tx_id\":{},\"offset\":{},\"field\":{},\"old\":{},\"new\":{},\"state\":{}}}",
+                at,
+                json::string(file),
+                c.tx_id,
+                c.offset,
+                json::string(c.field.name()),
+                json::value(&c.old),
+                json::value(&c.new),
+                json::string(state.as_str())
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Список изменений для человека.
+    pub fn write_summary<W: Write>(&self, mut writer: W, dry_run: bool) -> Result<()> {
+        for c in &self.changes {
+            writeln!(writer, "TX_ID {} @ {:#010x}: {} {} -> {}", c.tx_id, c.offset, c.field, c.old, c.new)?;
+        }
+        for tx_id in &self.not_found {
+            writeln!(writer, "TX_ID {} not found", tx_id)?;
+        }
+        let verb = if dry_run { "Would change" } else { "Changed" };
+        writeln!(writer, "{} {} fields in {} records.", verb, self.changes.len(), self.records.len())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+//! Сверка двух источников без общего `TX_ID`: пары ищутся по пользователям,
+//! сумме и окну времени, в том числе с разбиением одной операции на несколько.
+
+use std::collections::HashMap;
+use std::io::Write;
+use crate::compare::ReportFormat;
+use crate::{json, Transaction, Result};
+
+/// Максимальная оценка пары.
+pub const MAX_SCORE: u32 = 1000;
+
+/// Сколько ближайших по времени кандидатов перебирать при поиске разбиений.
+const SPLIT_CANDIDATES: usize = 16;
+
+/// Наибольшее `max_split`; большие значения урезаются до него.
+pub const MAX_SPLIT: usize = 8;
+
+/// Параметры сверки.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconcileOptions {
+    /// Максимальная разница `TIMESTAMP`, мс.
+    pub time_window: u64,
+    /// Допустимая разница `AMOUNT`.
+    pub amount_tolerance: u64,
+    /// Максимальное число частей при разбиении (1 — без разбиений),
+    /// не больше [`MAX_SPLIT`].
+    pub max_split: usize,
+}
+
+impl Default for ReconcileOptions {
+    fn default() -> Self {
+        ReconcileOptions { time_window: 60_000, amount_tolerance: 0, max_split: 3 }
+    }
+}
+
+/// Сопоставленная группа: одна запись с одной стороны и одна или несколько с другой.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub first: Vec<Transaction>,
+    pub second: Vec<Transaction>,
+    /// От 0 до [`MAX_SCORE`].
+    pub score: u32,
+}
+
+/// Сторона сверки.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    First,
+    Second,
+}
+
+/// Запись, для которой несколько кандидатов одинаково хороши.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ambiguous {
+    pub side: Side,
+    pub record: Transaction,
+    /// Кандидаты с другой стороны.
+    pub candidates: Vec<Transaction>,
+    pub score: u32,
+}
+
+/// Результат сверки. Каждая запись попадает ровно в одну группу:
+/// кандидаты неоднозначных случаев не считаются несопоставленными.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub matched: Vec<Match>,
+    pub ambiguous: Vec<Ambiguous>,
+    pub unmatched_first: Vec<Transaction>,
+    pub unmatched_second: Vec<Transaction>,
+}
+
+impl Reconciliation {
+    /// Всё сопоставлено однозначно.
+    pub fn is_complete(&self) -> bool {
+        self.ambiguous.is_empty() && self.unmatched_first.is_empty() && self.unmatched_second.is_empty()
+    }
+}
+
+/// Оценка пары: близость по времени и сумме, совпадение типа и статуса.
+/// `None`, если пара не проходит по окну времени или допуску суммы.
+fn score(a: &Transaction, b: &Transaction, amount: i64, options: &ReconcileOptions) -> Option<u32> {
+    let dt = a.timestamp.abs_diff(b.timestamp);
+    let da = a.amount.abs_diff(amount);
+    if dt > options.time_window || da > options.amount_tolerance {
+        return None;
+    }
+    let time_part = 700 - (700 * dt as u128 / (options.time_window as u128 + 1)) as u32;
+    let amount_part = 200 - (200 * da as u128 / (options.amount_tolerance as u128 + 1)) as u32;
+    let type_part = if a.tx_type == b.tx_type { 50 } else { 0 };
+    let status_part = if a.status == b.status { 50 } else { 0 };
+    Some(time_part + amount_part + type_part + status_part)
+}
+
+/// Сверяет два набора. Сначала ищутся пары один к одному (по убыванию оценки),
+/// затем для оставшихся — разбиения один ко многим в обе стороны.
+pub fn reconcile(first: &[Transaction], second: &[Transaction], options: &ReconcileOptions) -> Reconciliation {
+    let options = &ReconcileOptions { max_split: options.max_split.clamp(1, MAX_SPLIT), ..*options };
+    let mut by_users: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+    for (j, tx) in second.iter().enumerate() {
+        by_users.entry((tx.from_user_id, tx.to_user_id)).or_default().push(j);
+    }
+
+    let mut edges: Vec<(u32, usize, usize)> = Vec::new();
+    let mut of_first: Vec<Vec<(u32, usize)>> = vec![Vec::new(); first.len()];
+    let mut of_second: Vec<Vec<(u32, usize)>> = vec![Vec::new(); second.len()];
+    for (i, a) in first.iter().enumerate() {
+        for &j in by_users.get(&(a.from_user_id, a.to_user_id)).into_iter().flatten() {
+            if let Some(s) = score(a, &second[j], second[j].amount, options) {
+                edges.push((s, i, j));
+                of_first[i].push((s, j));
+                of_second[j].push((s, i));
+            }
+        }
+    }
+    edges.sort_by(|x, y| y.0.cmp(&x.0).then(x.1.cmp(&y.1)).then(x.2.cmp(&y.2)));
+
+    let mut used_first = vec![false; first.len()];
+    let mut used_second = vec![false; second.len()];
+    let mut result = Reconciliation::default();
+
+    for &(s, i, j) in &edges {
+        if used_first[i] || used_second[j] {
+            continue;
+        }
+        let rivals_j: Vec<usize> = of_first[i]
+            .iter()
+            .filter(|&&(s2, j2)| s2 == s && j2 != j && !used_second[j2])
+            .map(|&(_, j2)| j2)
+            .collect();
+        let rivals_i: Vec<usize> = of_second[j]
+            .iter()
+            .filter(|&&(s2, i2)| s2 == s && i2 != i && !used_first[i2])
+            .map(|&(_, i2)| i2)
+            .collect();
+
+        if !rivals_j.is_empty() {
+            used_first[i] = true;
+            for &k in std::iter::once(&j).chain(&rivals_j) {
+                used_second[k] = true;
+            }
+            let candidates = std::iter::once(j).chain(rivals_j).map(|k| second[k].clone()).collect();
+            result.ambiguous.push(Ambiguous { side: Side::First, record: first[i].clone(), candidates, score: s });
+        } else if !rivals_i.is_empty() {
+            used_second[j] = true;
+            for &k in std::iter::once(&i).chain(&rivals_i) {
+                used_first[k] = true;
+            }
+            let candidates = std::iter::once(i).chain(rivals_i).map(|k| first[k].clone()).collect();
+            result.ambiguous.push(Ambiguous { side: Side::Second, record: second[j].clone(), candidates, score: s });
+        } else {
+            used_first[i] = true;
+            used_second[j] = true;
+            result.matched.push(Match { first: vec![first[i].clone()], second: vec![second[j].clone()], score: s });
+        }
+    }
+
+    if options.max_split > 1 {
+        find_splits(first, second, &mut used_first, &mut used_second, options, Side::First, &mut result);
+        find_splits(second, first, &mut used_second, &mut used_first, options, Side::Second, &mut result);
+    }
+
+    result.unmatched_first = unused(first, &used_first);
+    result.unmatched_second = unused(second, &used_second);
+    result
+}
+
+fn unused(txs: &[Transaction], used: &[bool]) -> Vec<Transaction> {
+    txs.iter().zip(used).filter(|(_, &u)| !u).map(|(tx, _)| tx.clone()).collect()
+}
+
+/// Для каждой свободной записи `whole` ищет набор свободных записей `parts`
+/// с теми же пользователями, сумма которых равна её сумме.
+fn find_splits(
+    whole: &[Transaction],
+    parts: &[Transaction],
+    used_whole: &mut [bool],
+    used_parts: &mut [bool],
+    options: &ReconcileOptions,
+    side: Side,
+    result: &mut Reconciliation,
+) {
+    let mut by_users: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+    for (j, tx) in parts.iter().enumerate() {
+        if !used_parts[j] {
+            by_users.entry((tx.from_user_id, tx.to_user_id)).or_default().push(j);
+        }
+    }
+
+    for i in 0..whole.len() {
+        if used_whole[i] {
+            continue;
+        }
+        let target = &whole[i];
+        let mut candidates: Vec<usize> = by_users
+            .get(&(target.from_user_id, target.to_user_id))
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|&j| {
+                let p = &parts[j];
+                !used_parts[j]
+                    && p.timestamp.abs_diff(target.timestamp) <= options.time_window
+                    && p.amount.signum() == target.amount.signum()
+                    && p.amount.unsigned_abs() < target.amount.unsigned_abs()
+            })
+            .collect();
+        candidates.sort_by_key(|&j| (parts[j].timestamp.abs_diff(target.timestamp), j));
+        candidates.truncate(SPLIT_CANDIDATES);
+
+        let mut best: Vec<(u32, Vec<usize>)> = Vec::new();
+        let mut chosen = Vec::new();
+        search_subsets(target, parts, &candidates, 0, 0, &mut chosen, options, &mut best);
+        let Some((top, _)) = best.iter().max_by_key(|(s, _)| *s).cloned() else { continue };
+        let winners: Vec<&Vec<usize>> = best.iter().filter(|(s, _)| *s == top).map(|(_, set)| set).collect();
+
+        used_whole[i] = true;
+        if winners.len() > 1 {
+            let mut ids: Vec<usize> = winners.iter().flat_map(|set| set.iter().copied()).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            for &j in &ids {
+                used_parts[j] = true;
+            }
+            result.ambiguous.push(Ambiguous {
+                side,
+                record: target.clone(),
+                candidates: ids.iter().map(|&j| parts[j].clone()).collect(),
+                score: top,
+            });
+            continue;
+        }
+        let set = winners[0];
+        for &j in set {
+            used_parts[j] = true;
+        }
+        let one = vec![target.clone()];
+        let many: Vec<Transaction> = set.iter().map(|&j| parts[j].clone()).collect();
+        let (first, second) = match side {
+            Side::First => (one, many),
+            Side::Second => (many, one),
+        };
+        result.matched.push(Match { first, second, score: top });
+    }
+}
+
+/// Перебор подмножеств размером от 2 до `max_split`, сумма которых попадает в допуск.
+#[allow(clippy::too_many_arguments)]
+fn search_subsets(
+    target: &Transaction,
+    parts: &[Transaction],
+    candidates: &[usize],
+    start: usize,
+    sum: i64,
+    chosen: &mut Vec<usize>,
+    options: &ReconcileOptions,
+    found: &mut Vec<(u32, Vec<usize>)>,
+) {
+    if chosen.len() >= 2 {
+        let latest = chosen.iter().max_by_key(|&&j| parts[j].timestamp.abs_diff(target.timestamp));
+        if let Some(s) = latest.and_then(|&j| score(target, &parts[j], sum, options)) {
+            found.push((s, chosen.clone()));
+        }
+    }
+    if chosen.len() == options.max_split {
+        return;
+    }
+    for k in start..candidates.len() {
+        let j = candidates[k];
+        let Some(next) = sum.checked_add(parts[j].amount) else { continue };
+        if next.unsigned_abs() > target.amount.unsigned_abs().saturating_add(options.amount_tolerance) {
+            continue;
+        }
+        chosen.push(j);
+        search_subsets(target, parts, candidates, k + 1, next, chosen, options, found);
+        chosen.pop();
+    }
+}
+
+/// Пишет результат сверки: текст, JSON или CSV (`KIND,SCORE,FIRST,SECOND`).
+pub fn write_report<W: Write>(mut writer: W, result: &Reconciliation, format: ReportFormat) -> Result<()> {
+    let ids = |txs: &[Transaction]| txs.iter().map(|t| t.tx_id.to_string()).collect::<Vec<_>>();
+    match format {
+        ReportFormat::Text => {
+            for m in &result.matched {
+                let kind = if m.first.len() == 1 && m.second.len() == 1 { "MATCH" } else { "SPLIT" };
+                writeln!(
+                    writer,
+                    "{} (score {}): TX_ID {} <-> TX_ID {}",
+                    kind,
+                    m.score,
+                    ids(&m.first).join(" + "),
+                    ids(&m.second).join(" + ")
+                )?;
+            }
+            for a in &result.ambiguous {
+                writeln!(
+                    writer,
+                    "AMBIGUOUS (score {}): TX_ID {} from {} file has {} candidates: {}",
+                    a.score,
+                    a.record.tx_id,
+                    if a.side == Side::First { "first" } else { "second" },
+                    a.candidates.len(),
+                    ids(&a.candidates).join(", ")
+                )?;
+            }
+            for tx in &result.unmatched_first {
+                writeln!(writer, "UNMATCHED in first file: TX_ID {}", tx.tx_id)?;
+            }
+            for tx in &result.unmatched_second {
+                writeln!(writer, "UNMATCHED in second file: TX_ID {}", tx.tx_id)?;
+            }
+            writeln!(
+                writer,
+                "Summary: {} matched, {} ambiguous, {} unmatched in first file, {} unmatched in second file.",
+                result.matched.len(),
+                result.ambiguous.len(),
+                result.unmatched_first.len(),
+                result.unmatched_second.len()
+            )?;
+        }
+        ReportFormat::Json => {
+            write!(
+                writer,
+                "{{\"summary\":{{\"matched\":{},\"ambiguous\":{},\"unmatched_first\":{},\"unmatched_second\":{}}},\"matched\":[",
+                result.matched.len(),
+                result.ambiguous.len(),
+                result.unmatched_first.len(),
+                result.unmatched_second.len()
+            )?;
+            for (i, m) in result.matched.iter().enumerate() {
+                if i > 0 {
+                    write!(writer, ",")?;
+                }
+                write!(writer, "{{\"score\":{},\"first\":", m.score)?;
+                write_list(&mut writer, &m.first)?;
+                write!(writer, ",\"second\":")?;
+                write_list(&mut writer, &m.second)?;
+                write!(writer, "}}")?;
+            }
+            write!(writer, "],\"ambiguous\":[")?;
+            for (i, a) in result.ambiguous.iter().enumerate() {
+                if i > 0 {
+                    write!(writer, ",")?;
+                }
+                let side = if a.side == Side::First { "first" } else { "second" };
+                write!(writer, "{{\"score\":{},\"side\":{},\"record\":", a.score, json::string(side))?;
+                json::write_transaction(&mut writer, &a.record)?;
+                write!(writer, ",\"candidates\":")?;
+                write_list(&mut writer, &a.candidates)?;
+                write!(writer, "}}")?;
+            }
+            write!(writer, "],\"unmatched_first\":")?;
+            write_list(&mut writer, &result.unmatched_first)?;
+            write!(writer, ",\"unmatched_second\":")?;
+            write_list(&mut writer, &result.unmatched_second)?;
+            writeln!(writer, "}}")?;
+        }
+        ReportFormat::Csv => {
+            writeln!(writer, "KIND,SCORE,FIRST,SECOND")?;
+            for m in &result.matched {
+                let kind = if m.first.len() == 1 && m.second.len() == 1 { "MATCH" } else { "SPLIT" };
+                writeln!(writer, "{},{},{},{}", kind, m.score, ids(&m.first).join(" "), ids(&m.second).join(" "))?;
+            }
+            for a in &result.ambiguous {
+                let (own, others) = (a.record.tx_id.to_string(), ids(&a.candidates).join(" "));
+                let (first, second) = match a.side {
+                    Side::First => (own, others),
+                    Side::Second => (others, own),
+                };
+                writeln!(writer, "AMBIGUOUS,{},{},{}", a.score, first, second)?;
+            }
+            for tx in &result.unmatched_first {
+                writeln!(writer, "UNMATCHED,,{},", tx.tx_id)?;
+            }
+            for tx in &result.unmatched_second {
+                writeln!(writer, "UNMATCHED,,,{}", tx.tx_id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_list<W: Write>(writer: &mut W, txs: &[Transaction]) -> Result<()> {
+    write!(writer, "[")?;
+    for (i, tx) in txs.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        json::write_transaction(writer, tx)?;
+    }
+    write!(writer, "]")?;
+    Ok(())
+}
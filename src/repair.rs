@@ -0,0 +1,250 @@
+//! Восстановление повреждённого YPBankBin: целые записи переписываются в
+//! новый файл, выброшенное перечисляется в отчёте.
+//!
+//! Файл читается потоком, в памяти держится не больше одной записи. Запись с верной рамкой, но битым
+//! содержимым (CRC, UTF-8) пропускается. Если рамка не сходится (нет `MAGIC`,
+//! `RECORD_SIZE` не совпадает с `DESC_LEN`), ищется следующая сигнатура
+//! записи. Оборванный хвост отбрасывается. У v2 трейлер пересчитывается,
+//! индекс не переносится.
+
+use std::io::{Read, Write};
+use crate::bin_format::{self, Version, FLAG_INDEX, FLAG_RECORD_CRC, FLAG_TRAILER};
+use crate::bin_view::TransactionRef;
+use crate::bin_window::Window;
+use crate::compare::ReportFormat;
+use crate::crc32::crc32;
+use crate::{json, Result, ParserError, Truncation};
+
+/// Почему выброшен участок файла.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// Байты, не похожие на запись.
+    Garbage,
+    /// Рамка записи цела, но содержимое не разбирается.
+    InvalidRecord,
+    /// Не совпала CRC32 записи.
+    CrcMismatch,
+    /// Оборванная последняя запись.
+    Truncated,
+    /// Данные после трейлера.
+    AfterTrailer,
+}
+
+impl DropReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DropReason::Garbage => "garbage",
+            DropReason::InvalidRecord => "invalid-record",
+            DropReason::CrcMismatch => "crc-mismatch",
+            DropReason::Truncated => "truncated",
+            DropReason::AfterTrailer => "after-trailer",
+        }
+    }
+}
+
+/// Выброшенный участок исходного файла.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dropped {
+    pub offset: u64,
+    pub len: u64,
+    /// `TX_ID`, если его удалось прочитать.
+    pub tx_id: Option<u64>,
+    pub reason: DropReason,
+}
+
+/// Результат восстановления.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairReport {
+    pub version: Version,
+    /// Сколько записей переписано.
+    pub kept: u64,
+    /// В порядке файла.
+    pub dropped: Vec<Dropped>,
+    /// Где оборвался файл; этот участок есть и в `dropped`.
+    pub truncation: Option<Truncation>,
+    /// Трейлера v2 не было; в выходном файле он записан заново.
+    pub trailer_missing: bool,
+}
+
+impl RepairReport {
+    pub fn is_clean(&self) -> bool {
+        self.dropped.is_empty() && !self.trailer_missing
+    }
+
+    /// Сколько байт выброшено.
+    pub fn dropped_bytes(&self) -> u64 {
+        self.dropped.iter().map(|d| d.len).sum()
+    }
+
+    /// Отчёт текстом или JSON.
+    pub fn write<W: Write>(&self, mut writer: W, format: ReportFormat) -> Result<()> {
+        match format {
+            ReportFormat::Text => {
+                for d in &self.dropped {
+                    let tx_id = d.tx_id.map_or(String::new(), |id| format!(", TX_ID {}", id));
+                    writeln!(writer, "DROPPED {} at offset {} ({} bytes{})", d.reason.as_str(), d.offset, d.len, tx_id)?;
+                }
+                if let Some(t) = &self.truncation {
+                    writeln!(
+                        writer,
+                        "TRUNCATED record {} at offset {}: field {}, {} bytes missing",
+                        t.record, t.offset, t.field, t.missing
+                    )?;
+                }
+                if self.trailer_missing {
+                    writeln!(writer, "TRAILER missing, rebuilt")?;
+                }
+                writeln!(
+                    writer,
+                    "Kept {} records, dropped {} fragments ({} bytes).",
+                    self.kept,
+                    self.dropped.len(),
+                    self.dropped_bytes()
+                )?;
+            }
+            ReportFormat::Json => {
+                let dropped: Vec<String> = self
+                    .dropped
+                    .iter()
+                    .map(|d| {
+                        format!(
+                            "{{\"offset\":{},\"len\":{},\"tx_id\":{},\"reason\":{}}}",
+                            d.offset,
+                            d.len,
+                            d.tx_id.map_or("null".to_string(), |id| id.to_string()),
+                            json::string(d.reason.as_str())
+                        )
+                    })
+                    .collect();
+                let truncation = self.truncation.as_ref().map_or("null".to_string(), |t| {
+                    format!(
+                        "{{\"record\":{},\"offset\":{},\"field\":{},\"missing\":{}}}",
+                        t.record,
+                        t.offset,
+                        json::string(t.field),
+                        t.missing
+                    )
+                });
+                writeln!(
+                    writer,
+                    "{{\"kept\":{},\"dropped\":[{}],\"truncation\":{},\"trailer_missing\":{}}}",
+                    self.kept,
+                    dropped.join(","),
+                    truncation,
+                    self.trailer_missing
+                )?;
+            }
+            ReportFormat::Csv => {
+                return Err(ParserError::Format("Отчёт о восстановлении выводится как text или json".into()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Переписывает целые записи из `input` в `output` той же версией.
+/// Повреждённый заголовок v2 — ошибка: без него записи не разобрать.
+pub fn repair<R: Read, W: Write>(input: R, output: W) -> Result<RepairReport> {
+    let mut report = RepairReport::default();
+    let mut data = Window::new(input);
+    let mut header = None;
+    if data.peek(4)? == bin_format::FILE_MAGIC {
+        data.advance(4);
+        let mut h = bin_format::read_header(&mut data)?;
+        h.flags &= !FLAG_INDEX;
+        header = Some(h);
+        report.version = Version::V2;
+    }
+    let crc = header.as_ref().is_some_and(|h| h.has(FLAG_RECORD_CRC));
+    let trailer = header.as_ref().is_some_and(|h| h.has(FLAG_TRAILER));
+    let mut writer = match header.clone() {
+        Some(h) => bin_format::Writer::v2(output, h),
+        None => bin_format::Writer::new(output),
+    };
+    let mut seen_trailer = false;
+    let mut records = 0u64;
+
+    while !data.is_at_end()? {
+        let offset = data.pos();
+        let rest = data.peek(32)?;
+        if trailer && rest.starts_with(bin_format::TRAILER_MAGIC) && rest.len() == 32 {
+            // Старый трейлер не проверяется: итоги считаются по сохранённым записям.
+            seen_trailer = true;
+            data.advance(32);
+            let index = data.peek(4)? == crate::bin_index::INDEX_MAGIC;
+            let (len, _) = data.skip_to_end(0)?;
+            if len > 0 && !index {
+                report.dropped.push(Dropped { offset: offset + 32, len, tx_id: None, reason: DropReason::AfterTrailer });
+            }
+            break;
+        }
+        if trailer && rest.starts_with(bin_format::TRAILER_MAGIC) {
+            let len = rest.len() as u64;
+            report.dropped.push(Dropped { offset, len, tx_id: None, reason: DropReason::Truncated });
+            break;
+        }
+        if !rest.starts_with(bin_format::MAGIC) {
+            let (len, _, _) = data.skip_to_record(trailer, 0)?;
+            report.dropped.push(Dropped { offset, len, tx_id: None, reason: DropReason::Garbage });
+            continue;
+        }
+
+        records += 1;
+        // Тело читается, только если RECORD_SIZE сходится с DESC_LEN: иначе
+        // испорченный размер заставил бы держать в памяти случайный объём.
+        let fixed = data.peek(8 + 46)?;
+        let size = fixed.get(4..8).map(|b| u32::from_be_bytes(b.try_into().unwrap_or_default()) as usize);
+        let desc_len = fixed.get(50..54).map(|b| u32::from_be_bytes(b.try_into().unwrap_or_default()) as usize);
+        let record_len = size.map(|size| 8 + size + if crc { 4 } else { 0 });
+        let framed = match (size, desc_len, record_len) {
+            (Some(size), Some(desc_len), Some(len)) if size == 46 + desc_len => Some(len),
+            _ => None,
+        };
+        if let Some(len) = framed {
+            let record = data.peek(len)?;
+            if record.len() == len {
+                let body = &record[8..len - if crc { 4 } else { 0 }];
+                let tx_id = u64::from_be_bytes(body[..8].try_into().unwrap_or_default());
+                let reason = if crc && record[len - 4..] != crc32(body).to_be_bytes() {
+                    Some(DropReason::CrcMismatch)
+                } else {
+                    match TransactionRef::new(body).and_then(|tx| tx.to_transaction()) {
+                        Ok(tx) => {
+                            writer.write(&tx)?;
+                            report.kept += 1;
+                            None
+                        }
+                        Err(_) => Some(DropReason::InvalidRecord),
+                    }
+                };
+                if let Some(reason) = reason {
+                    report.dropped.push(Dropped { offset, len: len as u64, tx_id: Some(tx_id), reason });
+                }
+                data.advance(len);
+                continue;
+            }
+        }
+
+        let (len, head, at_end) = data.skip_to_record(trailer, 16)?;
+        if at_end && record_len.is_none_or(|record_len| record_len as u64 > len) {
+            // Дальше записей нет, а эта не помещается в файл — оборванный хвост.
+            let rest = len as usize;
+            let (field, missing) = match size {
+                None => ("RECORD_SIZE", (8 - rest) as u64),
+                Some(size) if rest < 8 + size => {
+                    (bin_format::body_field(rest - 8), (record_len.unwrap_or(0) - rest) as u64)
+                }
+                Some(_) => ("CRC32", (record_len.unwrap_or(0) - rest) as u64),
+            };
+            report.truncation = Some(Truncation { record: records, offset, field, missing });
+            let tx_id = head.get(8..16).map(|b| u64::from_be_bytes(b.try_into().unwrap_or_default()));
+            report.dropped.push(Dropped { offset, len, tx_id, reason: DropReason::Truncated });
+            break;
+        }
+        report.dropped.push(Dropped { offset, len, tx_id: None, reason: DropReason::InvalidRecord });
+    }
+
+    report.trailer_missing = trailer && !seen_trailer;
+    writer.finish()?;
+    Ok(report)
+}
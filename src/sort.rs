@@ -0,0 +1,220 @@
+//! Сортировка по полям и удаление дубликатов — в памяти или через внешнюю
+//! сортировку для файлов больше оперативной памяти.
+
+use std::collections::HashSet;
+use std::mem;
+use std::str::FromStr;
+use crate::external_sort::{self, Sequenced, SortOptions};
+use crate::field::Field;
+use crate::format::Transactions;
+use crate::{Transaction, Result, ParserError};
+
+/// Что считается дубликатом.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DedupKey {
+    /// Совпадает `TX_ID`.
+    #[default]
+    TxId,
+    /// Совпадают все поля.
+    Record,
+}
+
+impl DedupKey {
+    /// Поля, по которым сортировать, чтобы дубликаты оказались рядом.
+    pub fn fields(self) -> &'static [Field] {
+        match self {
+            DedupKey::TxId => &[Field::TxId],
+            DedupKey::Record => &Field::ALL,
+        }
+    }
+
+    fn same(self, a: &Transaction, b: &Transaction) -> bool {
+        match self {
+            DedupKey::TxId => a.tx_id == b.tx_id,
+            DedupKey::Record => a == b,
+        }
+    }
+}
+
+impl FromStr for DedupKey {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tx_id" | "TX_ID" => Ok(DedupKey::TxId),
+            "record" => Ok(DedupKey::Record),
+            _ => Err(ParserError::Format(format!("Неизвестный ключ дубликатов: {}", s))),
+        }
+    }
+}
+
+/// Какую из повторяющихся транзакций оставить.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    #[default]
+    KeepFirst,
+    KeepLast,
+    /// Любой повтор — ошибка.
+    Error,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "keep-first" | "first" => Ok(DuplicatePolicy::KeepFirst),
+            "keep-last" | "last" => Ok(DuplicatePolicy::KeepLast),
+            "error" => Ok(DuplicatePolicy::Error),
+            _ => Err(ParserError::Format(format!("Неизвестная политика дубликатов: {}", s))),
+        }
+    }
+}
+
+/// Параметры удаления дубликатов.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupOptions {
+    pub key: DedupKey,
+    pub policy: DuplicatePolicy,
+}
+
+fn duplicate_error(tx: &Transaction) -> ParserError {
+    ParserError::Format(format!("Повторяющаяся транзакция TX_ID {}", tx.tx_id))
+}
+
+/// Устойчивая сортировка по полям в памяти.
+pub fn sort(transactions: &mut [Transaction], key: &[Field]) {
+    transactions.sort_by(|a, b| a.cmp_by(b, key));
+}
+
+/// Удаляет дубликаты в памяти, сохраняя порядок: остаётся первое или
+/// последнее вхождение на своём месте.
+pub fn dedup(transactions: Vec<Transaction>, options: &DedupOptions) -> Result<Vec<Transaction>> {
+    match options.key {
+        DedupKey::TxId => dedup_by(transactions, options.policy, |tx| tx.tx_id),
+        DedupKey::Record => dedup_by(transactions, options.policy, |tx| tx.clone()),
+    }
+}
+
+fn dedup_by<K, F>(transactions: Vec<Transaction>, policy: DuplicatePolicy, key: F) -> Result<Vec<Transaction>>
+where
+    K: std::hash::Hash + Eq,
+    F: Fn(&Transaction) -> K,
+{
+    let mut seen = HashSet::new();
+    match policy {
+        DuplicatePolicy::KeepFirst => Ok(transactions.into_iter().filter(|tx| seen.insert(key(tx))).collect()),
+        DuplicatePolicy::KeepLast => {
+            let mut kept: Vec<Transaction> =
+                transactions.into_iter().rev().filter(|tx| seen.insert(key(tx))).collect();
+            kept.reverse();
+            Ok(kept)
+        }
+        DuplicatePolicy::Error => {
+            for tx in &transactions {
+                if !seen.insert(key(tx)) {
+                    return Err(duplicate_error(tx));
+                }
+            }
+            Ok(transactions)
+        }
+    }
+}
+
+/// Удаляет дубликаты из пронумерованного потока, отсортированного по
+/// [`DedupKey::fields`] и номеру (повторы идут подряд в исходном порядке).
+/// Остаётся запись со своим номером. Память — одна транзакция.
+pub fn dedup_sorted<I>(input: I, options: DedupOptions) -> DedupSorted<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<Sequenced>>,
+{
+    DedupSorted { input: input.into_iter(), options, held: None, done: false }
+}
+
+/// Поток без дубликатов, см. [`dedup_sorted`].
+pub struct DedupSorted<I> {
+    input: I,
+    options: DedupOptions,
+    /// Транзакция, которую ещё рано отдавать: следом может идти её повтор.
+    held: Option<Sequenced>,
+    done: bool,
+}
+
+impl<I: Iterator<Item = Result<Sequenced>>> Iterator for DedupSorted<I> {
+    type Item = Result<Sequenced>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            let tx = match self.input.next() {
+                Some(Ok(tx)) => tx,
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.done = true;
+                    return self.held.take().map(Ok);
+                }
+            };
+            let Some(held) = &mut self.held else {
+                self.held = Some(tx);
+                continue;
+            };
+            if !self.options.key.same(&held.1, &tx.1) {
+                return Some(Ok(mem::replace(held, tx)));
+            }
+            match self.options.policy {
+                DuplicatePolicy::KeepFirst => {}
+                DuplicatePolicy::KeepLast => *held = tx,
+                DuplicatePolicy::Error => {
+                    self.done = true;
+                    return Some(Err(duplicate_error(&tx.1)));
+                }
+            }
+        }
+    }
+}
+
+/// Сортирует по `key` (пустой — без сортировки) и удаляет дубликаты.
+///
+/// Без `external` всё делается в памяти, с `external` — внешней сортировкой.
+/// Результат одинаков: остаётся первое или последнее вхождение, равные по
+/// `key` транзакции (и весь поток без `key`) идут в исходном порядке. Для
+/// этого внешняя сортировка несёт номер записи во входе.
+pub fn sort_dedup<'a, I>(
+    input: I,
+    key: &[Field],
+    dedup_options: Option<DedupOptions>,
+    external: Option<&SortOptions>,
+) -> Result<Transactions<'a>>
+where
+    I: IntoIterator<Item = Result<Transaction>>,
+    I::IntoIter: 'a,
+{
+    let Some(sort_options) = external else {
+        let mut transactions: Vec<Transaction> = input.into_iter().collect::<Result<_>>()?;
+        if let Some(options) = &dedup_options {
+            transactions = dedup(transactions, options)?;
+        }
+        sort(&mut transactions, key);
+        return Ok(Box::new(transactions.into_iter().map(Ok)));
+    };
+
+    if dedup_options.is_none() && key.is_empty() {
+        return Ok(Box::new(input.into_iter()));
+    }
+    let numbered = input.into_iter().zip(0u64..).map(|(tx, seq)| tx.map(|tx| (seq, tx)));
+    let stream: Box<dyn Iterator<Item = Result<Sequenced>> + 'a> = match dedup_options {
+        Some(options) => {
+            let grouped = external_sort::sort_sequenced(numbered, options.key.fields(), sort_options)?;
+            Box::new(dedup_sorted(grouped, options))
+        }
+        None => Box::new(numbered),
+    };
+    // Пустой `key` — сортировка только по номеру, то есть исходный порядок.
+    let sorted = external_sort::sort_sequenced(stream, key, sort_options)?;
+    Ok(Box::new(sorted.map(|record| record.map(|(_, tx)| tx))))
+}
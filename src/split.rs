@@ -0,0 +1,250 @@
+//! Разбиение потока на файлы по ключу (пользователь, тип, статус, дата)
+//! и/или по размеру частей.
+//!
+//! Имена файлов строятся по шаблону с подстановками `{key}` (значение
+//! ключа), `{n}` (номер части внутри ключа, с 1) и `{ext}`. Вход читается
+//! потоком; открытыми держится не больше `max_open` файлов, остальные
+//! закрываются и при следующей записи дописываются. Части пишутся в
+//! построчных форматах и `bin`; `compact` и `columnar` не поддерживаются.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::calendar::Date;
+use crate::format::Format;
+use crate::{Transaction, Result, ParserError};
+
+/// Сколько файлов держать открытыми по умолчанию.
+pub const DEFAULT_MAX_OPEN: usize = 64;
+
+/// Ключ разбиения.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitKey {
+    /// Без ключа — только ограничения размера.
+    #[default]
+    None,
+    /// Транзакция попадает в файлы отправителя и получателя (кроме `0`).
+    User,
+    Type,
+    Status,
+    /// Местная дата `TIMESTAMP`, `YYYY-MM-DD`.
+    Date,
+}
+
+impl FromStr for SplitKey {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(SplitKey::None),
+            "user" => Ok(SplitKey::User),
+            "type" => Ok(SplitKey::Type),
+            "status" => Ok(SplitKey::Status),
+            "date" | "day" => Ok(SplitKey::Date),
+            _ => Err(ParserError::Format(format!("Неизвестный ключ разбиения: {}", s))),
+        }
+    }
+}
+
+/// Параметры разбиения.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitOptions {
+    pub key: SplitKey,
+    /// Сдвиг от UTC в минутах для [`SplitKey::Date`].
+    pub utc_offset_minutes: i32,
+    /// Не больше стольких записей в части.
+    pub max_records: Option<u64>,
+    /// Не больше стольких байт в части; запись больше лимита занимает часть целиком.
+    pub max_bytes: Option<u64>,
+    /// Шаблон имени; `None` — по умолчанию для выбранного ключа и ограничений.
+    pub template: Option<String>,
+    pub format: Format,
+    pub max_open: usize,
+}
+
+impl Default for SplitOptions {
+    fn default() -> Self {
+        SplitOptions {
+            key: SplitKey::None,
+            utc_offset_minutes: 0,
+            max_records: None,
+            max_bytes: None,
+            template: None,
+            format: Format::Csv,
+            max_open: DEFAULT_MAX_OPEN,
+        }
+    }
+}
+
+impl SplitOptions {
+    fn limited(&self) -> bool {
+        self.max_records.is_some() || self.max_bytes.is_some()
+    }
+
+    /// Шаблон имени, проверенный на нужные подстановки.
+    fn template(&self) -> Result<String> {
+        let template = match (&self.template, self.key != SplitKey::None, self.limited()) {
+            (Some(t), _, _) => t.clone(),
+            (None, true, true) => "{key}_{n}.{ext}".into(),
+            (None, true, false) => "{key}.{ext}".into(),
+            (None, false, true) => "part_{n}.{ext}".into(),
+            (None, false, false) => {
+                return Err(ParserError::Format("Укажите ключ разбиения или ограничение размера".into()))
+            }
+        };
+        if self.key != SplitKey::None && !template.contains("{key}") {
+            return Err(ParserError::Format(format!("В шаблоне нет {{key}}: {}", template)));
+        }
+        if self.limited() && !template.contains("{n}") {
+            return Err(ParserError::Format(format!("В шаблоне нет {{n}}: {}", template)));
+        }
+        Ok(template)
+    }
+
+    fn keys(&self, tx: &Transaction) -> Vec<String> {
+        match self.key {
+            SplitKey::None => vec![String::new()],
+            SplitKey::User => {
+                let mut users = vec![tx.from_user_id, tx.to_user_id];
+                users.retain(|&u| u != 0);
+                users.dedup();
+                users.iter().map(u64::to_string).collect()
+            }
+            SplitKey::Type => vec![tx.tx_type.as_str().to_string()],
+            SplitKey::Status => vec![tx.status.as_str().to_string()],
+            SplitKey::Date => vec![Date::from_timestamp(tx.timestamp, self.utc_offset_minutes).to_string()],
+        }
+    }
+}
+
+/// Итог по одному созданному файлу.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitFile {
+    pub path: PathBuf,
+    pub records: u64,
+    pub bytes: u64,
+}
+
+/// Текущая часть одного ключа.
+struct Part {
+    /// Индекс в списке итогов.
+    file: usize,
+    number: u64,
+    writer: Option<BufWriter<File>>,
+    last_used: u64,
+}
+
+/// Раскладывает поток по файлам в `dir`, возвращает созданные файлы в порядке создания.
+/// Форматы `compact` и `columnar` отклоняются до создания файлов.
+pub fn split<I>(input: I, dir: &Path, options: &SplitOptions) -> Result<Vec<SplitFile>>
+where
+    I: IntoIterator<Item = Result<Transaction>>,
+{
+    if matches!(options.format, Format::Compact | Format::Columnar) {
+        // Оба формата держат общий контекст на весь файл (словарь, группы строк),
+        // а части пишутся по записи и дописываются после переоткрытия.
+        return Err(ParserError::Format(format!("Разбиение не поддерживается для формата {:?}", options.format)));
+    }
+    let template = options.template()?;
+    let mut files: Vec<SplitFile> = Vec::new();
+    let mut parts: HashMap<String, Part> = HashMap::new();
+    let mut paths = HashSet::new();
+    let mut open = 0;
+    let mut clock = 0;
+    let mut encoded = Vec::new();
+
+    for tx in input {
+        let tx = tx?;
+        for key in options.keys(&tx) {
+            clock += 1;
+            let written = parts.get(&key).map_or(0, |p| files[p.file].records);
+            encode(&mut encoded, options.format, &tx, written)?;
+
+            let rotate = match parts.get(&key) {
+                None => true,
+                Some(part) => {
+                    let current = &files[part.file];
+                    options.max_records.is_some_and(|max| current.records >= max)
+                        || options.max_bytes.is_some_and(|max| current.bytes + encoded.len() as u64 > max)
+                }
+            };
+            if rotate {
+                let number = parts.get(&key).map_or(1, |p| p.number + 1);
+                if let Some(mut old) = parts.remove(&key) {
+                    if let Some(mut writer) = old.writer.take() {
+                        writer.flush()?;
+                        open -= 1;
+                    }
+                }
+                let path = dir.join(render(&template, &key, number, options.format));
+                if !paths.insert(path.clone()) {
+                    return Err(ParserError::Format(format!("Шаблон дал одно имя двум частям: {}", path.display())));
+                }
+                files.push(SplitFile { path, records: 0, bytes: 0 });
+                parts.insert(key.clone(), Part { file: files.len() - 1, number, writer: None, last_used: 0 });
+                // Новый файл: запись кодируется заново (заголовок CSV, нумерация с 1).
+                encode(&mut encoded, options.format, &tx, 0)?;
+            }
+
+            if parts[&key].writer.is_none() && open >= options.max_open.max(1) {
+                close_least_recent(&mut parts, &mut open)?;
+            }
+            let Some(part) = parts.get_mut(&key) else { continue };
+            if part.writer.is_none() {
+                let file = &files[part.file];
+                let handle = if file.records == 0 {
+                    File::create(&file.path)?
+                } else {
+                    OpenOptions::new().append(true).open(&file.path)?
+                };
+                part.writer = Some(BufWriter::new(handle));
+                open += 1;
+            }
+            part.last_used = clock;
+            if let Some(writer) = &mut part.writer {
+                writer.write_all(&encoded)?;
+            }
+            let file = &mut files[part.file];
+            file.records += 1;
+            file.bytes += encoded.len() as u64;
+        }
+    }
+
+    for part in parts.values_mut() {
+        if let Some(mut writer) = part.writer.take() {
+            writer.flush()?;
+        }
+    }
+    Ok(files)
+}
+
+/// Запись в формате как продолжение файла, где уже `written` записей.
+fn encode(buf: &mut Vec<u8>, format: Format, tx: &Transaction, written: u64) -> Result<()> {
+    buf.clear();
+    let mut writer = format.appending_writer(&mut *buf, written as usize);
+    writer.write(tx)?;
+    writer.finish()
+}
+
+fn close_least_recent(parts: &mut HashMap<String, Part>, open: &mut usize) -> Result<()> {
+    let oldest = parts
+        .values_mut()
+        .filter(|p| p.writer.is_some())
+        .min_by_key(|p| p.last_used);
+    if let Some(part) = oldest {
+        if let Some(mut writer) = part.writer.take() {
+            writer.flush()?;
+            *open -= 1;
+        }
+    }
+    Ok(())
+}
+
+fn render(template: &str, key: &str, number: u64, format: Format) -> String {
+    template
+        .replace("{key}", key)
+        .replace("{n}", &number.to_string())
+        .replace("{ext}", format.extension())
+}
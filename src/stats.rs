@@ -0,0 +1,267 @@
+//! Сводная статистика по файлу за один проход.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Seek, Write};
+use crate::bin_view::TransactionRef;
+use crate::columnar_format;
+use crate::compare::ReportFormat;
+use crate::field::Field;
+use crate::filter::Filter;
+use crate::{json, Transaction, TxType, TxStatus, Result, ParserError};
+
+/// Сколько лидеров по отправителям и получателям показывать по умолчанию.
+pub const DEFAULT_TOP: usize = 10;
+
+/// Поля, которые нужны статистике: `TX_ID` и описание не читаются.
+pub const COLUMNS: [Field; 6] =
+    [Field::TxType, Field::FromUserId, Field::ToUserId, Field::Amount, Field::Timestamp, Field::Status];
+
+/// Количество и сумма.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub count: u64,
+    /// Сумма в `i128`, чтобы не переполниться на больших файлах.
+    pub sum: i128,
+}
+
+impl Totals {
+    pub(crate) fn add(&mut self, amount: i64) {
+        self.count += 1;
+        self.sum += amount as i128;
+    }
+}
+
+/// Пользователь в рейтинге отправителей или получателей.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopUser {
+    pub user_id: u64,
+    pub totals: Totals,
+}
+
+/// Накопитель статистики. Память растёт с числом пользователей; медиана
+/// включается отдельно ([`Stats::with_median`]) и добавляет 8 байт на
+/// транзакцию.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    total: Totals,
+    by_type: BTreeMap<TxType, Totals>,
+    by_status: BTreeMap<TxStatus, Totals>,
+    min_amount: Option<i64>,
+    max_amount: Option<i64>,
+    /// Суммы для медианы; `None` — медиана не считается.
+    amounts: Option<Vec<i64>>,
+    first_timestamp: Option<u64>,
+    last_timestamp: Option<u64>,
+    users: HashSet<u64>,
+    senders: HashMap<u64, Totals>,
+    receivers: HashMap<u64, Totals>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Накопитель, который хранит суммы для медианы.
+    pub fn with_median() -> Self {
+        Stats { amounts: Some(Vec::new()), ..Self::default() }
+    }
+
+    /// Учитывает одну транзакцию. Пользователь `0` (внешний счёт) не считается.
+    pub fn add(&mut self, tx: &Transaction) {
+        self.record(tx.tx_type, tx.status, tx.amount, tx.timestamp, tx.from_user_id, tx.to_user_id);
+    }
+
+    /// Как [`Stats::add`], но без разбора описания.
+    pub fn add_ref(&mut self, tx: &TransactionRef<'_>) {
+        self.record(tx.tx_type(), tx.status(), tx.amount(), tx.timestamp(), tx.from_user_id(), tx.to_user_id());
+    }
+
+    fn record(&mut self, tx_type: TxType, status: TxStatus, amount: i64, timestamp: u64, from: u64, to: u64) {
+        self.total.add(amount);
+        self.by_type.entry(tx_type).or_default().add(amount);
+        self.by_status.entry(status).or_default().add(amount);
+        self.min_amount = Some(self.min_amount.map_or(amount, |a| a.min(amount)));
+        self.max_amount = Some(self.max_amount.map_or(amount, |a| a.max(amount)));
+        if let Some(amounts) = &mut self.amounts {
+            amounts.push(amount);
+        }
+        self.first_timestamp = Some(self.first_timestamp.map_or(timestamp, |t| t.min(timestamp)));
+        self.last_timestamp = Some(self.last_timestamp.map_or(timestamp, |t| t.max(timestamp)));
+        if from != 0 {
+            self.users.insert(from);
+            self.senders.entry(from).or_default().add(amount);
+        }
+        if to != 0 {
+            self.users.insert(to);
+            self.receivers.entry(to).or_default().add(amount);
+        }
+    }
+
+    /// Учитывает весь поток.
+    pub fn add_all<I>(&mut self, transactions: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<Transaction>>,
+    {
+        for tx in transactions {
+            self.add(&tx?);
+        }
+        Ok(())
+    }
+
+    /// Учитывает колоночный файл: читаются только [`COLUMNS`] (и поля
+    /// фильтра), группы строк отсекаются по фильтру.
+    pub fn add_columnar<R: Read + Seek>(&mut self, reader: R, filter: Option<Filter>) -> Result<()> {
+        let reader = columnar_format::Reader::seekable(reader).columns(&COLUMNS);
+        match filter {
+            Some(filter) => self.add_all(reader.filter(filter)),
+            None => self.add_all(reader),
+        }
+    }
+
+    pub fn total(&self) -> Totals {
+        self.total
+    }
+
+    pub fn by_type(&self) -> &BTreeMap<TxType, Totals> {
+        &self.by_type
+    }
+
+    pub fn by_status(&self) -> &BTreeMap<TxStatus, Totals> {
+        &self.by_status
+    }
+
+    pub fn min_amount(&self) -> Option<i64> {
+        self.min_amount
+    }
+
+    pub fn max_amount(&self) -> Option<i64> {
+        self.max_amount
+    }
+
+    /// Среднее, округлённое к нулю.
+    pub fn mean_amount(&self) -> Option<i64> {
+        (self.total.count > 0).then(|| (self.total.sum / self.total.count as i128) as i64)
+    }
+
+    /// Медиана; для чётного числа — среднее двух центральных, округлённое к нулю.
+    /// `None`, если транзакций нет или накопитель создан без [`Stats::with_median`].
+    pub fn median_amount(&self) -> Option<i64> {
+        let amounts = self.amounts.as_ref().filter(|a| !a.is_empty())?;
+        let mut sorted = amounts.clone();
+        sorted.sort_unstable();
+        let mid = sorted.len() / 2;
+        Some(if sorted.len() % 2 == 1 {
+            sorted[mid]
+        } else {
+            ((sorted[mid - 1] as i128 + sorted[mid] as i128) / 2) as i64
+        })
+    }
+
+    /// Самый ранний и самый поздний `TIMESTAMP`.
+    pub fn time_range(&self) -> Option<(u64, u64)> {
+        self.first_timestamp.zip(self.last_timestamp)
+    }
+
+    /// Число различных пользователей (кроме `0`).
+    pub fn distinct_users(&self) -> usize {
+        self.users.len()
+    }
+
+    /// Лидеры по сумме отправленного.
+    pub fn top_senders(&self, n: usize) -> Vec<TopUser> {
+        top(&self.senders, n)
+    }
+
+    /// Лидеры по сумме полученного.
+    pub fn top_receivers(&self, n: usize) -> Vec<TopUser> {
+        top(&self.receivers, n)
+    }
+
+    /// Пишет статистику таблицей (`Text`) или JSON.
+    pub fn write<W: Write>(&self, writer: W, format: ReportFormat, top_n: usize) -> Result<()> {
+        match format {
+            ReportFormat::Text => self.write_text(writer, top_n),
+            ReportFormat::Json => self.write_json(writer, top_n),
+            ReportFormat::Csv => Err(ParserError::Format("Статистика выводится как text или json".into())),
+        }
+    }
+
+    fn write_text<W: Write>(&self, mut writer: W, top_n: usize) -> Result<()> {
+        let opt = |v: Option<i64>| v.map_or_else(|| "-".to_string(), |v| v.to_string());
+        writeln!(writer, "{:<12} {:>12} {:>24}", "", "COUNT", "SUM")?;
+        writeln!(writer, "{:<12} {:>12} {:>24}", "TOTAL", self.total.count, self.total.sum)?;
+        for (tx_type, t) in &self.by_type {
+            writeln!(writer, "{:<12} {:>12} {:>24}", tx_type.as_str(), t.count, t.sum)?;
+        }
+        for (status, t) in &self.by_status {
+            writeln!(writer, "{:<12} {:>12} {:>24}", status.as_str(), t.count, t.sum)?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, "{:<16} {}", "MIN AMOUNT", opt(self.min_amount()))?;
+        writeln!(writer, "{:<16} {}", "MAX AMOUNT", opt(self.max_amount()))?;
+        writeln!(writer, "{:<16} {}", "MEAN AMOUNT", opt(self.mean_amount()))?;
+        if self.amounts.is_some() {
+            writeln!(writer, "{:<16} {}", "MEDIAN AMOUNT", opt(self.median_amount()))?;
+        }
+        match self.time_range() {
+            Some((from, to)) => writeln!(writer, "{:<16} {} .. {}", "TIME RANGE", from, to)?,
+            None => writeln!(writer, "{:<16} -", "TIME RANGE")?,
+        }
+        writeln!(writer, "{:<16} {}", "DISTINCT USERS", self.distinct_users())?;
+        for (title, users) in [("TOP SENDERS", self.top_senders(top_n)), ("TOP RECEIVERS", self.top_receivers(top_n))] {
+            writeln!(writer)?;
+            writeln!(writer, "{:<20} {:>12} {:>24}", title, "COUNT", "SUM")?;
+            for u in users {
+                writeln!(writer, "{:<20} {:>12} {:>24}", u.user_id, u.totals.count, u.totals.sum)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_json<W: Write>(&self, mut writer: W, top_n: usize) -> Result<()> {
+        let totals = |t: &Totals| format!("{{\"count\":{},\"sum\":{}}}", t.count, t.sum);
+        let opt = |v: Option<i64>| v.map_or_else(|| "null".to_string(), |v| v.to_string());
+        let group = |items: Vec<(&str, &Totals)>| {
+            items
+                .into_iter()
+                .map(|(name, t)| format!("{}:{}", json::string(name), totals(t)))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let users = |list: Vec<TopUser>| {
+            list.iter()
+                .map(|u| format!("{{\"user_id\":{},\"count\":{},\"sum\":{}}}", u.user_id, u.totals.count, u.totals.sum))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let time_range = match self.time_range() {
+            Some((from, to)) => format!("{{\"from\":{},\"to\":{}}}", from, to),
+            None => "null".to_string(),
+        };
+        writeln!(
+            writer,
+            "{{\"total\":{},\"by_type\":{{{}}},\"by_status\":{{{}}},\"amount\":{{\"min\":{},\"max\":{},\"mean\":{},\"median\":{}}},\"time_range\":{},\"distinct_users\":{},\"top_senders\":[{}],\"top_receivers\":[{}]}}",
+            totals(&self.total),
+            group(self.by_type.iter().map(|(k, t)| (k.as_str(), t)).collect()),
+            group(self.by_status.iter().map(|(k, t)| (k.as_str(), t)).collect()),
+            opt(self.min_amount()),
+            opt(self.max_amount()),
+            opt(self.mean_amount()),
+            opt(self.median_amount()),
+            time_range,
+            self.distinct_users(),
+            users(self.top_senders(top_n)),
+            users(self.top_receivers(top_n)),
+        )?;
+        Ok(())
+    }
+}
+
+/// По убыванию суммы, при равенстве — по возрастанию `USER_ID`.
+fn top(map: &HashMap<u64, Totals>, n: usize) -> Vec<TopUser> {
+    let mut users: Vec<TopUser> = map.iter().map(|(&user_id, &totals)| TopUser { user_id, totals }).collect();
+    users.sort_by(|a, b| b.totals.sum.cmp(&a.totals.sum).then(a.user_id.cmp(&b.user_id)));
+    users.truncate(n);
+    users
+}
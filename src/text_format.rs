@@ -1,37 +1,64 @@
 //! Блоки «ключ: значение», пустая строка между. # — комментарий.
 
-use std::io::{BufRead, BufReader, Write, Read};
+use std::io::{BufRead, BufReader, Lines, Write, Read};
 use crate::{Transaction, TxType, TxStatus, Result, ParserError};
 
 /// Блок до пустой строки = одна транзакция.
 pub fn from_read<R: Read>(reader: R) -> Result<Vec<Transaction>> {
-    let mut transactions = Vec::new();
-    let buf_reader = BufReader::new(reader);
-    let mut current_block = Vec::new();
-
-    for line_result in buf_reader.lines() {
-        let line = line_result?;
-        let trimmed = line.trim();
-
-        if trimmed.is_empty() {
-            if !current_block.is_empty() {
-                transactions.push(parse_block(&current_block)?);
-                current_block.clear();
+    Reader::new(reader).collect()
+}
+
+/// Потоковое чтение: по одному блоку за шаг, после ошибки итерация заканчивается.
+pub struct Reader<R: Read> {
+    lines: Lines<BufReader<R>>,
+    done: bool,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Reader { lines: BufReader::new(reader).lines(), done: false }
+    }
+
+    fn next_block(&mut self) -> Result<Option<Transaction>> {
+        let mut current_block = Vec::new();
+        for line_result in self.lines.by_ref() {
+            let line = line_result?;
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() {
+                if !current_block.is_empty() {
+                    return parse_block(&current_block).map(Some);
+                }
+            } else if !trimmed.starts_with('#') {
+                current_block.push(line);
             }
-        } else if !trimmed.starts_with('#') {
-            current_block.push(line);
         }
-    }
 
-    if !current_block.is_empty() {
-        transactions.push(parse_block(&current_block)?);
+        if current_block.is_empty() {
+            Ok(None)
+        } else {
+            parse_block(&current_block).map(Some)
+        }
     }
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Transaction>;
 
-    Ok(transactions)
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_block().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
 }
 
 /// Строки KEY: value → Transaction.
-fn parse_block(lines: &[String]) -> Result<Transaction> {
+pub(crate) fn parse_block(lines: &[String]) -> Result<Transaction> {
     let mut tx_id = None;
     let mut tx_type = None;
     let mut from_user_id = None;
@@ -50,22 +77,12 @@ fn parse_block(lines: &[String]) -> Result<Transaction> {
 
         match key {
             "TX_ID" => tx_id = Some(value.parse::<u64>()?),
-            "TX_TYPE" => tx_type = Some(match value {
-                "DEPOSIT" => TxType::Deposit,
-                "TRANSFER" => TxType::Transfer,
-                "WITHDRAWAL" => TxType::Withdrawal,
-                _ => return Err(ParserError::Format(format!("Неизвестный тип: {}", value))),
-            }),
+            "TX_TYPE" => tx_type = Some(value.parse::<TxType>()?),
             "FROM_USER_ID" => from_user_id = Some(value.parse::<u64>()?),
             "TO_USER_ID" => to_user_id = Some(value.parse::<u64>()?),
             "AMOUNT" => amount = Some(value.parse::<i64>()?),
             "TIMESTAMP" => timestamp = Some(value.parse::<u64>()?),
-            "STATUS" => status = Some(match value {
-                "SUCCESS" => TxStatus::Success,
-                "FAILURE" => TxStatus::Failure,
-                "PENDING" => TxStatus::Pending,
-                _ => return Err(ParserError::Format(format!("Неизвестный статус: {}", value))),
-            }),
+            "STATUS" => status = Some(value.parse::<TxStatus>()?),
             "DESCRIPTION" => description = Some(value.trim_matches('"').to_string()),
             _ => {}
         }
@@ -84,26 +101,53 @@ fn parse_block(lines: &[String]) -> Result<Transaction> {
 }
 
 /// Пишем блоками, между блоками пустая строка.
-pub fn write_to<W: Write>(mut writer: W, transactions: &[Transaction]) -> Result<()> {
-    for (i, tx) in transactions.iter().enumerate() {
-        writeln!(writer, "# Запись {}", i + 1)?;
-        writeln!(writer, "TX_ID: {}", tx.tx_id)?;
-        writeln!(writer, "TX_TYPE: {}", match tx.tx_type {
-            TxType::Deposit => "DEPOSIT",
-            TxType::Transfer => "TRANSFER",
-            TxType::Withdrawal => "WITHDRAWAL",
-        })?;
-        writeln!(writer, "FROM_USER_ID: {}", tx.from_user_id)?;
-        writeln!(writer, "TO_USER_ID: {}", tx.to_user_id)?;
-        writeln!(writer, "AMOUNT: {}", tx.amount)?;
-        writeln!(writer, "TIMESTAMP: {}", tx.timestamp)?;
-        writeln!(writer, "STATUS: {}", match tx.status {
-            TxStatus::Success => "SUCCESS",
-            TxStatus::Failure => "FAILURE",
-            TxStatus::Pending => "PENDING",
-        })?;
-        writeln!(writer, "DESCRIPTION: \"{}\"", tx.description)?;
-        writeln!(writer)?;
+pub fn write_to<W: Write>(writer: W, transactions: &[Transaction]) -> Result<()> {
+    let mut writer = Writer::new(writer);
+    for tx in transactions {
+        writer.write(tx)?;
     }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Потоковая запись; нумерация блоков продолжается между вызовами.
+pub struct Writer<W: Write> {
+    writer: W,
+    written: usize,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Writer { writer, written: 0 }
+    }
+
+    /// Продолжает файл, в котором уже `written` записей: нумерация идёт дальше.
+    pub fn appending(writer: W, written: usize) -> Self {
+        Writer { writer, written }
+    }
+
+    pub fn write(&mut self, tx: &Transaction) -> Result<()> {
+        self.written += 1;
+        write_block(&mut self.writer, self.written, tx)
+    }
+
+    /// Сбрасывает буферы и возвращает исходный writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn write_block<W: Write>(writer: &mut W, number: usize, tx: &Transaction) -> Result<()> {
+    writeln!(writer, "# Запись {}", number)?;
+    writeln!(writer, "TX_ID: {}", tx.tx_id)?;
+    writeln!(writer, "TX_TYPE: {}", tx.tx_type.as_str())?;
+    writeln!(writer, "FROM_USER_ID: {}", tx.from_user_id)?;
+    writeln!(writer, "TO_USER_ID: {}", tx.to_user_id)?;
+    writeln!(writer, "AMOUNT: {}", tx.amount)?;
+    writeln!(writer, "TIMESTAMP: {}", tx.timestamp)?;
+    writeln!(writer, "STATUS: {}", tx.status.as_str())?;
+    writeln!(writer, "DESCRIPTION: \"{}\"", tx.description)?;
+    writeln!(writer)?;
     Ok(())
 }